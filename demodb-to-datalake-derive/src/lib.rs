@@ -0,0 +1,523 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Expr, Fields, GenericArgument,
+    PathArguments, Type,
+};
+
+/// Generates `schema()`, `to_record_batch()`, `to_df()`, the string formatter and both
+/// table worker traits for a `FromRow` struct.
+///
+/// ```ignore
+/// #[derive(Debug, Default, FromRow, Serialize, TableWorker)]
+/// #[table_worker(table = "tickets")]
+/// pub struct Tickets {
+///     pub ticket_no: String,
+///     #[table_worker(decimal(10, 2))]
+///     pub amount: Option<Decimal>,
+///     #[table_worker(flatten)]
+///     pub contact_data: Option<Json<ContactData>>,
+/// }
+/// ```
+///
+/// Field types map to Arrow through `ArrowColumn`, `decimal(precision, scale)` stores a
+/// `Decimal` as `Decimal128` and `flatten` lays a `Json<T: JsonFields>` out as a struct column.
+///
+/// The string formatter prints `name: value` pairs, `#[table_worker(format = path)]` on the
+/// struct replaces it for rows selecting every field with `fn(&PgRow) -> Result<String, AppError>`.
+///
+/// Record batches hold the columns a query returns, as postgres describes them, so queries
+/// selecting only some of the fields work too. Exports lay the columns out as the `JsonLayout`
+/// they are given, queries use the default one.
+#[proc_macro_derive(TableWorker, attributes(table_worker))]
+pub fn derive_table_worker(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+enum Column {
+    Default,
+    Decimal(Box<(Expr, Expr)>),
+    Flatten,
+}
+
+struct FieldDef {
+    ident: syn::Ident,
+    name: String,
+    ty: Type,
+    inner: Type,
+    nullable: bool,
+    column: Column,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let TableAttrs { table, format } = table_attrs(&input)?;
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new(
+            input.span(),
+            "TableWorker can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new(
+            data.fields.span(),
+            "TableWorker requires named fields",
+        ));
+    };
+    let fields = fields
+        .named
+        .iter()
+        .map(field_def)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let p = quote!(::demodb_to_datalake::__private);
+
+    let schema_fields = fields.iter().map(|field| {
+        let name = &field.name;
+        let inner = &field.inner;
+        let nullable = field.nullable;
+        let data_type = match &field.column {
+            Column::Default => quote!(<#inner as #p::ArrowColumn>::data_type(layout)),
+            Column::Decimal(args) => {
+                let (precision, scale) = args.as_ref();
+                quote!(#p::DataType::Decimal128(#precision, #scale))
+            }
+            Column::Flatten => quote!(<#inner as #p::FlattenColumn>::data_type(layout)),
+        };
+        quote!(#p::Field::new(#name, #data_type, #nullable))
+    });
+
+    let columns = fields.iter().map(|field| {
+        let field_ident = &field.ident;
+        let array = to_array(field, &p);
+        let values = match field.nullable {
+            true => quote!(r.#field_ident.as_ref()),
+            false => quote!(Some(&r.#field_ident)),
+        };
+        quote!({
+            let values = records.iter().map(|r| #values).collect::<Vec<_>>();
+            #array
+        })
+    });
+
+    // decodes the columns straight from the rows, a column missing from them is an error
+    let row_columns = fields.iter().enumerate().map(|(index, field)| {
+        let index = proc_macro2::Literal::usize_unsuffixed(index);
+        let name = &field.name;
+        let ty = &field.ty;
+        let array = to_array(field, &p);
+        let values = match field.nullable {
+            true => quote!(r.as_ref()),
+            false => quote!(Some(r)),
+        };
+        quote!(#index => {
+            let records = rows
+                .iter()
+                .map(|row| #p::sqlx::Row::try_get::<#ty, _>(row, #name))
+                .collect::<Result<Vec<_>, _>>()?;
+            let values = records.iter().map(|r| #values).collect::<Vec<_>>();
+            #array
+        })
+    });
+
+    let field_count = fields.len();
+    let format_full = format.map(|format| {
+        quote!(if projection.iter().copied().eq(0..#field_count) {
+            return #format(row);
+        })
+    });
+
+    let formatters = fields.iter().map(|field| {
+        let name = &field.name;
+        let inner = &field.inner;
+        quote!((#name, #p::format_column::<#inner>))
+    });
+
+    Ok(quote! {
+        impl AsRef<str> for #ident {
+            fn as_ref(&self) -> &str {
+                #table
+            }
+        }
+
+        impl #ident {
+            pub fn schema() -> #p::Schema {
+                Self::schema_with(#p::JsonLayout::default())
+            }
+
+            pub fn schema_with(layout: #p::JsonLayout) -> #p::Schema {
+                #p::Schema::new(vec![#(#schema_fields),*])
+            }
+
+            fn to_record_batch(records: &[Self]) -> Result<#p::RecordBatch, #p::AppError> {
+                Self::to_record_batch_with(records, #p::JsonLayout::default())
+            }
+
+            /// Record batch of the `projection` fields decoded from `rows`.
+            fn rows_to_record_batch(
+                rows: &[#p::PgRow],
+                projection: &[usize],
+                layout: #p::JsonLayout,
+            ) -> Result<#p::RecordBatch, #p::AppError> {
+                let schema = #p::Arc::new(Self::schema_with(layout).project(projection)?);
+                let columns = projection
+                    .iter()
+                    .map(|&index| -> Result<#p::ArrayRef, #p::AppError> {
+                        Ok(match index {
+                            #(#row_columns)*
+                            _ => unreachable!("projection index {} out of the schema", index),
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(#p::RecordBatch::try_new(schema, columns)?)
+            }
+
+            async fn projection(
+                pool: &#p::PgPool,
+                query: &str,
+            ) -> Result<Vec<usize>, #p::AppError> {
+                #p::query_projection(pool, query, #table, &Self::schema()).await
+            }
+
+            fn to_record_batch_with(
+                records: &[Self],
+                layout: #p::JsonLayout,
+            ) -> Result<#p::RecordBatch, #p::AppError> {
+                let schema = #p::Arc::new(Self::schema_with(layout));
+                let columns: Vec<#p::ArrayRef> = vec![#(#columns),*];
+                Ok(#p::RecordBatch::try_new(schema, columns)?)
+            }
+
+            pub fn to_df(
+                ctx: &#p::SessionContext,
+                records: &[Self],
+            ) -> Result<#p::DataFrame, #p::AppError> {
+                Self::to_df_with(ctx, records, #p::JsonLayout::default())
+            }
+
+            pub fn to_df_with(
+                ctx: &#p::SessionContext,
+                records: &[Self],
+                layout: #p::JsonLayout,
+            ) -> Result<#p::DataFrame, #p::AppError> {
+                let batch = Self::to_record_batch_with(records, layout)?;
+                let df = ctx.read_batch(batch)?;
+                Ok(df)
+            }
+
+            fn format_row(
+                row: &#p::PgRow,
+                projection: &[usize],
+            ) -> Result<String, #p::AppError> {
+                #format_full
+                type Formatter = fn(&#p::PgRow, &str) -> Result<String, #p::AppError>;
+                let formatters: &[(&str, Formatter)] = &[#(#formatters),*];
+                let values = projection
+                    .iter()
+                    .map(|&index| {
+                        let (name, format) = formatters[index];
+                        Ok(format!("{}: {}", name, format(row, name)?))
+                    })
+                    .collect::<Result<Vec<_>, #p::AppError>>()?;
+                Ok(values.join(", "))
+            }
+        }
+
+        #[#p::async_trait]
+        impl #p::TableWorkerDyn for #ident {
+            async fn query_table(
+                &self,
+                pool: &#p::PgPool,
+                query: &str,
+            ) -> Result<(), #p::AppError> {
+                <Self as #p::TableWorkerStatic>::query_table(pool, query).await
+            }
+
+            async fn query_table_to_string(
+                &self,
+                pool: &#p::PgPool,
+                query: &str,
+            ) -> Result<Vec<String>, #p::AppError> {
+                <Self as #p::TableWorkerStatic>::query_table_to_string(pool, query).await
+            }
+
+            async fn query_table_to_json(
+                &self,
+                pool: &#p::PgPool,
+                query: &str,
+            ) -> Result<String, #p::AppError> {
+                <Self as #p::TableWorkerStatic>::query_table_to_json(pool, query).await
+            }
+
+            async fn query_table_to_df(
+                &self,
+                pool: &#p::PgPool,
+                query: &str,
+                ctx: &#p::SessionContext,
+            ) -> Result<#p::DataFrame, #p::AppError> {
+                <Self as #p::TableWorkerStatic>::query_table_to_df(pool, query, ctx).await
+            }
+
+            async fn query_table_to_stream(
+                &self,
+                pool: &#p::PgPool,
+                query: &str,
+                batch_size: usize,
+            ) -> Result<#p::SendableRecordBatchStream, #p::AppError> {
+                <Self as #p::TableWorkerStatic>::query_table_to_stream(pool, query, batch_size)
+                    .await
+            }
+
+            async fn export_table_to_stream(
+                &self,
+                pool: &#p::PgPool,
+                query: &str,
+                batch_size: usize,
+            ) -> Result<#p::SendableRecordBatchStream, #p::AppError> {
+                <Self as #p::TableWorkerStatic>::export_table_to_stream(pool, query, batch_size)
+                    .await
+            }
+
+            async fn export_table_to_stream_with(
+                &self,
+                pool: &#p::PgPool,
+                query: &str,
+                batch_size: usize,
+                layout: #p::JsonLayout,
+            ) -> Result<#p::SendableRecordBatchStream, #p::AppError> {
+                <Self as #p::TableWorkerStatic>::export_table_to_stream_with(
+                    pool, query, batch_size, layout,
+                )
+                .await
+            }
+
+            async fn copy_table_to_stream(
+                &self,
+                pool: &#p::PgPool,
+                query: &str,
+                batch_size: usize,
+            ) -> Result<#p::SendableRecordBatchStream, #p::AppError> {
+                <Self as #p::TableWorkerStatic>::copy_table_to_stream(pool, query, batch_size)
+                    .await
+            }
+
+            async fn copy_table_to_stream_with(
+                &self,
+                pool: &#p::PgPool,
+                query: &str,
+                batch_size: usize,
+                layout: #p::JsonLayout,
+            ) -> Result<#p::SendableRecordBatchStream, #p::AppError> {
+                <Self as #p::TableWorkerStatic>::copy_table_to_stream_with(
+                    pool, query, batch_size, layout,
+                )
+                .await
+            }
+        }
+
+        #[#p::async_trait]
+        impl #p::TableWorkerStatic for #ident {
+            async fn query_table(pool: &#p::PgPool, query: &str) -> Result<(), #p::AppError> {
+                let query = #p::prepare_query(query)?;
+                let query = #p::sqlx::query_as::<_, Self>(&query);
+                let data = query.fetch_all(pool).await?;
+                println!("{:?}", data);
+                Ok(())
+            }
+
+            async fn query_table_to_string(
+                pool: &#p::PgPool,
+                query: &str,
+            ) -> Result<Vec<String>, #p::AppError> {
+                let query = #p::prepare_query(query)?;
+                let projection = Self::projection(pool, &query).await?;
+                let query = #p::sqlx::query(&query);
+                let data: Vec<#p::PgRow> = query.fetch_all(pool).await?;
+                let rows = data
+                    .iter()
+                    .map(|row| Self::format_row(row, &projection))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(rows)
+            }
+
+            async fn query_table_to_json(
+                pool: &#p::PgPool,
+                query: &str,
+            ) -> Result<String, #p::AppError> {
+                let query = #p::prepare_query(query)?;
+                let projection = Self::projection(pool, &query).await?;
+                if !#p::is_full_projection(&projection, &Self::schema()) {
+                    let rows = #p::sqlx::query(&query).fetch_all(pool).await?;
+                    let batch = Self::rows_to_record_batch(&rows, &projection, #p::JsonLayout::default())?;
+                    return #p::record_batch_to_json(&batch);
+                }
+                let query = #p::sqlx::query_as::<_, Self>(&query);
+                let data = query.fetch_all(pool).await?;
+                let res = #p::serde_json::to_string(&data)?;
+                Ok(res)
+            }
+
+            async fn query_table_to_df(
+                pool: &#p::PgPool,
+                query: &str,
+                ctx: &#p::SessionContext,
+            ) -> Result<#p::DataFrame, #p::AppError> {
+                let query = #p::prepare_query(query)?;
+                let projection = Self::projection(pool, &query).await?;
+                let rows = #p::sqlx::query(&query).fetch_all(pool).await?;
+                let batch = Self::rows_to_record_batch(&rows, &projection, #p::JsonLayout::default())?;
+                let df = ctx.read_batch(batch)?;
+                Ok(df)
+            }
+
+            async fn query_table_to_stream(
+                pool: &#p::PgPool,
+                query: &str,
+                batch_size: usize,
+            ) -> Result<#p::SendableRecordBatchStream, #p::AppError> {
+                let query = #p::prepare_query(query)?;
+                let projection = Self::projection(pool, &query).await?;
+                let schema = #p::Arc::new(Self::schema().project(&projection)?);
+                let stream = #p::rows_to_stream(pool, query, schema, batch_size, move |rows| {
+                    Self::rows_to_record_batch(rows, &projection, #p::JsonLayout::default())
+                });
+                Ok(stream)
+            }
+
+            async fn export_table_to_stream_with(
+                pool: &#p::PgPool,
+                query: &str,
+                batch_size: usize,
+                layout: #p::JsonLayout,
+            ) -> Result<#p::SendableRecordBatchStream, #p::AppError> {
+                let query = #p::validate_query(query)?;
+                let projection = Self::projection(pool, &query).await?;
+                let schema = #p::Arc::new(Self::schema_with(layout).project(&projection)?);
+                let stream = #p::rows_to_stream(pool, query, schema, batch_size, move |rows| {
+                    Self::rows_to_record_batch(rows, &projection, layout)
+                });
+                Ok(stream)
+            }
+
+            async fn copy_table_to_stream_with(
+                pool: &#p::PgPool,
+                query: &str,
+                batch_size: usize,
+                layout: #p::JsonLayout,
+            ) -> Result<#p::SendableRecordBatchStream, #p::AppError> {
+                let query = #p::validate_query(query)?;
+                let projection = Self::projection(pool, &query).await?;
+                let schema = #p::Arc::new(Self::schema_with(layout).project(&projection)?);
+                Ok(#p::copy_to_stream(pool, query, schema, batch_size))
+            }
+        }
+    })
+}
+
+struct TableAttrs {
+    table: Expr,
+    format: Option<Expr>,
+}
+
+fn table_attrs(input: &DeriveInput) -> syn::Result<TableAttrs> {
+    let mut table = None;
+    let mut format = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("table_worker"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("table") {
+                table = Some(meta.value()?.parse::<Expr>()?);
+                Ok(())
+            } else if meta.path.is_ident("format") {
+                format = Some(meta.value()?.parse::<Expr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `table = ...` or `format = ...`"))
+            }
+        })?;
+    }
+    let table = table.ok_or_else(|| {
+        Error::new(
+            input.ident.span(),
+            "missing `#[table_worker(table = ...)]` attribute",
+        )
+    })?;
+    Ok(TableAttrs { table, format })
+}
+
+fn field_def(field: &syn::Field) -> syn::Result<FieldDef> {
+    let ident = field.ident.clone().expect("named field");
+    let mut column = Column::Default;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("table_worker"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("decimal") {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                let precision = content.parse::<Expr>()?;
+                content.parse::<syn::Token![,]>()?;
+                let scale = content.parse::<Expr>()?;
+                column = Column::Decimal(Box::new((precision, scale)));
+                Ok(())
+            } else if meta.path.is_ident("flatten") {
+                column = Column::Flatten;
+                Ok(())
+            } else {
+                Err(meta.error("expected `decimal(precision, scale)` or `flatten`"))
+            }
+        })?;
+    }
+    let (inner, nullable) = match option_inner(&field.ty) {
+        Some(inner) => (inner.clone(), true),
+        None => (field.ty.clone(), false),
+    };
+    Ok(FieldDef {
+        name: ident.to_string(),
+        ident,
+        ty: field.ty.clone(),
+        inner,
+        nullable,
+        column,
+    })
+}
+
+/// Returns `T` for a field of type `Option<T>`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+// Arrow array of the field from `values: Vec<Option<&T>>`
+fn to_array(field: &FieldDef, p: &TokenStream2) -> TokenStream2 {
+    let inner = &field.inner;
+    match &field.column {
+        Column::Default => quote!(<#inner as #p::ArrowColumn>::to_array(&values, layout)?),
+        Column::Decimal(args) => {
+            let (precision, scale) = args.as_ref();
+            quote!(#p::decimal_array(&values, #precision, #scale)?)
+        }
+        Column::Flatten => quote!(<#inner as #p::FlattenColumn>::to_array(&values, layout)?),
+    }
+}
@@ -0,0 +1,44 @@
+use demodb_to_datalake::{
+    write_stream_to_file, PostgresDb, Table, ALL_TABLE_NAMES, DATABASE_URL, DEFAULT_BATCH_SIZE,
+    MAX_DB_CONS,
+};
+
+use color_eyre::{eyre::Context, Result};
+use futures_util::future::try_join_all;
+use secrecy::ExposeSecret;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+
+    // every table is read at the same point in time, even on different connections
+    let snapshot = db.snapshot().await?;
+    println!("exporting snapshot: {}", snapshot.snapshot_id());
+
+    let exports = ALL_TABLE_NAMES
+        .iter()
+        .filter_map(|name| Table::new(name))
+        .map(|table| {
+            let pool = snapshot.as_ref();
+            async move {
+                let query = format!("select * from {}", table.as_ref());
+                let file_path = format!("{}.parquet", table.as_ref());
+                let stream = table
+                    .to_worker()
+                    .export_table_to_stream(pool, &query, DEFAULT_BATCH_SIZE)
+                    .await
+                    .wrap_err(format!("failed quering table: {}", table.as_ref()))?;
+                write_stream_to_file(stream, &file_path).await?;
+                println!("written table: {} to: {}", table.as_ref(), file_path);
+                Ok::<_, color_eyre::Report>(())
+            }
+        });
+    try_join_all(exports).await?;
+
+    snapshot.close().await?;
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::catalog::{CatalogProvider, SchemaProvider};
+use datafusion::datasource::file_format::parquet::ParquetFormat;
+use datafusion::datasource::listing::{
+    ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+};
+use datafusion::datasource::TableProvider;
+use datafusion::error::DataFusionError;
+use datafusion::prelude::SessionContext;
+use futures_util::StreamExt;
+use sqlx::PgPool;
+use tokio::sync::OnceCell;
+
+use crate::{
+    AppError, LakeStore, PostgresTableProvider, Table, ALL_TABLE_NAMES, DEMODB_CATALOG_NAME,
+    DEMODB_SCHEMA_NAME,
+};
+
+/// DataFusion catalog exposing every table of [`ALL_TABLE_NAMES`] as
+/// `demodb.bookings.<table>`, read from postgres or from a lake of Parquet files.
+#[derive(Debug)]
+pub struct DemoDbCatalog {
+    schema: Arc<DemoDbSchema>,
+}
+
+impl DemoDbCatalog {
+    /// Tables are scanned lazily through [`PostgresTableProvider`].
+    pub fn postgres(pool: &PgPool) -> Self {
+        Self::with_source(DemoDbSource::Postgres(pool.clone()))
+    }
+
+    /// Tables are read from `<table>.parquet` files, as the command line tool exports them, or
+    /// from `<table>/` directories, as partitioned exports write them. The tables in `store` are
+    /// looked up now, those exported later are found on first use.
+    pub async fn lake(store: LakeStore) -> Result<Self, AppError> {
+        let catalog = Self::with_source(DemoDbSource::Lake(store));
+        for name in ALL_TABLE_NAMES {
+            catalog.schema.table(name).await?;
+        }
+        Ok(catalog)
+    }
+
+    fn with_source(source: DemoDbSource) -> Self {
+        let tables = ALL_TABLE_NAMES
+            .iter()
+            .map(|name| (name.to_string(), OnceCell::new()))
+            .collect();
+        Self {
+            schema: Arc::new(DemoDbSchema { source, tables }),
+        }
+    }
+
+    /// Registers the catalog with `ctx` under [`DEMODB_CATALOG_NAME`].
+    pub fn register(self, ctx: &SessionContext) {
+        if let DemoDbSource::Lake(store) = &self.schema.source {
+            store.register(ctx);
+        }
+        ctx.register_catalog(DEMODB_CATALOG_NAME, Arc::new(self));
+    }
+
+    /// New session context with the catalog registered.
+    pub fn session_context(self) -> SessionContext {
+        let ctx = SessionContext::new();
+        self.register(&ctx);
+        ctx
+    }
+}
+
+impl CatalogProvider for DemoDbCatalog {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema_names(&self) -> Vec<String> {
+        vec![DEMODB_SCHEMA_NAME.to_string()]
+    }
+
+    fn schema(&self, name: &str) -> Option<Arc<dyn SchemaProvider>> {
+        match name {
+            DEMODB_SCHEMA_NAME => Some(self.schema.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum DemoDbSource {
+    Postgres(PgPool),
+    Lake(LakeStore),
+}
+
+#[derive(Debug)]
+struct DemoDbSchema {
+    source: DemoDbSource,
+    // providers are built on first use, a lake table missing from the store is looked up again
+    // the next time, as it may be exported meanwhile
+    tables: HashMap<String, OnceCell<Arc<dyn TableProvider>>>,
+}
+
+#[async_trait]
+impl SchemaProvider for DemoDbSchema {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        ALL_TABLE_NAMES
+            .iter()
+            .filter(|name| self.table_exist(name))
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    async fn table(&self, name: &str) -> Result<Option<Arc<dyn TableProvider>>, DataFusionError> {
+        let Some(table) = self.tables.get(name) else {
+            return Ok(None);
+        };
+        if let Some(provider) = table.get() {
+            return Ok(Some(provider.clone()));
+        }
+        let provider = match &self.source {
+            DemoDbSource::Postgres(pool) => {
+                let table = Table::new(name).expect("known table name");
+                let provider = PostgresTableProvider::try_new(pool, table).await?;
+                Some(Arc::new(provider) as Arc<dyn TableProvider>)
+            }
+            DemoDbSource::Lake(store) => lake_table(store, name).await?,
+        };
+        let Some(provider) = provider else {
+            return Ok(None);
+        };
+        // of two lookups racing the first provider is kept
+        let provider = table.get_or_init(|| async { provider }).await;
+        Ok(Some(provider.clone()))
+    }
+
+    // postgres has every table, the lake those found so far
+    fn table_exist(&self, name: &str) -> bool {
+        match &self.source {
+            DemoDbSource::Postgres(_) => self.tables.contains_key(name),
+            DemoDbSource::Lake(_) => self.tables.get(name).is_some_and(OnceCell::initialized),
+        }
+    }
+}
+
+async fn lake_table(
+    store: &LakeStore,
+    name: &str,
+) -> Result<Option<Arc<dyn TableProvider>>, AppError> {
+    let file = format!("{}.parquet", name);
+    let path = match store.store().head(&store.path(&file)).await {
+        Ok(_) => file,
+        Err(object_store::Error::NotFound { .. }) => {
+            let (object_store, prefix) = (store.store(), store.path(name));
+            let mut objects = object_store.list(Some(&prefix));
+            if objects.next().await.transpose()?.is_none() {
+                return Ok(None);
+            }
+            format!("{}/", name)
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let ctx = SessionContext::new();
+    store.register(&ctx);
+    let state = ctx.state();
+    let options =
+        ListingOptions::new(Arc::new(ParquetFormat::default())).with_file_extension(".parquet");
+    let config = ListingTableConfig::new(ListingTableUrl::parse(store.url(&path))?)
+        .with_listing_options(options)
+        .infer_partitions_from_path(&state)
+        .await?
+        .infer_schema(&state)
+        .await?;
+    Ok(Some(Arc::new(ListingTable::try_new(config)?)))
+}
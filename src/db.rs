@@ -1,9 +1,16 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use secrecy::{ExposeSecret, Secret};
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
+use sqlx::postgres::{PgConnectOptions, PgConnection, PgPoolOptions};
+use sqlx::{Executor, Pool, Postgres, Transaction};
+
+use crate::{AppError, QueryPolicy};
 
 pub struct PostgresDbBuilder {
     url: String,
     max_cons: u32,
+    statement_timeout: Option<Duration>,
 }
 
 impl Default for PostgresDbBuilder {
@@ -11,6 +18,7 @@ impl Default for PostgresDbBuilder {
         PostgresDbBuilder {
             url: String::default(),
             max_cons: 10,
+            statement_timeout: None,
         }
     }
 }
@@ -19,21 +27,40 @@ impl PostgresDbBuilder {
     pub fn with_url(self, url: &str) -> Self {
         Self {
             url: url.to_string(),
-            max_cons: self.max_cons,
+            ..self
         }
     }
 
     pub fn with_max_cons(self, max_cons: u32) -> Self {
+        Self { max_cons, ..self }
+    }
+
+    /// Postgres cancels statements running longer than `statement_timeout`.
+    pub fn with_statement_timeout(self, statement_timeout: Duration) -> Self {
         Self {
-            url: self.url,
-            max_cons,
+            statement_timeout: Some(statement_timeout),
+            ..self
+        }
+    }
+
+    /// Applies the settings of `policy` postgres enforces, its statement timeout. Queries still
+    /// have to be checked with [`prepare_query_with_policy`](crate::prepare_query_with_policy).
+    pub fn with_query_policy(self, policy: &QueryPolicy) -> Self {
+        Self {
+            statement_timeout: policy.statement_timeout().or(self.statement_timeout),
+            ..self
         }
     }
 
     pub async fn build(self) -> Result<PostgresDb, sqlx::Error> {
+        let mut options: PgConnectOptions = self.url.parse()?;
+        if let Some(timeout) = self.statement_timeout {
+            let timeout = timeout.as_millis().to_string();
+            options = options.options([("statement_timeout", timeout.as_str())]);
+        }
         let pool = PgPoolOptions::new()
             .max_connections(self.max_cons)
-            .connect(&self.url)
+            .connect_with(options)
             .await?;
 
         Ok(PostgresDb {
@@ -63,4 +90,86 @@ impl PostgresDb {
     pub fn get_url(&self) -> &str {
         self.url.expose_secret()
     }
+
+    /// Opens a read-only `REPEATABLE READ` transaction and exports its snapshot, see [`DbSnapshot`].
+    pub async fn snapshot(&self) -> Result<DbSnapshot, AppError> {
+        let mut tx = self
+            .pool
+            .begin_with("BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .await?;
+        let snapshot_id = sqlx::query_scalar::<_, String>("select pg_export_snapshot()")
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let set_snapshot = Arc::new(format!(
+            "BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY; SET TRANSACTION SNAPSHOT '{}'",
+            snapshot_id.replace('\'', "''")
+        ));
+        let on_connect = set_snapshot.clone();
+        let on_acquire = set_snapshot.clone();
+        let pool = self
+            .pool
+            .options()
+            .clone()
+            .after_connect(move |conn, _| {
+                let query = on_connect.clone();
+                Box::pin(async move { begin_snapshot(conn, &query).await })
+            })
+            .before_acquire(move |conn, _| {
+                let query = on_acquire.clone();
+                Box::pin(async move {
+                    begin_snapshot(conn, &query).await?;
+                    Ok(true)
+                })
+            })
+            .after_release(|conn, _| {
+                Box::pin(async move {
+                    conn.execute("ROLLBACK").await?;
+                    Ok(true)
+                })
+            })
+            .connect_with((*self.pool.connect_options()).clone())
+            .await?;
+
+        Ok(DbSnapshot {
+            pool,
+            snapshot_id,
+            tx,
+        })
+    }
+}
+
+async fn begin_snapshot(conn: &mut PgConnection, query: &str) -> Result<(), sqlx::Error> {
+    conn.execute(query).await?;
+    Ok(())
+}
+
+/// Point in time shared by every query run on its pool.
+///
+/// Each connection of the pool imports the exported snapshot with `SET TRANSACTION SNAPSHOT`,
+/// so tables exported by different workers are consistent with each other. The snapshot stays
+/// valid until [`DbSnapshot::close`] or drop.
+#[derive(Debug)]
+pub struct DbSnapshot {
+    pool: Pool<Postgres>,
+    snapshot_id: String,
+    tx: Transaction<'static, Postgres>,
+}
+
+impl AsRef<Pool<Postgres>> for DbSnapshot {
+    fn as_ref(&self) -> &Pool<Postgres> {
+        &self.pool
+    }
+}
+
+impl DbSnapshot {
+    pub fn snapshot_id(&self) -> &str {
+        &self.snapshot_id
+    }
+
+    pub async fn close(self) -> Result<(), AppError> {
+        self.pool.close().await;
+        self.tx.rollback().await?;
+        Ok(())
+    }
 }
@@ -1,10 +1,15 @@
 use std::num::ParseIntError;
 
-use crate::utils::QueryParserError;
+use crate::pipeline::PipelineError;
+use crate::utils::{
+    AvroError, CopyError, DeltaError, FormatError, IcebergError, LakeStoreError, QueryParserError,
+    SchemaError,
+};
 
 use color_eyre::Report;
 use datafusion::arrow::error::ArrowError;
 use datafusion::error::DataFusionError;
+use object_store::Error as ObjectStoreError;
 use parquet::errors::ParquetError;
 use serde_json::Error as SerdeError;
 use sqlx::Error as SqlxError;
@@ -19,6 +24,12 @@ pub enum AppError {
     #[error("QueryParserError")]
     QueryParserError(#[from] QueryParserError),
 
+    #[error("SchemaError")]
+    SchemaError(#[from] SchemaError),
+
+    #[error("CopyError")]
+    CopyError(#[from] CopyError),
+
     #[error("IoError")]
     IOError(#[from] IoError),
 
@@ -37,6 +48,75 @@ pub enum AppError {
     #[error("ParquetError")]
     ParquetError(#[from] ParquetError),
 
+    #[error("LakeStoreError")]
+    LakeStoreError(#[from] LakeStoreError),
+
+    #[error("ObjectStoreError")]
+    ObjectStoreError(#[from] ObjectStoreError),
+
+    #[error("ObjectStorePathError")]
+    ObjectStorePathError(#[from] object_store::path::Error),
+
+    #[error("DeltaError")]
+    DeltaError(#[from] DeltaError),
+
+    #[error("AvroError")]
+    AvroError(#[from] AvroError),
+
+    #[error("IcebergError")]
+    IcebergError(#[from] IcebergError),
+
+    #[error("FormatError")]
+    FormatError(#[from] FormatError),
+
+    #[error("PipelineError")]
+    PipelineError(#[from] PipelineError),
+
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
+
+impl AppError {
+    /// Process exit code for the error, following the BSD `sysexits.h` conventions.
+    pub fn exit_code(&self) -> u8 {
+        const EX_USAGE: u8 = 64;
+        const EX_DATAERR: u8 = 65;
+        const EX_UNAVAILABLE: u8 = 69;
+        const EX_SOFTWARE: u8 = 70;
+        const EX_IOERR: u8 = 74;
+        const EX_CONFIG: u8 = 78;
+
+        match self {
+            Self::ParseIntError(_) | Self::QueryParserError(_) | Self::FormatError(_) => EX_USAGE,
+            Self::PipelineError(PipelineError::MissingDatabaseUrl) => EX_CONFIG,
+            Self::PipelineError(_) => EX_USAGE,
+            Self::SqlxError(SqlxError::Configuration(_)) => EX_CONFIG,
+            Self::SqlxError(
+                SqlxError::Io(_)
+                | SqlxError::Tls(_)
+                | SqlxError::PoolTimedOut
+                | SqlxError::PoolClosed,
+            ) => EX_UNAVAILABLE,
+            Self::SchemaError(_)
+            | Self::CopyError(_)
+            | Self::SerdeError(_)
+            | Self::SqlxError(_)
+            | Self::ArrowError(_)
+            | Self::ParquetError(_)
+            | Self::DeltaError(_)
+            | Self::AvroError(_)
+            | Self::IcebergError(_) => EX_DATAERR,
+            Self::IOError(_)
+            | Self::LakeStoreError(_)
+            | Self::ObjectStoreError(_)
+            | Self::ObjectStorePathError(_) => EX_IOERR,
+            Self::DatafusionError(_) | Self::UnexpectedError(_) => EX_SOFTWARE,
+        }
+    }
+}
+
+impl From<AppError> for DataFusionError {
+    fn from(err: AppError) -> Self {
+        DataFusionError::External(Box::new(err))
+    }
+}
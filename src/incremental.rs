@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::Utc;
+use sqlx::PgPool;
+
+use crate::partition::quote_ident;
+use crate::{
+    add_watermark_filter, write_stream_to_file_with, AppError, ExtractBackend, JsonLayout,
+    ParquetWriteOptions, Table, DEFAULT_BATCH_SIZE,
+};
+
+/// High-water marks of incremental loads by table name, persisted as a JSON object.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Watermarks(BTreeMap<String, String>);
+
+impl Watermarks {
+    /// Reads the state file, a missing file means no table was loaded yet.
+    pub async fn load(path: &str) -> Result<Self, AppError> {
+        match tokio::fs::read(path).await {
+            Ok(buf) => Ok(serde_json::from_slice(&buf)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the state file through a temporary file, so a crash never leaves it half written.
+    pub async fn save(&self, path: &str) -> Result<(), AppError> {
+        let tmp_path = format!("{}.tmp", path);
+        tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(self)?).await?;
+        tokio::fs::rename(&tmp_path, path).await?;
+        Ok(())
+    }
+
+    pub fn get(&self, table: &str) -> Option<&str> {
+        self.0.get(table).map(String::as_str)
+    }
+
+    pub fn set(&mut self, table: &str, watermark: &str) {
+        self.0.insert(table.to_string(), watermark.to_string());
+    }
+}
+
+/// Exports only the rows of a table whose `column` is past the watermark of the previous run.
+#[derive(Debug)]
+pub struct IncrementalExtractor {
+    table: Table,
+    column: String,
+    state_path: String,
+    query: Option<String>,
+    batch_size: usize,
+    backend: ExtractBackend,
+    layout: JsonLayout,
+    write_options: ParquetWriteOptions,
+}
+
+impl IncrementalExtractor {
+    /// `column` must only grow for new or changed rows, like `bookings.book_date`.
+    pub fn new(table: Table, column: &str, state_path: &str) -> Self {
+        Self {
+            table,
+            column: column.to_string(),
+            state_path: state_path.to_string(),
+            query: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            backend: ExtractBackend::default(),
+            layout: JsonLayout::default(),
+            write_options: ParquetWriteOptions::default(),
+        }
+    }
+
+    /// Base query the watermark filter is added to, `select * from <table>` by default.
+    pub fn with_query(self, query: &str) -> Self {
+        Self {
+            query: Some(query.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_batch_size(self, batch_size: usize) -> Self {
+        Self { batch_size, ..self }
+    }
+
+    pub fn with_backend(self, backend: ExtractBackend) -> Self {
+        Self { backend, ..self }
+    }
+
+    pub fn with_layout(self, layout: JsonLayout) -> Self {
+        Self { layout, ..self }
+    }
+
+    pub fn with_write_options(self, write_options: ParquetWriteOptions) -> Self {
+        Self {
+            write_options,
+            ..self
+        }
+    }
+
+    fn base_query(&self) -> String {
+        self.query
+            .clone()
+            .unwrap_or_else(|| format!("select * from {}", self.table.as_ref()))
+    }
+
+    /// Returns the largest `column` value of the rows past `low`, the next watermark.
+    async fn high_watermark(
+        &self,
+        pool: &PgPool,
+        low: Option<&str>,
+    ) -> Result<Option<String>, AppError> {
+        let query = add_watermark_filter(&self.base_query(), &self.column, low, None)?;
+        let query = format!(
+            "select max(delta.{})::text from ({}) as delta",
+            quote_ident(&self.column),
+            query
+        );
+        let high = sqlx::query_scalar::<_, Option<String>>(&query)
+            .fetch_one(pool)
+            .await?;
+        Ok(high)
+    }
+
+    /// Writes the rows added since the last run to `<table>_<timestamp>.parquet` in `dir` and
+    /// returns its path, or `None` when there is nothing new. The watermark only advances once
+    /// the file is written.
+    pub async fn extract_to_file(
+        &self,
+        pool: &PgPool,
+        dir: &str,
+    ) -> Result<Option<String>, AppError> {
+        let table = self.table.as_ref();
+        let mut watermarks = Watermarks::load(&self.state_path).await?;
+        let low = watermarks.get(table);
+        let Some(high) = self.high_watermark(pool, low).await? else {
+            return Ok(None);
+        };
+        let query = add_watermark_filter(&self.base_query(), &self.column, low, Some(&high))?;
+
+        let file_name = format!(
+            "{}_{}.parquet",
+            table,
+            Utc::now().format("%Y%m%dT%H%M%S%6f")
+        );
+        let file_path = Path::new(dir)
+            .join(file_name)
+            .to_string_lossy()
+            .into_owned();
+        let stream = self
+            .table
+            .run_extract_to_stream(pool, &query, self.batch_size, self.backend, self.layout)
+            .await?;
+        write_stream_to_file_with(stream, &file_path, &self.write_options).await?;
+
+        watermarks.set(table, &high);
+        watermarks.save(&self.state_path).await?;
+        Ok(Some(file_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn watermarks_test() {
+        let path = std::env::temp_dir()
+            .join(format!("watermarks_{}.json", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let mut watermarks = Watermarks::load(&path).await.unwrap();
+        assert_eq!(watermarks, Watermarks::default());
+
+        watermarks.set("bookings", "2017-08-15 15:00:00+00");
+        watermarks.set("flights", "33121");
+        watermarks.save(&path).await.unwrap();
+        let loaded = Watermarks::load(&path).await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(loaded, watermarks);
+        assert_eq!(loaded.get("flights"), Some("33121"));
+        assert_eq!(loaded.get("seats"), None);
+    }
+}
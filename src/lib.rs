@@ -1,13 +1,47 @@
+extern crate self as demodb_to_datalake;
+
+mod catalog;
 mod db;
 mod error;
+mod incremental;
+mod partition;
+mod pipeline;
+mod provider;
 mod table;
 mod table_worker;
 mod tables;
 mod utils;
 
+pub use catalog::*;
 pub use db::*;
+pub use demodb_to_datalake_derive::TableWorker;
 pub use error::AppError;
+pub use incremental::*;
+pub use partition::*;
+pub use pipeline::*;
+pub use provider::*;
 pub use table::*;
 pub use table_worker::*;
 pub use tables::*;
 pub use utils::*;
+
+/// Items referenced by `#[derive(TableWorker)]` expansions.
+#[doc(hidden)]
+pub mod __private {
+    pub use std::sync::Arc;
+
+    pub use async_trait::async_trait;
+    pub use datafusion::arrow::array::{ArrayRef, RecordBatch};
+    pub use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    pub use datafusion::physical_plan::SendableRecordBatchStream;
+    pub use datafusion::prelude::{DataFrame, SessionContext};
+    pub use serde_json;
+    pub use sqlx::{self, postgres::PgRow, PgPool};
+
+    pub use crate::table_worker::stream::{copy_to_stream, rows_to_stream};
+    pub use crate::{
+        decimal_array, format_column, is_full_projection, prepare_query, query_projection,
+        record_batch_to_json, validate_query, AppError, ArrowColumn, FlattenColumn, JsonLayout,
+        TableWorkerDyn, TableWorkerStatic,
+    };
+}
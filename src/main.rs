@@ -0,0 +1,352 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::builder::PossibleValuesParser;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use datafusion::prelude::SessionContext;
+use demodb_to_datalake::{
+    query_table_name, validate_query, validate_query_with_tables, write_stream_to_file_as,
+    write_stream_to_writer_as, AppError, ArrowIpcOptions, AvroOptions, CsvOptions, ExtractBackend,
+    JsonLayout, NdJsonOptions, OutputFormat, ParquetWriteOptions, PipelineConfig, PostgresDb,
+    Table, ALL_TABLE_NAMES, DEFAULT_BATCH_SIZE, MAX_DB_CONS,
+};
+use futures_util::future::try_join_all;
+use parquet::arrow::parquet_to_arrow_schema;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use sqlx::PgPool;
+
+/// Exports the demo database tables to data lake files.
+#[derive(Debug, Parser)]
+#[command(name = "demodb-to-datalake", version)]
+struct Cli {
+    /// Postgres connection url, read from `DATABASE_URL` or `.env` when not given.
+    #[arg(long, env = "DATABASE_URL", global = true, hide_env_values = true)]
+    database_url: Option<String>,
+
+    #[arg(long, default_value_t = MAX_DB_CONS, global = true)]
+    max_cons: u32,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Lists the tables that can be exported.
+    ListTables,
+    /// Exports one table to `<out>/<table>.<extension>`.
+    Export {
+        #[arg(long, value_parser = PossibleValuesParser::new(ALL_TABLE_NAMES))]
+        table: String,
+        /// Select query on the table, defaults to the whole table.
+        #[arg(long)]
+        query: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Exports every table from one consistent database snapshot.
+    ExportAll {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Runs a select query on one table and prints the rows.
+    Query {
+        query: String,
+        #[arg(long, value_enum, default_value_t = QueryFormat::Table)]
+        format: QueryFormat,
+    },
+    /// Prints the schema and metadata of a Parquet file.
+    Inspect { file: PathBuf },
+    /// Runs the export pipeline described in a TOML or YAML file.
+    Run { config: PathBuf },
+}
+
+#[derive(Debug, Args)]
+struct OutputArgs {
+    #[arg(long, value_enum, default_value_t = FileFormat::Parquet)]
+    format: FileFormat,
+    /// Output directory, created when missing.
+    #[arg(long, default_value = ".")]
+    out: PathBuf,
+    #[arg(long, value_enum, default_value_t = Backend::Query)]
+    backend: Backend,
+    /// Layout of the jsonb and point columns.
+    #[arg(long, value_enum, default_value_t = Layout::Struct)]
+    layout: Layout,
+    #[arg(long, default_value_t = DEFAULT_BATCH_SIZE)]
+    batch_size: usize,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FileFormat {
+    Parquet,
+    Csv,
+    Ndjson,
+    #[value(alias = "feather")]
+    Arrow,
+    Avro,
+}
+
+impl FileFormat {
+    fn output_format(&self, table: &str) -> OutputFormat {
+        match self {
+            Self::Parquet => OutputFormat::Parquet(ParquetWriteOptions::new()),
+            Self::Csv => OutputFormat::Csv(CsvOptions::new()),
+            Self::Ndjson => OutputFormat::NdJson(NdJsonOptions::new()),
+            Self::Arrow => OutputFormat::ArrowIpc(ArrowIpcOptions::new()),
+            Self::Avro => OutputFormat::Avro(AvroOptions::new().with_name(table)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Backend {
+    Query,
+    CopyBinary,
+}
+
+impl From<Backend> for ExtractBackend {
+    fn from(backend: Backend) -> Self {
+        match backend {
+            Backend::Query => Self::Query,
+            Backend::CopyBinary => Self::CopyBinary,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Layout {
+    Struct,
+    JsonString,
+}
+
+impl From<Layout> for JsonLayout {
+    fn from(layout: Layout) -> Self {
+        match layout {
+            Layout::Struct => Self::Struct,
+            Layout::JsonString => Self::JsonString,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum QueryFormat {
+    Table,
+    Json,
+    Csv,
+    Ndjson,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", error_chain(&e));
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn error_chain(e: &AppError) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(err) = source {
+        message.push_str(&format!(": {}", err));
+        source = err.source();
+    }
+    message
+}
+
+async fn run(cli: Cli) -> Result<ExitCode, AppError> {
+    match cli.command {
+        Command::ListTables => {
+            for name in ALL_TABLE_NAMES {
+                println!("{}", name);
+            }
+        }
+        Command::Export {
+            table,
+            query,
+            output,
+        } => {
+            let query = match query {
+                Some(query) => validate_query_with_tables(&query, &[&table])?,
+                None => format!("select * from {}", table),
+            };
+            let db = connect(cli.database_url, cli.max_cons).await?;
+            tokio::fs::create_dir_all(&output.out).await?;
+            export_table(db.as_ref(), &table, &query, &output).await?;
+        }
+        Command::ExportAll { output } => {
+            let db = connect(cli.database_url, cli.max_cons).await?;
+            let snapshot = db.snapshot().await?;
+            println!("exporting snapshot: {}", snapshot.snapshot_id());
+            tokio::fs::create_dir_all(&output.out).await?;
+            let exports = ALL_TABLE_NAMES.iter().map(|table| {
+                let query = format!("select * from {}", table);
+                let pool = snapshot.as_ref();
+                let output = &output;
+                async move { export_table(pool, table, &query, output).await }
+            });
+            let res = try_join_all(exports).await;
+            let closed = snapshot.close().await;
+            res?;
+            closed?;
+        }
+        Command::Query { query, format } => {
+            let query = validate_query(&query)?;
+            let table = table(&query_table_name(&query)?);
+            let db = connect(cli.database_url, cli.max_cons).await?;
+            match format {
+                QueryFormat::Table => {
+                    let ctx = SessionContext::new();
+                    let df = table
+                        .run_query_table_to_df(db.as_ref(), &query, &ctx)
+                        .await?;
+                    df.show().await?;
+                }
+                QueryFormat::Json => {
+                    let json = table.run_query_table_to_json(db.as_ref(), &query).await?;
+                    println!("{}", json);
+                }
+                QueryFormat::Csv | QueryFormat::Ndjson => {
+                    let format = match format {
+                        QueryFormat::Csv => OutputFormat::Csv(CsvOptions::new()),
+                        _ => OutputFormat::NdJson(NdJsonOptions::new()),
+                    };
+                    let stream = table
+                        .run_query_table_to_stream(db.as_ref(), &query, DEFAULT_BATCH_SIZE)
+                        .await?;
+                    write_stream_to_writer_as(stream, tokio::io::stdout(), &format).await?;
+                }
+            }
+        }
+        Command::Inspect { file } => inspect(&file)?,
+        Command::Run { config } => {
+            let config = PipelineConfig::load(&config.to_string_lossy()).await?;
+            let db = match config.database_url() {
+                Some(_) => config.connect().await?,
+                None => connect(cli.database_url, config.max_cons()).await?,
+            };
+            let report = config.run(&db).await?;
+            let mut code = ExitCode::SUCCESS;
+            for table in report.tables() {
+                match table.error() {
+                    Some(e) => {
+                        eprintln!("failed table: {}: {}", table.table(), error_chain(e));
+                        if code == ExitCode::SUCCESS {
+                            code = ExitCode::from(e.exit_code());
+                        }
+                    }
+                    None => {
+                        for file in table.files() {
+                            println!("written table: {} to: {}", table.table(), file);
+                        }
+                    }
+                }
+            }
+            return Ok(code);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+async fn connect(database_url: Option<String>, max_cons: u32) -> Result<PostgresDb, AppError> {
+    let Some(url) = database_url else {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "--database-url or DATABASE_URL must be set",
+            )
+            .exit()
+    };
+    let db = PostgresDb::builder()
+        .with_url(&url)
+        .with_max_cons(max_cons)
+        .build()
+        .await?;
+    Ok(db)
+}
+
+// names are checked against ALL_TABLE_NAMES before they get here
+fn table(name: &str) -> Table {
+    Table::new(name).expect("known table name")
+}
+
+async fn export_table(
+    pool: &PgPool,
+    name: &str,
+    query: &str,
+    output: &OutputArgs,
+) -> Result<(), AppError> {
+    let format = output.format.output_format(name);
+    let file_path = output.out.join(format!("{}.{}", name, format.extension()));
+    let file_path = file_path.to_string_lossy();
+    let stream = table(name)
+        .run_extract_to_stream(
+            pool,
+            query,
+            output.batch_size,
+            output.backend.into(),
+            output.layout.into(),
+        )
+        .await?;
+    write_stream_to_file_as(stream, &file_path, &format).await?;
+    println!("written table: {} to: {}", name, file_path);
+    Ok(())
+}
+
+fn inspect(path: &Path) -> Result<(), AppError> {
+    let reader = SerializedFileReader::new(std::fs::File::open(path)?)?;
+    let metadata = reader.metadata();
+    let file_metadata = metadata.file_metadata();
+    let schema = parquet_to_arrow_schema(
+        file_metadata.schema_descr(),
+        file_metadata.key_value_metadata(),
+    )?;
+
+    println!("file: {}", path.display());
+    println!(
+        "created by: {}",
+        file_metadata.created_by().unwrap_or("unknown")
+    );
+    println!("rows: {}", file_metadata.num_rows());
+    println!("row groups: {}", metadata.num_row_groups());
+    println!("schema:");
+    for field in schema.fields() {
+        let nullable = if field.is_nullable() { "" } else { " not null" };
+        println!("  {}: {}{}", field.name(), field.data_type(), nullable);
+    }
+    println!("columns:");
+    for (i, column) in file_metadata.schema_descr().columns().iter().enumerate() {
+        let chunks = metadata
+            .row_groups()
+            .iter()
+            .map(|row_group| row_group.column(i));
+        let (compressed, uncompressed) =
+            chunks
+                .clone()
+                .fold((0, 0), |(compressed, uncompressed), chunk| {
+                    (
+                        compressed + chunk.compressed_size(),
+                        uncompressed + chunk.uncompressed_size(),
+                    )
+                });
+        let compression = chunks
+            .map(|chunk| chunk.compression().to_string())
+            .next()
+            .unwrap_or_else(|| "none".to_string());
+        println!(
+            "  {}: {} {} {}/{} bytes",
+            column.path().string(),
+            column.physical_type(),
+            compression,
+            compressed,
+            uncompressed
+        );
+    }
+    Ok(())
+}
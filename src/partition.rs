@@ -0,0 +1,267 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use datafusion::arrow::array::RecordBatch;
+use datafusion::datasource::MemTable;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use datafusion::prelude::*;
+use futures_util::{StreamExt, TryStreamExt};
+use sqlx::PgPool;
+
+use crate::{
+    write_stream_to_file_with, AppError, ExtractBackend, JsonLayout, ParquetWriteOptions, Table,
+    DEFAULT_BATCH_SIZE, DEFAULT_PARALLELISM,
+};
+
+/// How a table is split into partitions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PartitionKey {
+    /// Integer column, split into equal value ranges between its min and max.
+    Column(String),
+    /// Physical heap pages, split into equal `ctid` ranges.
+    Ctid,
+}
+
+/// Reads a table as `partitions` range queries running concurrently on the pool.
+#[derive(Debug)]
+pub struct PartitionedExtractor {
+    table: Table,
+    key: PartitionKey,
+    partitions: usize,
+    parallelism: usize,
+    batch_size: usize,
+    backend: ExtractBackend,
+    layout: JsonLayout,
+    write_options: ParquetWriteOptions,
+}
+
+impl PartitionedExtractor {
+    pub fn new(table: Table, key: PartitionKey) -> Self {
+        Self {
+            table,
+            key,
+            partitions: DEFAULT_PARALLELISM,
+            parallelism: DEFAULT_PARALLELISM,
+            batch_size: DEFAULT_BATCH_SIZE,
+            backend: ExtractBackend::default(),
+            layout: JsonLayout::default(),
+            write_options: ParquetWriteOptions::default(),
+        }
+    }
+
+    pub fn with_partitions(self, partitions: usize) -> Self {
+        Self {
+            partitions: partitions.max(1),
+            ..self
+        }
+    }
+
+    /// Number of partitions read at the same time, bounded by the pool size.
+    pub fn with_parallelism(self, parallelism: usize) -> Self {
+        Self {
+            parallelism: parallelism.max(1),
+            ..self
+        }
+    }
+
+    pub fn with_batch_size(self, batch_size: usize) -> Self {
+        Self { batch_size, ..self }
+    }
+
+    pub fn with_backend(self, backend: ExtractBackend) -> Self {
+        Self { backend, ..self }
+    }
+
+    pub fn with_layout(self, layout: JsonLayout) -> Self {
+        Self { layout, ..self }
+    }
+
+    pub fn with_write_options(self, write_options: ParquetWriteOptions) -> Self {
+        Self {
+            write_options,
+            ..self
+        }
+    }
+
+    /// Returns the `WHERE` predicate of every partition, together they cover the whole table.
+    pub async fn predicates(&self, pool: &PgPool) -> Result<Vec<String>, AppError> {
+        let table = self.table.as_ref();
+        let (column, bounds) = match &self.key {
+            PartitionKey::Column(column) => {
+                let column = quote_ident(column);
+                let query = format!("select min({column})::int8, max({column})::int8 from {table}");
+                let (min, max) = sqlx::query_as::<_, (Option<i64>, Option<i64>)>(&query)
+                    .fetch_one(pool)
+                    .await?;
+                let bounds = match (min, max) {
+                    (Some(min), Some(max)) => split_range(min, max, self.partitions),
+                    _ => vec![],
+                };
+                let bounds = bounds
+                    .into_iter()
+                    .map(|val| val.to_string())
+                    .collect::<Vec<_>>();
+                (column, bounds)
+            }
+            PartitionKey::Ctid => {
+                let query = format!(
+                    "select (pg_relation_size('{table}') / current_setting('block_size')::int8)::int8"
+                );
+                let pages = sqlx::query_scalar::<_, i64>(&query).fetch_one(pool).await?;
+                let bounds = if pages > 0 {
+                    split_range(0, pages - 1, self.partitions)
+                } else {
+                    vec![]
+                };
+                let bounds = bounds
+                    .into_iter()
+                    .map(|page| format!("'({page},0)'::tid"))
+                    .collect::<Vec<_>>();
+                ("ctid".to_string(), bounds)
+            }
+        };
+        Ok(range_predicates(&column, &bounds))
+    }
+
+    /// Returns the export query of every partition.
+    pub async fn queries(&self, pool: &PgPool) -> Result<Vec<String>, AppError> {
+        let queries = self
+            .predicates(pool)
+            .await?
+            .into_iter()
+            .map(|predicate| format!("select * from {} where {}", self.table.as_ref(), predicate))
+            .collect();
+        Ok(queries)
+    }
+
+    // streams are opened lazily, a partition query only starts once its turn comes
+    async fn stream(
+        &self,
+        pool: &PgPool,
+        query: String,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        self.table
+            .run_extract_to_stream(pool, &query, self.batch_size, self.backend, self.layout)
+            .await
+    }
+
+    /// Reads all partitions and merges them into one dataframe.
+    pub async fn extract_to_df(
+        &self,
+        pool: &PgPool,
+        ctx: &SessionContext,
+    ) -> Result<DataFrame, AppError> {
+        let queries = self.queries(pool).await?;
+        let partitions = futures_util::stream::iter(queries)
+            .map(|query| async move {
+                let stream = self.stream(pool, query).await?;
+                let schema = stream.schema();
+                let batches = stream.try_collect::<Vec<RecordBatch>>().await?;
+                Ok::<_, AppError>((schema, batches))
+            })
+            .buffered(self.parallelism)
+            .try_collect::<Vec<_>>()
+            .await?;
+        let schema = partitions[0].0.clone();
+        let partitions = partitions.into_iter().map(|(_, batches)| batches).collect();
+        let table = MemTable::try_new(schema, partitions)?;
+        let df = ctx.read_table(Arc::new(table))?;
+        Ok(df)
+    }
+
+    /// Writes every partition to its own parquet file `<table>_<n>.parquet` in `dir`,
+    /// returning the file paths.
+    pub async fn extract_to_files(
+        &self,
+        pool: &PgPool,
+        dir: &str,
+    ) -> Result<Vec<String>, AppError> {
+        let queries = self.queries(pool).await?;
+        let files = (0..queries.len())
+            .map(|i| {
+                let file_name = format!("{}_{:05}.parquet", self.table.as_ref(), i);
+                Path::new(dir)
+                    .join(file_name)
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>();
+        futures_util::stream::iter(queries.into_iter().zip(&files))
+            .map(|(query, file_path)| async move {
+                let stream = self.stream(pool, query).await?;
+                write_stream_to_file_with(stream, file_path, &self.write_options).await
+            })
+            .buffer_unordered(self.parallelism)
+            .try_collect::<Vec<_>>()
+            .await?;
+        Ok(files)
+    }
+}
+
+pub(crate) fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Splits `min..=max` into at most `partitions` ranges, returning the inner range bounds.
+fn split_range(min: i64, max: i64, partitions: usize) -> Vec<i64> {
+    let len = (max as i128 - min as i128 + 1) as u128;
+    let partitions = (partitions as u128).min(len).max(1);
+    let step = len.div_ceil(partitions);
+    (1..partitions)
+        .map(|i| (min as i128 + (i * step) as i128) as i64)
+        .filter(|bound| *bound <= max)
+        .collect()
+}
+
+/// Builds predicates for the ranges split at `bounds`, the first one also takes nulls and the
+/// last one is open so rows outside the sampled range are not lost.
+fn range_predicates(column: &str, bounds: &[String]) -> Vec<String> {
+    if bounds.is_empty() {
+        return vec!["true".to_string()];
+    }
+    let mut predicates = vec![format!("({column} < {} or {column} is null)", bounds[0])];
+    for window in bounds.windows(2) {
+        predicates.push(format!(
+            "{column} >= {} and {column} < {}",
+            window[0], window[1]
+        ));
+    }
+    predicates.push(format!("{column} >= {}", bounds[bounds.len() - 1]));
+    predicates
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(1, 100, 4, vec![26, 51, 76])]
+    #[case(1, 10, 3, vec![5, 9])]
+    #[case(1, 2, 4, vec![2])]
+    #[case(5, 5, 4, vec![])]
+    #[case(0, 99, 1, vec![])]
+    fn split_range_test(
+        #[case] min: i64,
+        #[case] max: i64,
+        #[case] partitions: usize,
+        #[case] expected: Vec<i64>,
+    ) {
+        assert_eq!(expected, split_range(min, max, partitions));
+    }
+
+    #[test]
+    fn range_predicates_test() {
+        let bounds = vec!["10".to_string(), "20".to_string()];
+        assert_eq!(
+            range_predicates("flight_id", &bounds),
+            vec![
+                "(flight_id < 10 or flight_id is null)",
+                "flight_id >= 10 and flight_id < 20",
+                "flight_id >= 20",
+            ]
+        );
+        assert_eq!(range_predicates("ctid", &[]), vec!["true"]);
+    }
+}
@@ -0,0 +1,595 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use dotenvy::dotenv;
+use futures_util::StreamExt;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sqlx::PgPool;
+use thiserror::Error;
+
+use crate::{
+    add_filter, env, validate_query_with_tables, write_stream_to_hive_dir_with,
+    write_stream_to_store_as, AppError, ExtractBackend, JsonLayout, LakeStore, OutputFormat,
+    PartitionColumn, PostgresDb, Table, DEFAULT_BATCH_SIZE, DEFAULT_PARALLELISM, MAX_DB_CONS,
+};
+
+/// Rows per part file of partitioned tables unless configured.
+pub const DEFAULT_MAX_ROWS_PER_FILE: usize = 1_000_000;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PipelineError {
+    #[error("Invalid pipeline config: {0}")]
+    InvalidConfig(String),
+
+    #[error("Unsupported pipeline config file: {0}, expected .toml, .yaml or .yml")]
+    UnsupportedConfigFile(String),
+
+    #[error("Pipeline has no tables")]
+    NoTables,
+
+    #[error("Unknown table: {0}")]
+    UnknownTable(String),
+
+    #[error("Table {0} has no column {1}")]
+    UnknownColumn(String, String),
+
+    #[error("Table {0} has an invalid partition column: {1}")]
+    InvalidPartition(String, String),
+
+    #[error("Table {0} is partitioned, which needs parquet output to a local destination")]
+    UnsupportedPartitioning(String),
+
+    #[error("Output path is used by more than one table: {0}")]
+    DuplicatePath(String),
+
+    #[error("DATABASE_URL must be set when the config has no database url")]
+    MissingDatabaseUrl,
+}
+
+/// Database connection of a pipeline, the url falls back to `DATABASE_URL`.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    url: Option<Secret<String>>,
+    max_cons: u32,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            max_cons: MAX_DB_CONS,
+        }
+    }
+}
+
+/// Export of one table, the settings left out are taken from the pipeline.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableConfig {
+    name: String,
+    /// Select query on the table, `select * from <name>` by default.
+    #[serde(default)]
+    query: Option<String>,
+    /// Condition added to the `WHERE` clause of the query.
+    #[serde(default)]
+    filter: Option<String>,
+    /// Columns written, in this order. All columns when empty.
+    #[serde(default)]
+    columns: Vec<String>,
+    #[serde(default)]
+    output: Option<OutputFormat>,
+    /// Hive partition columns, either `column`, `day(column)` or `month(column)`.
+    #[serde(default)]
+    partition_by: Vec<String>,
+    /// Path inside the destination, `<name>.<extension>` or `<name>/` when partitioned.
+    #[serde(default)]
+    path: Option<String>,
+}
+
+impl TableConfig {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // checked once the filter is added, so subqueries in it cannot read other tables
+    fn query(&self) -> Result<String, AppError> {
+        let query = match &self.query {
+            Some(query) => query.clone(),
+            None => format!("select * from {}", self.name),
+        };
+        let query = match &self.filter {
+            Some(filter) => add_filter(&query, filter)?,
+            None => query,
+        };
+        Ok(validate_query_with_tables(&query, &[&self.name])?)
+    }
+
+    fn partition_columns(&self) -> Result<Vec<(PartitionColumn, String)>, PipelineError> {
+        self.partition_by
+            .iter()
+            .map(|spec| {
+                let invalid = || PipelineError::InvalidPartition(self.name.clone(), spec.clone());
+                let (transform, column) = match spec.split_once('(') {
+                    Some((transform, rest)) => (
+                        Some(transform.trim()),
+                        rest.strip_suffix(')').ok_or_else(invalid)?,
+                    ),
+                    None => (None, spec.as_str()),
+                };
+                let column = column.trim();
+                let partition = match transform {
+                    None => PartitionColumn::new(column),
+                    Some("day") => PartitionColumn::day(&format!("{}_day", column), column),
+                    Some("month") => PartitionColumn::month(&format!("{}_month", column), column),
+                    Some(_) => return Err(invalid()),
+                };
+                Ok((partition, column.to_string()))
+            })
+            .collect()
+    }
+
+    fn path(&self, format: &OutputFormat) -> String {
+        match &self.path {
+            Some(path) => path.clone(),
+            None if self.partition_by.is_empty() => {
+                format!("{}.{}", self.name, format.extension())
+            }
+            None => format!("{}/", self.name),
+        }
+    }
+}
+
+/// Nightly export job described in a TOML or YAML file:
+///
+/// ```toml
+/// destination = "s3://lake/demo"
+/// backend = "copy_binary"
+/// layout = "json_string"
+///
+/// [output]
+/// format = "parquet"
+/// compression = "zstd(3)"
+///
+/// [[tables]]
+/// name = "flights"
+/// filter = "status = 'Arrived'"
+/// columns = ["flight_id", "flight_no", "scheduled_departure"]
+///
+/// [[tables]]
+/// name = "seats"
+/// output = { format = "csv", delimiter = ";" }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineConfig {
+    #[serde(default)]
+    database: DatabaseConfig,
+    /// Lake url or local directory the tables are written to, see [`LakeStore`].
+    destination: String,
+    #[serde(default)]
+    output: OutputFormat,
+    #[serde(default)]
+    backend: ExtractBackend,
+    /// Layout of the jsonb and point columns, see [`JsonLayout`].
+    #[serde(default)]
+    layout: JsonLayout,
+    #[serde(default = "default_batch_size")]
+    batch_size: usize,
+    /// Number of tables exported at the same time.
+    #[serde(default = "default_parallelism")]
+    parallelism: usize,
+    /// Reads every table from one database snapshot, so they are consistent with each other.
+    #[serde(default = "default_snapshot")]
+    snapshot: bool,
+    #[serde(default = "default_max_rows_per_file")]
+    max_rows_per_file: usize,
+    tables: Vec<TableConfig>,
+}
+
+fn default_batch_size() -> usize {
+    DEFAULT_BATCH_SIZE
+}
+
+fn default_parallelism() -> usize {
+    DEFAULT_PARALLELISM
+}
+
+fn default_snapshot() -> bool {
+    true
+}
+
+fn default_max_rows_per_file() -> usize {
+    DEFAULT_MAX_ROWS_PER_FILE
+}
+
+impl PipelineConfig {
+    /// Reads and validates the config, the format is picked by the file extension.
+    pub async fn load(path: &str) -> Result<Self, AppError> {
+        let content = tokio::fs::read_to_string(path).await?;
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("yaml" | "yml") => Self::from_yaml(&content),
+            _ => Err(PipelineError::UnsupportedConfigFile(path.to_string()).into()),
+        }
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, AppError> {
+        let config: Self = toml::from_str(content)
+            .map_err(|e| PipelineError::InvalidConfig(e.message().to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self, AppError> {
+        let config: Self = serde_yaml::from_str(content)
+            .map_err(|e| PipelineError::InvalidConfig(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn tables(&self) -> &[TableConfig] {
+        &self.tables
+    }
+
+    pub fn database_url(&self) -> Option<&str> {
+        self.database
+            .url
+            .as_ref()
+            .map(|url| url.expose_secret().as_str())
+    }
+
+    pub fn max_cons(&self) -> u32 {
+        self.database.max_cons
+    }
+
+    /// Checks the tables, queries, columns and output paths without touching the database.
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.tables.is_empty() {
+            return Err(PipelineError::NoTables.into());
+        }
+        let store = LakeStore::from_url(&self.destination)?;
+        let mut paths = HashSet::new();
+        for table in &self.tables {
+            let Some(schema) = Table::new(&table.name).map(|t| t.schema()) else {
+                return Err(PipelineError::UnknownTable(table.name.clone()).into());
+            };
+            table.query()?;
+
+            let unknown =
+                |column: &str| PipelineError::UnknownColumn(table.name.clone(), column.to_string());
+            for column in &table.columns {
+                schema.index_of(column).map_err(|_| unknown(column))?;
+            }
+            for (_, column) in table.partition_columns()? {
+                let selected = table.columns.is_empty() || table.columns.contains(&column);
+                if schema.index_of(&column).is_err() || !selected {
+                    return Err(unknown(&column).into());
+                }
+            }
+
+            let format = self.format(table);
+            if let OutputFormat::Parquet(options) = format {
+                options.writer_properties()?;
+            }
+            let path = table.path(format);
+            if !table.partition_by.is_empty()
+                && (!matches!(format, OutputFormat::Parquet(_))
+                    || store.local_path(&path).is_none())
+            {
+                return Err(PipelineError::UnsupportedPartitioning(table.name.clone()).into());
+            }
+            if !paths.insert(store.path(&path)) {
+                return Err(PipelineError::DuplicatePath(path).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Connects with the configured url or `DATABASE_URL`.
+    pub async fn connect(&self) -> Result<PostgresDb, AppError> {
+        let url = match self.database_url() {
+            Some(url) => url.to_string(),
+            None => {
+                dotenv().ok();
+                std::env::var(env::DATABASE_URL_ENV_VAR)
+                    .ok()
+                    .filter(|url| !url.is_empty())
+                    .ok_or(PipelineError::MissingDatabaseUrl)?
+            }
+        };
+        let db = PostgresDb::builder()
+            .with_url(&url)
+            .with_max_cons(self.max_cons())
+            .build()
+            .await?;
+        Ok(db)
+    }
+
+    /// Exports every table, a failing table is reported without stopping the others. Only
+    /// errors before any table starts, like an unreachable lake, fail the whole run.
+    pub async fn run(&self, db: &PostgresDb) -> Result<PipelineReport, AppError> {
+        let store = LakeStore::from_url(&self.destination)?;
+        let snapshot = match self.snapshot {
+            true => Some(db.snapshot().await?),
+            false => None,
+        };
+        let pool = match &snapshot {
+            Some(snapshot) => snapshot.as_ref(),
+            None => db.as_ref(),
+        };
+        let tables = futures_util::stream::iter(&self.tables)
+            .map(|table| {
+                let store = &store;
+                async move {
+                    TableReport {
+                        table: table.name.clone(),
+                        result: self.run_table(pool, store, table).await,
+                    }
+                }
+            })
+            .buffered(self.parallelism.max(1))
+            .collect::<Vec<_>>()
+            .await;
+        if let Some(snapshot) = snapshot {
+            snapshot.close().await?;
+        }
+        Ok(PipelineReport { tables })
+    }
+
+    fn format<'a>(&'a self, table: &'a TableConfig) -> &'a OutputFormat {
+        table.output.as_ref().unwrap_or(&self.output)
+    }
+
+    async fn run_table(
+        &self,
+        pool: &PgPool,
+        store: &LakeStore,
+        config: &TableConfig,
+    ) -> Result<Vec<String>, AppError> {
+        let table = Table::new(&config.name)
+            .ok_or_else(|| PipelineError::UnknownTable(config.name.clone()))?;
+        let format = self.format(config);
+        let path = config.path(format);
+        let stream = table
+            .run_extract_to_stream(
+                pool,
+                &config.query()?,
+                self.batch_size,
+                self.backend,
+                self.layout,
+            )
+            .await?;
+        let stream = select_columns(stream, &config.columns)?;
+
+        if config.partition_by.is_empty() {
+            write_stream_to_store_as(stream, store, &path, format).await?;
+            return Ok(vec![store.url(&path)]);
+        }
+        let (OutputFormat::Parquet(options), Some(dir)) = (format, store.local_path(&path)) else {
+            return Err(PipelineError::UnsupportedPartitioning(config.name.clone()).into());
+        };
+        let partition_by = config
+            .partition_columns()?
+            .into_iter()
+            .map(|(partition, _)| partition)
+            .collect::<Vec<_>>();
+        let files = write_stream_to_hive_dir_with(
+            stream,
+            &dir.to_string_lossy(),
+            &partition_by,
+            self.max_rows_per_file,
+            options,
+        )
+        .await?;
+        Ok(files)
+    }
+}
+
+fn select_columns(
+    stream: SendableRecordBatchStream,
+    columns: &[String],
+) -> Result<SendableRecordBatchStream, AppError> {
+    if columns.is_empty() {
+        return Ok(stream);
+    }
+    let schema = stream.schema();
+    let indices = columns
+        .iter()
+        .map(|column| schema.index_of(column))
+        .collect::<Result<Vec<_>, _>>()?;
+    let projected = Arc::new(schema.project(&indices)?);
+    let stream = stream.map(move |batch| Ok(batch?.project(&indices)?));
+    Ok(Box::pin(RecordBatchStreamAdapter::new(projected, stream)))
+}
+
+/// Outcome of one table of a pipeline run, the written files or the error it failed with.
+#[derive(Debug)]
+pub struct TableReport {
+    table: String,
+    result: Result<Vec<String>, AppError>,
+}
+
+impl TableReport {
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn files(&self) -> &[String] {
+        self.result.as_deref().unwrap_or_default()
+    }
+
+    pub fn error(&self) -> Option<&AppError> {
+        self.result.as_ref().err()
+    }
+}
+
+/// Per-table outcomes of a pipeline run, in config order.
+#[derive(Debug)]
+pub struct PipelineReport {
+    tables: Vec<TableReport>,
+}
+
+impl PipelineReport {
+    pub fn tables(&self) -> &[TableReport] {
+        &self.tables
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.tables.iter().all(|table| table.error().is_none())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+destination = "memory:///lake"
+backend = "copy_binary"
+layout = "json_string"
+
+[database]
+url = "postgres://postgres@localhost/demo"
+
+[output]
+format = "parquet"
+compression = "zstd(3)"
+
+[[tables]]
+name = "flights"
+filter = "status = 'Arrived'"
+columns = ["flight_id", "flight_no"]
+
+[[tables]]
+name = "seats"
+path = "dim/seats.csv"
+output = { format = "csv", delimiter = ";" }
+"#;
+
+    #[test]
+    fn from_toml_test() {
+        let config = PipelineConfig::from_toml(CONFIG).unwrap();
+        assert_eq!(
+            config.database_url(),
+            Some("postgres://postgres@localhost/demo")
+        );
+        assert_eq!(config.max_cons(), MAX_DB_CONS);
+        assert_eq!(config.backend, ExtractBackend::CopyBinary);
+        assert_eq!(config.layout, JsonLayout::JsonString);
+        assert!(config.snapshot);
+        assert_eq!(config.tables().len(), 2);
+        assert_eq!(
+            config.tables[0].query().unwrap(),
+            "SELECT * FROM flights WHERE status = 'Arrived'"
+        );
+        assert_eq!(
+            config.tables[0].path(config.format(&config.tables[0])),
+            "flights.parquet"
+        );
+        assert_eq!(
+            config.tables[1].path(config.format(&config.tables[1])),
+            "dim/seats.csv"
+        );
+    }
+
+    #[test]
+    fn from_yaml_test() {
+        let config = PipelineConfig::from_yaml(
+            r#"
+destination: /tmp/lake
+snapshot: false
+output:
+  format: ndjson
+  compression: gzip
+tables:
+  - name: bookings
+    partition_by: ["month(book_date)"]
+    output:
+      format: parquet
+  - name: tickets
+"#,
+        )
+        .unwrap();
+        assert!(config.database_url().is_none());
+        assert!(!config.snapshot);
+        assert_eq!(
+            config.tables[0].path(config.format(&config.tables[0])),
+            "bookings/"
+        );
+        assert_eq!(
+            config.tables[1].path(config.format(&config.tables[1])),
+            "tickets.ndjson.gz"
+        );
+    }
+
+    fn table_config(table: &str) -> String {
+        format!("destination = \"/tmp/lake\"\n[[tables]]\n{}", table)
+    }
+
+    #[rstest]
+    #[case("destination = \"/tmp/lake\"\ntables = []", PipelineError::NoTables)]
+    #[case(
+        &table_config("name = \"foo\""),
+        PipelineError::UnknownTable("foo".to_string())
+    )]
+    #[case(
+        &table_config("name = \"flights\"\ncolumns = [\"flight_id\", \"foo\"]"),
+        PipelineError::UnknownColumn("flights".to_string(), "foo".to_string())
+    )]
+    #[case(
+        &table_config("name = \"flights\"\ncolumns = [\"flight_id\"]\npartition_by = [\"status\"]"),
+        PipelineError::UnknownColumn("flights".to_string(), "status".to_string())
+    )]
+    #[case(
+        &table_config("name = \"flights\"\npartition_by = [\"week(scheduled_departure)\"]"),
+        PipelineError::InvalidPartition("flights".to_string(), "week(scheduled_departure)".to_string())
+    )]
+    #[case(
+        &table_config("name = \"flights\"\npartition_by = [\"status\"]\noutput = { format = \"csv\" }"),
+        PipelineError::UnsupportedPartitioning("flights".to_string())
+    )]
+    #[case(
+        &table_config("name = \"flights\"\n[[tables]]\nname = \"flights\""),
+        PipelineError::DuplicatePath("flights.parquet".to_string())
+    )]
+    #[case(
+        &table_config("name = \"flights\"\nlimit = 10"),
+        PipelineError::InvalidConfig("unknown field `limit`, expected one of `name`, `query`, `filter`, `columns`, `output`, `partition_by`, `path`".to_string())
+    )]
+    fn validate_test(#[case] config: &str, #[case] expected: PipelineError) {
+        match PipelineConfig::from_toml(config) {
+            Err(AppError::PipelineError(e)) => assert_eq!(e, expected),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[rstest]
+    #[case("output = { format = \"parquet\", max_row_group_size = 0 }")]
+    #[case("output = { format = \"parquet\", compression = \"zstd(30)\" }")]
+    fn validate_parquet_options_test(#[case] output: &str) {
+        let config = table_config(&format!("name = \"flights\"\n{}", output));
+        assert!(matches!(
+            PipelineConfig::from_toml(&config),
+            Err(AppError::ParquetError(_))
+        ));
+    }
+
+    #[rstest]
+    #[case("query = \"select * from seats\"")]
+    #[case("filter = \"flight_id in (select flight_id from ticket_flights)\"")]
+    #[case("filter = \"pg_sleep(10) is not null\"")]
+    fn validate_query_test(#[case] query: &str) {
+        let config = table_config(&format!("name = \"flights\"\n{}", query));
+        assert!(matches!(
+            PipelineConfig::from_toml(&config),
+            Err(AppError::QueryParserError(_))
+        ));
+    }
+}
@@ -0,0 +1,536 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::{DataType, SchemaRef, TimeUnit};
+use datafusion::catalog::Session;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::{Column, ScalarValue};
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::TaskContext;
+use datafusion::logical_expr::expr::{Between, BinaryExpr, Cast, InList, Like};
+use datafusion::logical_expr::{Expr, Operator, TableProviderFilterPushDown};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::streaming::{PartitionStream, StreamingTableExec};
+use datafusion::physical_plan::{ExecutionPlan, SendableRecordBatchStream};
+use datafusion::sql::unparser::dialect::PostgreSqlDialect;
+use datafusion::sql::unparser::Unparser;
+use futures_util::{StreamExt, TryStreamExt};
+use sqlx::types::chrono::DateTime;
+use sqlx::PgPool;
+
+use crate::table_worker::rows_to_stream;
+use crate::{
+    columns_to_schema, fetch_columns, rows_to_record_batch, AppError, GenericTableWorker, Table,
+    DEFAULT_BATCH_SIZE,
+};
+
+/// DataFusion table backed by a postgres table, rows are read lazily on every scan with the
+/// projection, filters and limit of the scan pushed into the SQL where postgres gives the same
+/// result.
+#[derive(Debug)]
+pub struct PostgresTableProvider {
+    pool: PgPool,
+    source: TableSource,
+    batch_size: usize,
+}
+
+impl PostgresTableProvider {
+    /// Provider for one of the demo tables, rows are decoded by its table worker.
+    pub async fn try_new(pool: &PgPool, table: Table) -> Result<Self, AppError> {
+        let columns = fetch_columns(pool, None, table.as_ref()).await?;
+        let source = TableSource {
+            table: Some(table),
+            relation: table.as_ref().to_string(),
+            schema: Arc::new(table.schema()),
+            udt_names: columns
+                .into_iter()
+                .map(|column| (column.name, column.udt_name))
+                .collect(),
+        };
+        Ok(Self::with_source(pool, source))
+    }
+
+    /// Provider for any table or view, see [`GenericTableWorker`].
+    pub async fn try_new_generic(
+        pool: &PgPool,
+        worker: &GenericTableWorker,
+    ) -> Result<Self, AppError> {
+        let columns = fetch_columns(pool, worker.schema_name(), worker.as_ref()).await?;
+        let relation = match worker.schema_name() {
+            Some(schema_name) => format!(
+                "{}.{}",
+                quote_ident(schema_name),
+                quote_ident(worker.as_ref())
+            ),
+            None => quote_ident(worker.as_ref()),
+        };
+        let source = TableSource {
+            table: None,
+            relation,
+            schema: Arc::new(columns_to_schema(&columns)?),
+            udt_names: columns
+                .into_iter()
+                .map(|column| (column.name, column.udt_name))
+                .collect(),
+        };
+        Ok(Self::with_source(pool, source))
+    }
+
+    fn with_source(pool: &PgPool, source: TableSource) -> Self {
+        Self {
+            pool: pool.clone(),
+            source,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    pub fn with_batch_size(self, batch_size: usize) -> Self {
+        Self { batch_size, ..self }
+    }
+}
+
+#[async_trait]
+impl TableProvider for PostgresTableProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.source.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        Ok(filters
+            .iter()
+            .map(|filter| self.source.filter_pushdown(filter))
+            .collect())
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let (columns, rows_projection) = self.source.select_columns(projection);
+        let query = self.source.select_query(&columns, filters, limit)?;
+        let rows_schema = Arc::new(self.source.schema.project(&columns)?);
+        let partition = PostgresPartition {
+            pool: self.pool.clone(),
+            table: self.source.table,
+            query,
+            schema: Arc::new(rows_schema.project(&rows_projection)?),
+            rows_schema,
+            rows_projection,
+            batch_size: self.batch_size,
+        };
+        let exec = StreamingTableExec::try_new(
+            partition.schema.clone(),
+            vec![Arc::new(partition)],
+            None,
+            vec![],
+            false,
+            limit,
+        )?;
+        Ok(Arc::new(exec))
+    }
+}
+
+#[derive(Debug)]
+struct TableSource {
+    // known tables are decoded by their worker
+    table: Option<Table>,
+    relation: String,
+    schema: SchemaRef,
+    udt_names: HashMap<String, String>,
+}
+
+impl TableSource {
+    // columns to select and the projection applied to the fetched rows
+    fn select_columns(&self, projection: Option<&Vec<usize>>) -> (Vec<usize>, Vec<usize>) {
+        let projection = projection
+            .cloned()
+            .unwrap_or_else(|| (0..self.schema.fields().len()).collect());
+        if projection.is_empty() {
+            // rows are decoded by column, fetch one so an empty projection still counts them
+            return (vec![0], vec![]);
+        }
+        let rows_projection = (0..projection.len()).collect();
+        (projection, rows_projection)
+    }
+
+    fn select_query(
+        &self,
+        columns: &[usize],
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<String> {
+        let columns = columns
+            .iter()
+            .map(|i| quote_ident(self.schema.field(*i).name()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut query = format!("select {} from {}", columns, self.relation);
+        let conditions = filters
+            .iter()
+            .map(filter_to_sql)
+            .collect::<Result<Vec<_>>>()?;
+        if !conditions.is_empty() {
+            query.push_str(&format!(" where {}", conditions.join(" and ")));
+        }
+        if let Some(limit) = limit {
+            query.push_str(&format!(" limit {}", limit));
+        }
+        Ok(query)
+    }
+
+    fn filter_pushdown(&self, expr: &Expr) -> TableProviderFilterPushDown {
+        use TableProviderFilterPushDown::*;
+
+        match expr {
+            Expr::Column(column) => self.column_pushdown(&column.name),
+            Expr::Literal(value) => literal_pushdown(value),
+            Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+                let support = match op {
+                    Operator::And | Operator::Or | Operator::Eq | Operator::NotEq => Exact,
+                    // text is ordered by the postgres collation
+                    Operator::Lt | Operator::LtEq | Operator::Gt | Operator::GtEq
+                        if !self.has_text_column(expr) =>
+                    {
+                        Exact
+                    }
+                    _ => Unsupported,
+                };
+                weakest([
+                    support,
+                    self.filter_pushdown(left),
+                    self.filter_pushdown(right),
+                ])
+            }
+            Expr::Not(expr)
+            | Expr::IsNull(expr)
+            | Expr::IsNotNull(expr)
+            | Expr::IsTrue(expr)
+            | Expr::IsFalse(expr)
+            | Expr::IsNotTrue(expr)
+            | Expr::IsNotFalse(expr) => self.filter_pushdown(expr),
+            Expr::Between(Between {
+                expr: inner,
+                low,
+                high,
+                ..
+            }) if !self.has_text_column(expr) => {
+                weakest([inner, low, high].map(|expr| self.filter_pushdown(expr)))
+            }
+            Expr::InList(InList { expr, list, .. }) => weakest(
+                std::iter::once(expr.as_ref())
+                    .chain(list)
+                    .map(|expr| self.filter_pushdown(expr)),
+            ),
+            Expr::Like(Like {
+                expr,
+                pattern,
+                escape_char: None,
+                ..
+            }) => weakest([self.filter_pushdown(expr), self.filter_pushdown(pattern)]),
+            // integer columns widened by type coercion
+            Expr::Cast(Cast {
+                expr,
+                data_type: DataType::Int16 | DataType::Int32 | DataType::Int64 | DataType::Float64,
+            }) => match expr.as_ref() {
+                Expr::Column(column)
+                    if matches!(self.udt_name(&column.name), Some("int2" | "int4" | "int8")) =>
+                {
+                    Exact
+                }
+                _ => Unsupported,
+            },
+            _ => Unsupported,
+        }
+    }
+
+    fn column_pushdown(&self, name: &str) -> TableProviderFilterPushDown {
+        use TableProviderFilterPushDown::*;
+
+        let Ok(field) = self.schema.field_with_name(name) else {
+            return Unsupported;
+        };
+        match (self.udt_name(name), field.data_type()) {
+            (
+                Some("bool" | "int2" | "int4" | "int8" | "float8" | "text" | "varchar" | "date"),
+                _,
+            ) => Exact,
+            // unconstrained numerics are kept as text
+            (Some("numeric"), DataType::Decimal128(_, _)) => Exact,
+            // padded chars, single precision floats and timestamps compare a little differently
+            (Some("bpchar" | "float4" | "timestamp" | "timestamptz"), _) => Inexact,
+            _ => Unsupported,
+        }
+    }
+
+    fn has_text_column(&self, expr: &Expr) -> bool {
+        expr.column_refs().iter().any(|column| {
+            matches!(
+                self.udt_name(&column.name),
+                Some("text" | "varchar" | "bpchar")
+            )
+        })
+    }
+
+    // postgres type of a column the schema field maps to one to one, flattened json fields of
+    // the demo tables have none
+    fn udt_name(&self, name: &str) -> Option<&str> {
+        self.udt_names.get(name).map(String::as_str)
+    }
+}
+
+fn literal_pushdown(value: &ScalarValue) -> TableProviderFilterPushDown {
+    use TableProviderFilterPushDown::*;
+
+    match value {
+        ScalarValue::Null
+        | ScalarValue::Boolean(_)
+        | ScalarValue::Int8(_)
+        | ScalarValue::Int16(_)
+        | ScalarValue::Int32(_)
+        | ScalarValue::Int64(_)
+        | ScalarValue::UInt8(_)
+        | ScalarValue::UInt16(_)
+        | ScalarValue::UInt32(_)
+        | ScalarValue::UInt64(_)
+        | ScalarValue::Float64(_)
+        | ScalarValue::Utf8(_)
+        | ScalarValue::LargeUtf8(_)
+        | ScalarValue::Date32(_)
+        | ScalarValue::Decimal128(_, _, _) => Exact,
+        // postgres keeps microseconds, see `timestamptz_literal` for the zone
+        ScalarValue::Float32(_)
+        | ScalarValue::TimestampSecond(_, _)
+        | ScalarValue::TimestampMillisecond(_, _)
+        | ScalarValue::TimestampMicrosecond(_, _) => Inexact,
+        _ => Unsupported,
+    }
+}
+
+fn weakest(
+    supports: impl IntoIterator<Item = TableProviderFilterPushDown>,
+) -> TableProviderFilterPushDown {
+    supports.into_iter().fold(
+        TableProviderFilterPushDown::Exact,
+        |weakest, support| match (weakest, support) {
+            (TableProviderFilterPushDown::Unsupported, _)
+            | (_, TableProviderFilterPushDown::Unsupported) => {
+                TableProviderFilterPushDown::Unsupported
+            }
+            (TableProviderFilterPushDown::Inexact, _)
+            | (_, TableProviderFilterPushDown::Inexact) => TableProviderFilterPushDown::Inexact,
+            _ => TableProviderFilterPushDown::Exact,
+        },
+    )
+}
+
+fn filter_to_sql(filter: &Expr) -> Result<String> {
+    // columns are qualified by the name the table is registered under, which postgres doesn't know
+    let filter = filter
+        .clone()
+        .transform(|expr| match expr {
+            Expr::Column(column) => Ok(Transformed::yes(Expr::Column(Column::new_unqualified(
+                column.name,
+            )))),
+            Expr::Literal(value) => Ok(match timestamptz_literal(&value) {
+                Some(expr) => Transformed::yes(expr),
+                None => Transformed::no(Expr::Literal(value)),
+            }),
+            expr => Ok(Transformed::no(expr)),
+        })?
+        .data;
+    let dialect = PostgreSqlDialect {};
+    Ok(Unparser::new(&dialect).expr_to_sql(&filter)?.to_string())
+}
+
+// the unparser casts timestamp literals to `timestamp`, which drops their zone and has postgres
+// compare them in the session time zone, so zoned ones are cast from their UTC time instead
+fn timestamptz_literal(value: &ScalarValue) -> Option<Expr> {
+    let (micros, tz) = match value {
+        ScalarValue::TimestampSecond(Some(secs), Some(tz)) => (secs.checked_mul(1_000_000)?, tz),
+        ScalarValue::TimestampMillisecond(Some(millis), Some(tz)) => {
+            (millis.checked_mul(1_000)?, tz)
+        }
+        ScalarValue::TimestampMicrosecond(Some(micros), Some(tz)) => (*micros, tz),
+        _ => return None,
+    };
+    let timestamp = DateTime::from_timestamp_micros(micros)?;
+    Some(Expr::Cast(Cast::new(
+        Box::new(Expr::Literal(ScalarValue::Utf8(Some(
+            timestamp.format("%Y-%m-%dT%H:%M:%S%.fZ").to_string(),
+        )))),
+        DataType::Timestamp(TimeUnit::Microsecond, Some(tz.clone())),
+    )))
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+#[derive(Debug)]
+struct PostgresPartition {
+    pool: PgPool,
+    table: Option<Table>,
+    query: String,
+    rows_schema: SchemaRef,
+    rows_projection: Vec<usize>,
+    schema: SchemaRef,
+    batch_size: usize,
+}
+
+impl PartitionStream for PostgresPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let rows: SendableRecordBatchStream = match self.table {
+            Some(table) => {
+                let pool = self.pool.clone();
+                let query = self.query.clone();
+                let batch_size = self.batch_size;
+                let stream = futures_util::stream::once(async move {
+                    table
+                        .run_export_table_to_stream(&pool, &query, batch_size)
+                        .await
+                        .map_err(DataFusionError::from)
+                })
+                .try_flatten();
+                Box::pin(RecordBatchStreamAdapter::new(
+                    self.rows_schema.clone(),
+                    stream,
+                ))
+            }
+            None => {
+                let schema = self.rows_schema.clone();
+                rows_to_stream(
+                    &self.pool,
+                    self.query.clone(),
+                    schema.clone(),
+                    self.batch_size,
+                    move |rows| rows_to_record_batch(schema.clone(), rows),
+                )
+            }
+        };
+        let projection = self.rows_projection.clone();
+        let stream = rows.map(move |batch| Ok(batch?.project(&projection)?));
+        Box::pin(RecordBatchStreamAdapter::new(self.schema.clone(), stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::prelude::*;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn source(table: Option<Table>) -> TableSource {
+        let table_name = table.map(|table| table.as_ref().to_string());
+        TableSource {
+            table,
+            relation: table_name.unwrap_or_else(|| quote_ident("flights")),
+            schema: Arc::new(Table::FlightsTable.schema()),
+            udt_names: [
+                ("flight_id", "int4"),
+                ("flight_no", "bpchar"),
+                ("scheduled_departure", "timestamptz"),
+                ("departure_airport", "bpchar"),
+                ("status", "varchar"),
+            ]
+            .into_iter()
+            .map(|(name, udt_name)| (name.to_string(), udt_name.to_string()))
+            .collect(),
+        }
+    }
+
+    #[rstest]
+    #[case(col("flight_id").eq(lit(1)), TableProviderFilterPushDown::Exact)]
+    #[case(col("flight_id").gt(lit(1)).and(col("status").eq(lit("Arrived"))), TableProviderFilterPushDown::Exact)]
+    #[case(col("status").in_list(vec![lit("Arrived"), lit("Scheduled")], false), TableProviderFilterPushDown::Exact)]
+    #[case(col("status").like(lit("Arr%")), TableProviderFilterPushDown::Exact)]
+    #[case(col("flight_id").is_null().not(), TableProviderFilterPushDown::Exact)]
+    #[case(cast(col("flight_id"), DataType::Int64).eq(lit(1i64)), TableProviderFilterPushDown::Exact)]
+    #[case(col("flight_no").eq(lit("PG0001")), TableProviderFilterPushDown::Inexact)]
+    #[case(col("status").gt(lit("A")), TableProviderFilterPushDown::Unsupported)]
+    #[case(col("flight_id").between(lit(1), lit(5)), TableProviderFilterPushDown::Exact)]
+    #[case((col("flight_id") + lit(1)).eq(lit(2)), TableProviderFilterPushDown::Unsupported)]
+    #[case(col("aircraft_code").eq(lit("773")), TableProviderFilterPushDown::Unsupported)]
+    #[case(
+        col("scheduled_departure").lt(lit(ScalarValue::TimestampMicrosecond(Some(0), Some("+00:00".into())))),
+        TableProviderFilterPushDown::Inexact
+    )]
+    #[case(
+        col("scheduled_departure").lt(lit(ScalarValue::TimestampNanosecond(Some(1), Some("+00:00".into())))),
+        TableProviderFilterPushDown::Unsupported
+    )]
+    fn filter_pushdown_test(#[case] filter: Expr, #[case] expected: TableProviderFilterPushDown) {
+        assert_eq!(source(None).filter_pushdown(&filter), expected);
+    }
+
+    #[rstest]
+    #[case(Some(Table::FlightsTable), Some(vec![2, 0]), (vec![2, 0], vec![0, 1]))]
+    #[case(Some(Table::FlightsTable), None, ((0..10).collect(), (0..10).collect()))]
+    #[case(None, Some(vec![2, 0]), (vec![2, 0], vec![0, 1]))]
+    #[case(None, Some(vec![]), (vec![0], vec![]))]
+    fn select_columns_test(
+        #[case] table: Option<Table>,
+        #[case] projection: Option<Vec<usize>>,
+        #[case] expected: (Vec<usize>, Vec<usize>),
+    ) {
+        assert_eq!(source(table).select_columns(projection.as_ref()), expected);
+    }
+
+    #[rstest]
+    #[case(
+        ScalarValue::TimestampMicrosecond(Some(1_497_342_540_000_000), Some("+00:00".into())),
+        r#"("scheduled_departure" < CAST('2017-06-13T08:29:00Z' AS TIMESTAMP WITH TIME ZONE))"#
+    )]
+    #[case(
+        ScalarValue::TimestampMillisecond(Some(1_497_342_540_123), Some("Europe/Moscow".into())),
+        r#"("scheduled_departure" < CAST('2017-06-13T08:29:00.123Z' AS TIMESTAMP WITH TIME ZONE))"#
+    )]
+    #[case(
+        ScalarValue::TimestampMicrosecond(Some(1_497_342_540_000_000), None),
+        r#"("scheduled_departure" < CAST('2017-06-13 08:29:00' AS TIMESTAMP))"#
+    )]
+    fn filter_to_sql_test(#[case] value: ScalarValue, #[case] expected: &str) {
+        let filter = col("scheduled_departure").lt(lit(value));
+        assert_eq!(filter_to_sql(&filter).unwrap(), expected);
+    }
+
+    #[test]
+    fn select_query_test() {
+        let filters = [
+            col("f.flight_id").gt(lit(10)),
+            col("status").eq(lit("It's")),
+        ];
+        let query = source(None)
+            .select_query(&[0, 1], &filters, Some(5))
+            .unwrap();
+        assert_eq!(
+            query,
+            r#"select "flight_id", "flight_no" from "flights" where ("flight_id" > 10) and ("status" = 'It''s') limit 5"#
+        );
+        let query = source(Some(Table::FlightsTable))
+            .select_query(&[6], &[], None)
+            .unwrap();
+        assert_eq!(query, r#"select "status" from flights"#);
+    }
+}
@@ -1,11 +1,25 @@
+use datafusion::arrow::datatypes::Schema;
+use datafusion::physical_plan::SendableRecordBatchStream;
 use datafusion::prelude::{DataFrame, SessionContext};
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 
 use crate::table_worker::helpers::*;
 use crate::{table_worker::TableWorkerDyn, utils::*};
 use crate::{tables::*, AppError};
 
-#[derive(Debug, PartialEq)]
+/// How table exports read rows from postgres.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtractBackend {
+    /// Rows fetched with `sqlx` and decoded one by one.
+    #[default]
+    Query,
+    /// Binary `COPY ... TO STDOUT` decoded straight into Arrow arrays.
+    CopyBinary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Table {
     AircraftDataTable,
     AirportsDataTable,
@@ -46,6 +60,20 @@ impl Table {
             _ => None,
         }
     }
+
+    /// Arrow schema of the table rows, see the `schema()` of each table struct.
+    pub fn schema(&self) -> Schema {
+        match *self {
+            Self::AircraftDataTable => AircraftsData::schema(),
+            Self::AirportsDataTable => AirportsData::schema(),
+            Self::BoardingPassesTable => BoardingPasses::schema(),
+            Self::BookingsTable => Bookings::schema(),
+            Self::FlightsTable => Flights::schema(),
+            Self::SeatsTable => Seats::schema(),
+            Self::TicketsTable => Tickets::schema(),
+            Self::TicketFlightsTable => TicketFlights::schema(),
+        }
+    }
 }
 
 // Dynamic dispatch
@@ -136,6 +164,146 @@ impl Table {
             }
         }
     }
+
+    pub async fn run_query_table_to_stream(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        match *self {
+            Self::AircraftDataTable => {
+                process_table_to_stream::<AircraftsData>(pool, query, batch_size).await
+            }
+            Self::AirportsDataTable => {
+                process_table_to_stream::<AirportsData>(pool, query, batch_size).await
+            }
+            Self::BoardingPassesTable => {
+                process_table_to_stream::<BoardingPasses>(pool, query, batch_size).await
+            }
+            Self::BookingsTable => {
+                process_table_to_stream::<Bookings>(pool, query, batch_size).await
+            }
+            Self::FlightsTable => process_table_to_stream::<Flights>(pool, query, batch_size).await,
+            Self::SeatsTable => process_table_to_stream::<Seats>(pool, query, batch_size).await,
+            Self::TicketsTable => process_table_to_stream::<Tickets>(pool, query, batch_size).await,
+            Self::TicketFlightsTable => {
+                process_table_to_stream::<TicketFlights>(pool, query, batch_size).await
+            }
+        }
+    }
+
+    pub async fn run_export_table_to_stream(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        self.run_export_table_to_stream_with(pool, query, batch_size, JsonLayout::default())
+            .await
+    }
+
+    pub async fn run_export_table_to_stream_with(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+        layout: JsonLayout,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        match *self {
+            Self::AircraftDataTable => {
+                process_export_to_stream::<AircraftsData>(pool, query, batch_size, layout).await
+            }
+            Self::AirportsDataTable => {
+                process_export_to_stream::<AirportsData>(pool, query, batch_size, layout).await
+            }
+            Self::BoardingPassesTable => {
+                process_export_to_stream::<BoardingPasses>(pool, query, batch_size, layout).await
+            }
+            Self::BookingsTable => {
+                process_export_to_stream::<Bookings>(pool, query, batch_size, layout).await
+            }
+            Self::FlightsTable => {
+                process_export_to_stream::<Flights>(pool, query, batch_size, layout).await
+            }
+            Self::SeatsTable => {
+                process_export_to_stream::<Seats>(pool, query, batch_size, layout).await
+            }
+            Self::TicketsTable => {
+                process_export_to_stream::<Tickets>(pool, query, batch_size, layout).await
+            }
+            Self::TicketFlightsTable => {
+                process_export_to_stream::<TicketFlights>(pool, query, batch_size, layout).await
+            }
+        }
+    }
+
+    pub async fn run_copy_table_to_stream(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        self.run_copy_table_to_stream_with(pool, query, batch_size, JsonLayout::default())
+            .await
+    }
+
+    pub async fn run_copy_table_to_stream_with(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+        layout: JsonLayout,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        match *self {
+            Self::AircraftDataTable => {
+                process_copy_to_stream::<AircraftsData>(pool, query, batch_size, layout).await
+            }
+            Self::AirportsDataTable => {
+                process_copy_to_stream::<AirportsData>(pool, query, batch_size, layout).await
+            }
+            Self::BoardingPassesTable => {
+                process_copy_to_stream::<BoardingPasses>(pool, query, batch_size, layout).await
+            }
+            Self::BookingsTable => {
+                process_copy_to_stream::<Bookings>(pool, query, batch_size, layout).await
+            }
+            Self::FlightsTable => {
+                process_copy_to_stream::<Flights>(pool, query, batch_size, layout).await
+            }
+            Self::SeatsTable => {
+                process_copy_to_stream::<Seats>(pool, query, batch_size, layout).await
+            }
+            Self::TicketsTable => {
+                process_copy_to_stream::<Tickets>(pool, query, batch_size, layout).await
+            }
+            Self::TicketFlightsTable => {
+                process_copy_to_stream::<TicketFlights>(pool, query, batch_size, layout).await
+            }
+        }
+    }
+
+    /// Exports `query` with the given backend, see [`ExtractBackend`], and jsonb and point
+    /// columns laid out as `layout`.
+    pub async fn run_extract_to_stream(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+        backend: ExtractBackend,
+        layout: JsonLayout,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        match backend {
+            ExtractBackend::Query => {
+                self.run_export_table_to_stream_with(pool, query, batch_size, layout)
+                    .await
+            }
+            ExtractBackend::CopyBinary => {
+                self.run_copy_table_to_stream_with(pool, query, batch_size, layout)
+                    .await
+            }
+        }
+    }
 }
 
 #[cfg(test)]
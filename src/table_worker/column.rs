@@ -0,0 +1,236 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{
+    ArrayRef, BooleanArray, Decimal128Array, Float32Array, Float64Array, Int16Array, Int32Array,
+    Int64Array, StringArray, TimestampMicrosecondArray,
+};
+use datafusion::arrow::datatypes::{DataType, TimeUnit};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::postgres::{types::PgPoint, PgRow};
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::{Decimal, Json};
+use sqlx::{Decode, Postgres, Row, Type};
+
+use crate::tables::JsonLayout;
+use crate::{
+    decimal_to_i128, point_struct_array, point_struct_type, utf8_struct_array, utf8_struct_type,
+    AppError, TIMESTAMP_TZ,
+};
+
+/// Arrow mapping of a record field type, used by `#[derive(TableWorker)]`.
+///
+/// `Option<T>` fields map through `T`, with `None` values becoming nulls.
+pub trait ArrowColumn: Sized {
+    fn data_type(layout: JsonLayout) -> DataType;
+
+    fn to_array(values: &[Option<&Self>], layout: JsonLayout) -> Result<ArrayRef, AppError>;
+
+    /// Formats column `name` of `row` for `query_table_to_string`.
+    fn format_value(row: &PgRow, name: &str) -> Result<Option<String>, AppError>;
+}
+
+/// Arrow mapping of a field marked `#[table_worker(flatten)]`.
+pub trait FlattenColumn: Sized {
+    fn data_type(layout: JsonLayout) -> DataType;
+
+    fn to_array(values: &[Option<&Self>], layout: JsonLayout) -> Result<ArrayRef, AppError>;
+}
+
+/// Flat jsonb object whose keys become the children of a struct column.
+pub trait JsonFields: Serialize {
+    const FIELDS: &'static [&'static str];
+
+    fn values(&self) -> Vec<Option<&str>>;
+}
+
+fn format_decoded<'r, T>(row: &'r PgRow, name: &str) -> Result<Option<String>, AppError>
+where
+    T: Decode<'r, Postgres> + Type<Postgres> + ToString,
+{
+    Ok(row
+        .try_get::<Option<T>, _>(name)?
+        .map(|val| val.to_string()))
+}
+
+macro_rules! primitive_column {
+    ($ty:ty, $data_type:expr, $array:ident) => {
+        impl ArrowColumn for $ty {
+            fn data_type(_layout: JsonLayout) -> DataType {
+                $data_type
+            }
+
+            fn to_array(
+                values: &[Option<&Self>],
+                _layout: JsonLayout,
+            ) -> Result<ArrayRef, AppError> {
+                let values = values.iter().map(|val| val.copied()).collect::<Vec<_>>();
+                Ok(Arc::new($array::from(values)))
+            }
+
+            fn format_value(row: &PgRow, name: &str) -> Result<Option<String>, AppError> {
+                format_decoded::<$ty>(row, name)
+            }
+        }
+    };
+}
+
+primitive_column!(bool, DataType::Boolean, BooleanArray);
+primitive_column!(i16, DataType::Int16, Int16Array);
+primitive_column!(i32, DataType::Int32, Int32Array);
+primitive_column!(i64, DataType::Int64, Int64Array);
+primitive_column!(f32, DataType::Float32, Float32Array);
+primitive_column!(f64, DataType::Float64, Float64Array);
+
+impl ArrowColumn for String {
+    fn data_type(_layout: JsonLayout) -> DataType {
+        DataType::Utf8
+    }
+
+    fn to_array(values: &[Option<&Self>], _layout: JsonLayout) -> Result<ArrayRef, AppError> {
+        let values = values
+            .iter()
+            .map(|val| val.map(String::as_str))
+            .collect::<Vec<_>>();
+        Ok(Arc::new(StringArray::from(values)))
+    }
+
+    fn format_value(row: &PgRow, name: &str) -> Result<Option<String>, AppError> {
+        format_decoded::<String>(row, name)
+    }
+}
+
+impl ArrowColumn for DateTime<Utc> {
+    fn data_type(_layout: JsonLayout) -> DataType {
+        DataType::Timestamp(TimeUnit::Microsecond, Some(TIMESTAMP_TZ.into()))
+    }
+
+    fn to_array(values: &[Option<&Self>], _layout: JsonLayout) -> Result<ArrayRef, AppError> {
+        let values = values
+            .iter()
+            .map(|val| val.map(|val| val.timestamp_micros()))
+            .collect::<Vec<_>>();
+        Ok(Arc::new(
+            TimestampMicrosecondArray::from(values).with_timezone(TIMESTAMP_TZ),
+        ))
+    }
+
+    fn format_value(row: &PgRow, name: &str) -> Result<Option<String>, AppError> {
+        format_decoded::<DateTime<Utc>>(row, name)
+    }
+}
+
+/// Without `#[table_worker(decimal(..))]` the exact value is kept as text.
+impl ArrowColumn for Decimal {
+    fn data_type(_layout: JsonLayout) -> DataType {
+        DataType::Utf8
+    }
+
+    fn to_array(values: &[Option<&Self>], _layout: JsonLayout) -> Result<ArrayRef, AppError> {
+        let values = values
+            .iter()
+            .map(|val| val.map(|val| val.to_string()))
+            .collect::<Vec<_>>();
+        Ok(Arc::new(StringArray::from(values)))
+    }
+
+    fn format_value(row: &PgRow, name: &str) -> Result<Option<String>, AppError> {
+        format_decoded::<Decimal>(row, name)
+    }
+}
+
+/// Serialized as a JSON string, see [`FlattenColumn`] for the struct layout.
+impl<T: Serialize> ArrowColumn for Json<T> {
+    fn data_type(_layout: JsonLayout) -> DataType {
+        DataType::Utf8
+    }
+
+    fn to_array(values: &[Option<&Self>], _layout: JsonLayout) -> Result<ArrayRef, AppError> {
+        let values = values
+            .iter()
+            .map(|val| val.map(serde_json::to_string).transpose())
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        Ok(Arc::new(StringArray::from(values)))
+    }
+
+    fn format_value(row: &PgRow, name: &str) -> Result<Option<String>, AppError> {
+        format_decoded::<Value>(row, name)
+    }
+}
+
+impl<T: JsonFields> FlattenColumn for Json<T> {
+    fn data_type(layout: JsonLayout) -> DataType {
+        match layout {
+            JsonLayout::Struct => utf8_struct_type(T::FIELDS),
+            JsonLayout::JsonString => DataType::Utf8,
+        }
+    }
+
+    fn to_array(values: &[Option<&Self>], layout: JsonLayout) -> Result<ArrayRef, AppError> {
+        match layout {
+            JsonLayout::Struct => {
+                let values = values
+                    .iter()
+                    .map(|val| val.map(|val| val.values()))
+                    .collect::<Vec<_>>();
+                Ok(Arc::new(utf8_struct_array(T::FIELDS, &values)?))
+            }
+            JsonLayout::JsonString => <Self as ArrowColumn>::to_array(values, layout),
+        }
+    }
+}
+
+impl ArrowColumn for PgPoint {
+    fn data_type(layout: JsonLayout) -> DataType {
+        match layout {
+            JsonLayout::Struct => point_struct_type(),
+            JsonLayout::JsonString => DataType::Utf8,
+        }
+    }
+
+    fn to_array(values: &[Option<&Self>], layout: JsonLayout) -> Result<ArrayRef, AppError> {
+        match layout {
+            JsonLayout::Struct => {
+                let values = values
+                    .iter()
+                    .map(|val| val.map(|val| (val.x, val.y)))
+                    .collect::<Vec<_>>();
+                Ok(Arc::new(point_struct_array(&values)?))
+            }
+            JsonLayout::JsonString => {
+                let values = values
+                    .iter()
+                    .map(|val| val.map(|val| serde_json::json!({ "x": val.x, "y": val.y })))
+                    .map(|val| val.map(|val| val.to_string()))
+                    .collect::<Vec<_>>();
+                Ok(Arc::new(StringArray::from(values)))
+            }
+        }
+    }
+
+    fn format_value(row: &PgRow, name: &str) -> Result<Option<String>, AppError> {
+        Ok(row
+            .try_get::<Option<PgPoint>, _>(name)?
+            .map(|val| format!("{:?}", val)))
+    }
+}
+
+/// Builds a `Decimal128` column for a field marked `#[table_worker(decimal(precision, scale))]`.
+pub fn decimal_array(
+    values: &[Option<&Decimal>],
+    precision: u8,
+    scale: i8,
+) -> Result<ArrayRef, AppError> {
+    let values = values
+        .iter()
+        .map(|val| val.map(|val| decimal_to_i128(val, scale)))
+        .collect::<Vec<_>>();
+    Ok(Arc::new(
+        Decimal128Array::from(values).with_precision_and_scale(precision, scale)?,
+    ))
+}
+
+/// Formats column `name` of `row`, a null value is shown as `null`.
+pub fn format_column<T: ArrowColumn>(row: &PgRow, name: &str) -> Result<String, AppError> {
+    Ok(T::format_value(row, name)?.unwrap_or_else(|| "null".to_string()))
+}
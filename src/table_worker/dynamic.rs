@@ -1,8 +1,9 @@
 use async_trait::async_trait;
+use datafusion::physical_plan::SendableRecordBatchStream;
 use datafusion::prelude::*;
 use sqlx::PgPool;
 
-use crate::AppError;
+use crate::{AppError, JsonLayout};
 
 #[async_trait]
 pub trait TableWorkerDyn {
@@ -19,4 +20,40 @@ pub trait TableWorkerDyn {
         query: &str,
         ctx: &SessionContext,
     ) -> Result<DataFrame, AppError>;
+    async fn query_table_to_stream(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError>;
+    /// Same as `query_table_to_stream` without the `MAX_ROWS` cap, for full table exports.
+    async fn export_table_to_stream(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError>;
+    /// Same as `export_table_to_stream`, with jsonb and point columns laid out as `layout`.
+    async fn export_table_to_stream_with(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+        layout: JsonLayout,
+    ) -> Result<SendableRecordBatchStream, AppError>;
+    /// Same as `export_table_to_stream`, reading the rows through binary `COPY` instead.
+    async fn copy_table_to_stream(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError>;
+    /// Same as `copy_table_to_stream`, with jsonb and point columns laid out as `layout`.
+    async fn copy_table_to_stream_with(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+        layout: JsonLayout,
+    ) -> Result<SendableRecordBatchStream, AppError>;
 }
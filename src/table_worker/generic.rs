@@ -0,0 +1,225 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::array::RecordBatch;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use datafusion::arrow::util::pretty::pretty_format_batches;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use datafusion::prelude::*;
+use sqlx::PgPool;
+use tokio::sync::OnceCell;
+
+use super::{copy_to_stream, record_batch_to_json, rows_to_stream, TableWorkerDyn};
+use crate::{
+    columns_to_schema, fetch_columns, prepare_query_with_policy, query_projection,
+    rows_to_record_batch, schema_with_layout, validate_query_with_policy, AppError, JsonLayout,
+    QueryPolicy,
+};
+
+/// Worker for any table or view, its Arrow schema is derived from `information_schema`
+/// on first use.
+#[derive(Debug, Default)]
+pub struct GenericTableWorker {
+    schema_name: Option<String>,
+    table_name: String,
+    schema: OnceCell<SchemaRef>,
+}
+
+impl AsRef<str> for GenericTableWorker {
+    fn as_ref(&self) -> &str {
+        &self.table_name
+    }
+}
+
+impl GenericTableWorker {
+    pub fn new(table_name: &str) -> Self {
+        Self {
+            table_name: table_name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_schema_name(self, schema_name: &str) -> Self {
+        Self {
+            schema_name: Some(schema_name.to_string()),
+            ..self
+        }
+    }
+
+    pub fn schema_name(&self) -> Option<&str> {
+        self.schema_name.as_deref()
+    }
+
+    pub async fn schema(&self, pool: &PgPool) -> Result<SchemaRef, AppError> {
+        let schema = self
+            .schema
+            .get_or_try_init(|| async {
+                let columns =
+                    fetch_columns(pool, self.schema_name.as_deref(), &self.table_name).await?;
+                Ok::<_, AppError>(Arc::new(columns_to_schema(&columns)?))
+            })
+            .await?;
+        Ok(schema.clone())
+    }
+
+    /// Schema of the columns `query` returns, in the order postgres describes them. Catalog
+    /// schemas have jsonb as JSON strings, `layout` only changes point columns.
+    async fn query_schema(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        layout: JsonLayout,
+    ) -> Result<SchemaRef, AppError> {
+        let schema = self.schema(pool).await?;
+        let schema = schema_with_layout(&schema, layout);
+        let projection = query_projection(pool, query, &self.table_name, &schema).await?;
+        Ok(Arc::new(schema.project(&projection)?))
+    }
+
+    // queries may only qualify the table with the schema it is read from, which is unknown
+    // without a schema name, so they could not reach a table of the same name elsewhere
+    fn policy(&self) -> QueryPolicy {
+        let schemas = self.schema_name.as_deref().into_iter().collect::<Vec<_>>();
+        QueryPolicy::default()
+            .with_tables(&[&self.table_name])
+            .with_schemas(&schemas)
+    }
+
+    fn prepare_query(&self, query: &str) -> Result<String, AppError> {
+        Ok(prepare_query_with_policy(query, &self.policy())?)
+    }
+
+    async fn stream(
+        &self,
+        pool: &PgPool,
+        query: String,
+        batch_size: usize,
+        layout: JsonLayout,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        let schema = self.query_schema(pool, &query, layout).await?;
+        let stream = rows_to_stream(pool, query, schema.clone(), batch_size, move |rows| {
+            rows_to_record_batch(schema.clone(), rows)
+        });
+        Ok(stream)
+    }
+
+    async fn query_to_record_batch(
+        &self,
+        pool: &PgPool,
+        query: &str,
+    ) -> Result<RecordBatch, AppError> {
+        let query = self.prepare_query(query)?;
+        let schema = self
+            .query_schema(pool, &query, JsonLayout::default())
+            .await?;
+        let rows = sqlx::query(&query).fetch_all(pool).await?;
+        rows_to_record_batch(schema, &rows)
+    }
+}
+
+#[async_trait]
+impl TableWorkerDyn for GenericTableWorker {
+    async fn query_table(&self, pool: &PgPool, query: &str) -> Result<(), AppError> {
+        let batch = self.query_to_record_batch(pool, query).await?;
+        println!("{}", pretty_format_batches(&[batch])?);
+        Ok(())
+    }
+
+    async fn query_table_to_string(
+        &self,
+        pool: &PgPool,
+        query: &str,
+    ) -> Result<Vec<String>, AppError> {
+        let batch = self.query_to_record_batch(pool, query).await?;
+        let schema = batch.schema();
+        let options = FormatOptions::default();
+        let formatters = batch
+            .columns()
+            .iter()
+            .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+            .collect::<Result<Vec<_>, _>>()?;
+        let rows = (0..batch.num_rows())
+            .map(|row| {
+                schema
+                    .fields()
+                    .iter()
+                    .zip(&formatters)
+                    .map(|(field, formatter)| format!("{}: {}", field.name(), formatter.value(row)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .collect();
+        Ok(rows)
+    }
+
+    async fn query_table_to_json(&self, pool: &PgPool, query: &str) -> Result<String, AppError> {
+        let batch = self.query_to_record_batch(pool, query).await?;
+        record_batch_to_json(&batch)
+    }
+
+    async fn query_table_to_df(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        ctx: &SessionContext,
+    ) -> Result<DataFrame, AppError> {
+        let batch = self.query_to_record_batch(pool, query).await?;
+        let df = ctx.read_batch(batch)?;
+        Ok(df)
+    }
+
+    async fn query_table_to_stream(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        let query = self.prepare_query(query)?;
+        self.stream(pool, query, batch_size, JsonLayout::default())
+            .await
+    }
+
+    async fn export_table_to_stream(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        self.export_table_to_stream_with(pool, query, batch_size, JsonLayout::default())
+            .await
+    }
+
+    async fn export_table_to_stream_with(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+        layout: JsonLayout,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        let query = validate_query_with_policy(query, &self.policy())?;
+        self.stream(pool, query, batch_size, layout).await
+    }
+
+    async fn copy_table_to_stream(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        self.copy_table_to_stream_with(pool, query, batch_size, JsonLayout::default())
+            .await
+    }
+
+    async fn copy_table_to_stream_with(
+        &self,
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+        layout: JsonLayout,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        let query = validate_query_with_policy(query, &self.policy())?;
+        let schema = self.query_schema(pool, &query, layout).await?;
+        Ok(copy_to_stream(pool, query, schema, batch_size))
+    }
+}
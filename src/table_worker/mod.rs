@@ -1,5 +1,13 @@
+mod column;
 mod dynamic;
+mod generic;
+mod projection;
 mod stat;
+pub(crate) mod stream;
 
+pub use column::*;
 pub use dynamic::*;
+pub use generic::*;
+pub use projection::*;
 pub use stat::*;
+pub(crate) use stream::*;
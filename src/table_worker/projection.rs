@@ -0,0 +1,44 @@
+use datafusion::arrow::array::RecordBatch;
+use datafusion::arrow::datatypes::Schema;
+use sqlx::{Column, Executor, PgPool};
+
+use crate::{similar_columns, AppError, QueryParserError};
+
+/// Indices of the `schema` fields `query` returns, in the order postgres describes its result
+/// columns. A column that is not a field of `table` is an error.
+pub async fn query_projection(
+    pool: &PgPool,
+    query: &str,
+    table: &str,
+    schema: &Schema,
+) -> Result<Vec<usize>, AppError> {
+    let describe = pool.describe(query).await?;
+    let projection = describe
+        .columns()
+        .iter()
+        .map(|column| {
+            schema.index_of(column.name()).map_err(|_| {
+                let names = schema.fields().iter().map(|field| field.name().as_str());
+                QueryParserError::UnknownColumn(
+                    table.to_string(),
+                    column.name().to_string(),
+                    similar_columns(column.name(), names),
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(projection)
+}
+
+/// Whether `projection` selects every field of `schema` in order.
+pub fn is_full_projection(projection: &[usize], schema: &Schema) -> bool {
+    projection.iter().copied().eq(0..schema.fields().len())
+}
+
+/// Writes `batch` as a JSON array of objects.
+pub fn record_batch_to_json(batch: &RecordBatch) -> Result<String, AppError> {
+    let mut writer = arrow_json::ArrayWriter::new(Vec::new());
+    writer.write(batch)?;
+    writer.finish()?;
+    String::from_utf8(writer.into_inner()).map_err(|e| AppError::UnexpectedError(e.into()))
+}
@@ -1,8 +1,9 @@
 use async_trait::async_trait;
+use datafusion::physical_plan::SendableRecordBatchStream;
 use datafusion::prelude::*;
 use sqlx::PgPool;
 
-use crate::AppError;
+use crate::{AppError, JsonLayout};
 
 #[async_trait]
 pub trait TableWorkerStatic {
@@ -14,6 +15,41 @@ pub trait TableWorkerStatic {
         query: &str,
         ctx: &SessionContext,
     ) -> Result<DataFrame, AppError>;
+    async fn query_table_to_stream(
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError>;
+    /// Same as `query_table_to_stream` without the `MAX_ROWS` cap, for full table exports.
+    async fn export_table_to_stream(
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        Self::export_table_to_stream_with(pool, query, batch_size, JsonLayout::default()).await
+    }
+    /// Same as `export_table_to_stream`, with jsonb and point columns laid out as `layout`.
+    async fn export_table_to_stream_with(
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+        layout: JsonLayout,
+    ) -> Result<SendableRecordBatchStream, AppError>;
+    /// Same as `export_table_to_stream`, reading the rows through binary `COPY` instead.
+    async fn copy_table_to_stream(
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        Self::copy_table_to_stream_with(pool, query, batch_size, JsonLayout::default()).await
+    }
+    /// Same as `copy_table_to_stream`, with jsonb and point columns laid out as `layout`.
+    async fn copy_table_to_stream_with(
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+        layout: JsonLayout,
+    ) -> Result<SendableRecordBatchStream, AppError>;
 }
 
 pub mod helpers {
@@ -47,4 +83,30 @@ pub mod helpers {
     ) -> Result<DataFrame, AppError> {
         T::query_table_to_df(pool, query, ctx).await
     }
+
+    pub async fn process_table_to_stream<T: TableWorkerStatic>(
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        T::query_table_to_stream(pool, query, batch_size).await
+    }
+
+    pub async fn process_export_to_stream<T: TableWorkerStatic>(
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+        layout: JsonLayout,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        T::export_table_to_stream_with(pool, query, batch_size, layout).await
+    }
+
+    pub async fn process_copy_to_stream<T: TableWorkerStatic>(
+        pool: &PgPool,
+        query: &str,
+        batch_size: usize,
+        layout: JsonLayout,
+    ) -> Result<SendableRecordBatchStream, AppError> {
+        T::copy_table_to_stream_with(pool, query, batch_size, layout).await
+    }
 }
@@ -0,0 +1,124 @@
+use datafusion::arrow::array::RecordBatch;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::stream::RecordBatchReceiverStream;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures_util::TryStreamExt;
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::PgRow;
+use sqlx::{Column, Executor, PgPool, Postgres, TypeInfo};
+
+use crate::{AppError, BinaryCopyDecoder};
+
+/// Runs `query` on a background task and yields its rows as record batches of at most
+/// `batch_size` rows, so only one batch is held in memory at a time.
+pub fn rows_to_stream<F>(
+    pool: &PgPool,
+    query: String,
+    schema: SchemaRef,
+    batch_size: usize,
+    to_record_batch: F,
+) -> SendableRecordBatchStream
+where
+    F: Fn(&[PgRow]) -> Result<RecordBatch, AppError> + Send + 'static,
+{
+    let pool = pool.clone();
+    let mut builder = RecordBatchReceiverStream::builder(schema, 2);
+    let tx = builder.tx();
+    builder.spawn(async move {
+        let mut chunks = sqlx::query(&query)
+            .fetch(&pool)
+            .try_chunks(batch_size.max(1));
+        while let Some(rows) = chunks
+            .try_next()
+            .await
+            .map_err(|e| DataFusionError::from(AppError::from(e.1)))?
+        {
+            let batch = to_record_batch(&rows)?;
+            if tx.send(Ok(batch)).await.is_err() {
+                // receiver was dropped, stop fetching
+                break;
+            }
+        }
+        Ok(())
+    });
+    builder.build()
+}
+
+/// Runs `query` through `COPY ... TO STDOUT WITH (FORMAT binary)` on a background task and
+/// decodes the copy data straight into record batches of at most `batch_size` rows.
+pub fn copy_to_stream(
+    pool: &PgPool,
+    query: String,
+    schema: SchemaRef,
+    batch_size: usize,
+) -> SendableRecordBatchStream {
+    let pool = pool.clone();
+    let batch_size = batch_size.max(1);
+    let mut builder = RecordBatchReceiverStream::builder(schema.clone(), 2);
+    let tx = builder.tx();
+    builder.spawn(async move {
+        let mut conn = pool.acquire().await.map_err(AppError::from)?;
+        let describe = conn.describe(&query).await.map_err(AppError::from)?;
+        let columns = describe
+            .columns()
+            .iter()
+            .map(|column| {
+                (
+                    column.name().to_string(),
+                    column.type_info().name().to_lowercase(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut decoder = BinaryCopyDecoder::try_new(schema, &columns).map_err(AppError::from)?;
+
+        let statement = format!("COPY ({}) TO STDOUT WITH (FORMAT binary)", query);
+        let mut copy = CopyConnection {
+            conn,
+            finished: false,
+        };
+        let mut chunks = copy
+            .conn
+            .copy_out_raw(&statement)
+            .await
+            .map_err(AppError::from)?;
+        while let Some(chunk) = chunks.try_next().await.map_err(AppError::from)? {
+            let mut chunk = &chunk[..];
+            loop {
+                decoder.decode(chunk, batch_size).map_err(AppError::from)?;
+                chunk = &[];
+                if decoder.num_rows() < batch_size {
+                    break;
+                }
+                if tx.send(Ok(decoder.flush()?)).await.is_err() {
+                    // receiver was dropped, stop copying
+                    return Ok(());
+                }
+            }
+        }
+        drop(chunks);
+        copy.finished = true;
+        decoder.finish().map_err(AppError::from)?;
+        if decoder.num_rows() > 0 {
+            let _ = tx.send(Ok(decoder.flush()?)).await;
+        }
+        Ok(())
+    });
+    builder.build()
+}
+
+/// Pool connection running a COPY. Unless the copy ran to completion the connection is still in
+/// copy mode, so it is closed instead of handed back to the pool, whether the copy stopped on an
+/// error, a dropped receiver or an aborted task.
+struct CopyConnection {
+    conn: PoolConnection<Postgres>,
+    finished: bool,
+}
+
+impl Drop for CopyConnection {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.conn.close_on_drop();
+        }
+    }
+}
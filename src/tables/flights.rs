@@ -1,17 +1,12 @@
-use crate::table_worker::{TableWorkerDyn, TableWorkerStatic};
-use crate::{prepare_query, AppError, FLIGHTS_TABLE_NAME};
+use crate::{AppError, TableWorker, FLIGHTS_TABLE_NAME};
 
-use std::sync::Arc;
-
-use async_trait::async_trait;
-use datafusion::arrow::array::{Int32Array, RecordBatch, StringArray};
-use datafusion::arrow::datatypes::{DataType, Field, Schema};
-use datafusion::prelude::*;
 use serde::Serialize;
+use sqlx::postgres::PgRow;
 use sqlx::types::chrono::{DateTime, Utc};
-use sqlx::{postgres::PgRow, FromRow, PgPool, Row};
+use sqlx::{FromRow, Row};
 
-#[derive(Debug, Default, FromRow)]
+#[derive(Debug, Default, FromRow, TableWorker)]
+#[table_worker(table = FLIGHTS_TABLE_NAME, format = Self::format_flight)]
 pub struct Flights {
     pub flight_id: i32,
     pub flight_no: Option<String>,
@@ -43,207 +38,25 @@ impl Serialize for Flights {
     }
 }
 
-impl AsRef<str> for Flights {
-    fn as_ref(&self) -> &str {
-        FLIGHTS_TABLE_NAME
-    }
-}
-
 impl Flights {
     pub fn new() -> Self {
         Flights::default()
     }
-}
-
-impl Flights {
-    pub fn schema() -> Schema {
-        Schema::new(vec![
-            Field::new("flight_id", DataType::Int32, false),
-            Field::new("flight_no", DataType::Utf8, true),
-            Field::new("scheduled_departure", DataType::Utf8, true),
-            Field::new("scheduled_arrival", DataType::Utf8, true),
-            Field::new("departure_airport", DataType::Utf8, true),
-            Field::new("arrival_airport", DataType::Utf8, true),
-            Field::new("status", DataType::Utf8, true),
-            Field::new("aircraft_code", DataType::Utf8, true),
-            Field::new("actual_departure", DataType::Utf8, true),
-            Field::new("actual_arrival", DataType::Utf8, true),
-        ])
-    }
-
-    fn to_record_batch(records: &[Self]) -> Result<RecordBatch, AppError> {
-        let schema = Arc::new(Self::schema());
-        let flight_ids = records.iter().map(|r| r.flight_id).collect::<Vec<_>>();
-        let flight_nos = records
-            .iter()
-            .map(|r| r.flight_no.as_deref())
-            .collect::<Vec<_>>();
-        let scheduled_departures = records
-            .iter()
-            .map(|r| r.scheduled_departure.as_ref().map(|val| val.to_rfc3339()))
-            .collect::<Vec<_>>();
-        let scheduled_arrivals = records
-            .iter()
-            .map(|r| r.scheduled_arrival.as_ref().map(|val| val.to_rfc3339()))
-            .collect::<Vec<_>>();
-        let departure_airports = records
-            .iter()
-            .map(|r| r.departure_airport.as_deref())
-            .collect::<Vec<_>>();
-        let arrival_airports = records
-            .iter()
-            .map(|r| r.arrival_airport.as_deref())
-            .collect::<Vec<_>>();
-        let statuses = records
-            .iter()
-            .map(|r| r.status.as_deref())
-            .collect::<Vec<_>>();
-        let aircraft_codes = records
-            .iter()
-            .map(|r| r.aircraft_code.as_deref())
-            .collect::<Vec<_>>();
-        let actual_departures = records
-            .iter()
-            .map(|r| r.actual_departure.as_ref().map(|val| val.to_rfc3339()))
-            .collect::<Vec<_>>();
-        let actual_arrivals = records
-            .iter()
-            .map(|r| r.actual_arrival.as_ref().map(|val| val.to_rfc3339()))
-            .collect::<Vec<_>>();
-
-        Ok(RecordBatch::try_new(
-            schema,
-            vec![
-                Arc::new(Int32Array::from(flight_ids)),
-                Arc::new(StringArray::from(flight_nos)),
-                Arc::new(StringArray::from(scheduled_departures)),
-                Arc::new(StringArray::from(scheduled_arrivals)),
-                Arc::new(StringArray::from(departure_airports)),
-                Arc::new(StringArray::from(arrival_airports)),
-                Arc::new(StringArray::from(statuses)),
-                Arc::new(StringArray::from(aircraft_codes)),
-                Arc::new(StringArray::from(actual_departures)),
-                Arc::new(StringArray::from(actual_arrivals)),
-            ],
-        )?)
-    }
-
-    pub fn to_df(ctx: &SessionContext, records: &[Self]) -> Result<DataFrame, AppError> {
-        let batch = Self::to_record_batch(records)?;
-        let df = ctx.read_batch(batch)?;
-        Ok(df)
-    }
-}
-
-#[async_trait]
-impl TableWorkerDyn for Flights {
-    async fn query_table(&self, pool: &PgPool, query: &str) -> Result<(), AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let data = query.fetch_all(pool).await?;
-        println!("{:?}", data);
-        Ok(())
-    }
-
-    async fn query_table_to_string(
-        &self,
-        pool: &PgPool,
-        query: &str,
-    ) -> Result<Vec<String>, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query(&query);
-        let data: Vec<PgRow> = query.fetch_all(pool).await?;
-        let rows: Vec<String> = data
-            .iter()
-            .map(|row| format!("flight_id: {}, flight_no: {}, scheduled_departure: {}, scheduled_arrival: {}, departure_airport: {} \
-            arrival_airport: {}, status: {}, aircraft_code: {}, actual_departure: {:?}, actual_arrival: {:?}", 
-                row.get::<i32, _>("flight_id"), 
-                row.get::<String, _>("flight_no"), 
-                row.get::<DateTime<Utc>, _>("scheduled_departure"),
-                row.get::<DateTime<Utc>, _>("scheduled_arrival"),
-                row.get::<String, _>("departure_airport"),
-                row.get::<String, _>("arrival_airport"),
-                row.get::<String, _>("status"),
-                row.get::<String, _>("aircraft_code"),
-                row.get::<Option<DateTime<Utc>>, _>("actual_departure"),
-                row.get::<Option<DateTime<Utc>>, _>("actual_arrival"),
-            ))
-            .collect();
-        Ok(rows)
-    }
-
-    async fn query_table_to_json(&self, pool: &PgPool, query: &str) -> Result<String, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let data = query.fetch_all(pool).await?;
-        let res = serde_json::to_string(&data)?;
-        Ok(res)
-    }
-
-    async fn query_table_to_df(
-        &self,
-        pool: &PgPool,
-        query: &str,
-        ctx: &SessionContext,
-    ) -> Result<DataFrame, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let records = query.fetch_all(pool).await?;
-        let df = Self::to_df(ctx, &records)?;
-        Ok(df)
-    }
-}
-
-#[async_trait]
-impl TableWorkerStatic for Flights {
-    async fn query_table(pool: &PgPool, query: &str) -> Result<(), AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let data = query.fetch_all(pool).await?;
-        println!("{:?}", data);
-        Ok(())
-    }
-
-    async fn query_table_to_string(pool: &PgPool, query: &str) -> Result<Vec<String>, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query(&query);
-        let data: Vec<PgRow> = query.fetch_all(pool).await?;
-        let rows: Vec<String> = data
-            .iter()
-            .map(|row| format!("flight_id: {}, flight_no: {}, scheduled_departure: {}, scheduled_arrival: {}, departure_airport: {} \
-            arrival_airport: {}, status: {}, aircraft_code: {}, actual_departure: {:?}, actual_arrival: {:?}", 
-                row.get::<i32, _>("flight_id"), 
-                row.get::<String, _>("flight_no"), 
-                row.get::<DateTime<Utc>, _>("scheduled_departure"),
-                row.get::<DateTime<Utc>, _>("scheduled_arrival"),
-                row.get::<String, _>("departure_airport"),
-                row.get::<String, _>("arrival_airport"),
-                row.get::<String, _>("status"),
-                row.get::<String, _>("aircraft_code"),
-                row.get::<Option<DateTime<Utc>>, _>("actual_departure"),
-                row.get::<Option<DateTime<Utc>>, _>("actual_arrival"),
-            ))
-            .collect();
-        Ok(rows)
-    }
-
-    async fn query_table_to_json(pool: &PgPool, query: &str) -> Result<String, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let data = query.fetch_all(pool).await?;
-        let res = serde_json::to_string(&data)?;
-        Ok(res)
-    }
 
-    async fn query_table_to_df(
-        pool: &PgPool,
-        query: &str,
-        ctx: &SessionContext,
-    ) -> Result<DataFrame, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let records = query.fetch_all(pool).await?;
-        let df = Self::to_df(ctx, &records)?;
-        Ok(df)
+    // keeps the flights string layout, which shows the actual times as `Option`s
+    fn format_flight(row: &PgRow) -> Result<String, AppError> {
+        Ok(format!("flight_id: {}, flight_no: {}, scheduled_departure: {}, scheduled_arrival: {}, departure_airport: {} \
+            arrival_airport: {}, status: {}, aircraft_code: {}, actual_departure: {:?}, actual_arrival: {:?}",
+            row.try_get::<i32, _>("flight_id")?,
+            row.try_get::<String, _>("flight_no")?,
+            row.try_get::<DateTime<Utc>, _>("scheduled_departure")?,
+            row.try_get::<DateTime<Utc>, _>("scheduled_arrival")?,
+            row.try_get::<String, _>("departure_airport")?,
+            row.try_get::<String, _>("arrival_airport")?,
+            row.try_get::<String, _>("status")?,
+            row.try_get::<String, _>("aircraft_code")?,
+            row.try_get::<Option<DateTime<Utc>>, _>("actual_departure")?,
+            row.try_get::<Option<DateTime<Utc>>, _>("actual_arrival")?,
+        ))
     }
 }
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// How jsonb and point columns are laid out in Arrow schemas.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonLayout {
+    /// Nested `Struct` columns, e.g. `model.en`, `contact_data.phone`.
+    #[default]
+    Struct,
+    /// Legacy layout: the value serialized as a JSON string.
+    JsonString,
+}
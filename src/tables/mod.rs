@@ -3,6 +3,7 @@ mod airports_data;
 mod boarding_passes;
 mod bookings;
 mod flights;
+mod layout;
 mod seats;
 mod ticket_flights;
 mod tickets;
@@ -12,6 +13,62 @@ pub use airports_data::*;
 pub use boarding_passes::*;
 pub use bookings::*;
 pub use flights::*;
+pub use layout::*;
 pub use seats::*;
 pub use ticket_flights::*;
 pub use tickets::*;
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use datafusion::arrow::array::{Array, AsArray};
+    use datafusion::arrow::datatypes::{DataType, Decimal128Type};
+    use datafusion::prelude::*;
+    use sqlx::types::Decimal;
+
+    use crate::{read_file_to_df, write_df_to_file, AppError};
+
+    /// Amounts of a `numeric(10, 2)` column, including its bounds and a null.
+    pub(crate) fn decimal_amounts() -> Vec<Option<Decimal>> {
+        ["55800.00", "0.01", "99999999.99", "-1250.50"]
+            .iter()
+            .map(|val| Decimal::from_str(val).ok())
+            .chain([None])
+            .collect()
+    }
+
+    /// Writes `df` to a Parquet file and checks `column` reads back as `Decimal128(precision,
+    /// scale)` holding `expected`.
+    pub(crate) async fn assert_decimal_round_trip(
+        df: DataFrame,
+        column: &str,
+        precision: u8,
+        scale: i8,
+        expected: &[Option<Decimal>],
+    ) -> Result<(), AppError> {
+        let file_path =
+            std::env::temp_dir().join(format!("{}_{}.parquet", column, std::process::id()));
+        let file_path = file_path.to_str().unwrap();
+        write_df_to_file(df, file_path).await?;
+        let batches = read_file_to_df(file_path).await?.collect().await?;
+        std::fs::remove_file(file_path)?;
+
+        let column = batches[0]
+            .column_by_name(column)
+            .unwrap()
+            .as_primitive::<Decimal128Type>();
+        assert_eq!(column.data_type(), &DataType::Decimal128(precision, scale));
+        let res = column
+            .iter()
+            .map(|val| val.map(|val| Decimal::from_i128_with_scale(val, scale as u32)))
+            .map(|val| val.map(|val| val.to_string()))
+            .collect::<Vec<_>>();
+        let expected = expected
+            .iter()
+            .map(|val| val.map(|val| val.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(res, expected);
+        Ok(())
+    }
+}
@@ -1,174 +1,18 @@
-use crate::table_worker::{TableWorkerDyn, TableWorkerStatic};
-use crate::{prepare_query, AppError, SEATS_TABLE_NAME};
+use crate::{TableWorker, SEATS_TABLE_NAME};
 
-use std::sync::Arc;
-
-use async_trait::async_trait;
-use datafusion::arrow::array::{RecordBatch, StringArray};
-use datafusion::arrow::datatypes::{DataType, Field, Schema};
-use datafusion::prelude::*;
 use serde::Serialize;
-use sqlx::{postgres::PgRow, FromRow, PgPool, Row};
+use sqlx::FromRow;
 
-#[derive(Debug, Default, FromRow, Serialize)]
+#[derive(Debug, Default, FromRow, Serialize, TableWorker)]
+#[table_worker(table = SEATS_TABLE_NAME)]
 pub struct Seats {
     pub aircraft_code: String,
     pub seat_no: Option<String>,
     pub fare_conditions: Option<String>,
 }
 
-impl AsRef<str> for Seats {
-    fn as_ref(&self) -> &str {
-        SEATS_TABLE_NAME
-    }
-}
-
 impl Seats {
     pub fn new() -> Self {
         Seats::default()
     }
 }
-
-impl Seats {
-    pub fn schema() -> Schema {
-        Schema::new(vec![
-            Field::new("aircraft_code", DataType::Utf8, false),
-            Field::new("seat_no", DataType::Utf8, true),
-            Field::new("fare_conditions", DataType::Utf8, true),
-        ])
-    }
-
-    fn to_record_batch(records: &[Self]) -> Result<RecordBatch, AppError> {
-        let schema = Arc::new(Self::schema());
-        let aircraft_codes = records
-            .iter()
-            .map(|r| r.aircraft_code.as_str())
-            .collect::<Vec<_>>();
-        let seat_nos = records
-            .iter()
-            .map(|r| r.seat_no.as_deref())
-            .collect::<Vec<_>>();
-        let fare_conditions_all = records
-            .iter()
-            .map(|r| r.fare_conditions.as_deref())
-            .collect::<Vec<_>>();
-
-        Ok(RecordBatch::try_new(
-            schema,
-            vec![
-                Arc::new(StringArray::from(aircraft_codes)),
-                Arc::new(StringArray::from(seat_nos)),
-                Arc::new(StringArray::from(fare_conditions_all)),
-            ],
-        )?)
-    }
-
-    pub fn to_df(ctx: &SessionContext, records: &[Self]) -> Result<DataFrame, AppError> {
-        let batch = Self::to_record_batch(records)?;
-        let df = ctx.read_batch(batch)?;
-        Ok(df)
-    }
-}
-
-#[async_trait]
-impl TableWorkerDyn for Seats {
-    async fn query_table(&self, pool: &PgPool, query: &str) -> Result<(), AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let data = query.fetch_all(pool).await?;
-        println!("{:?}", data);
-        Ok(())
-    }
-
-    async fn query_table_to_string(
-        &self,
-        pool: &PgPool,
-        query: &str,
-    ) -> Result<Vec<String>, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query(&query);
-        let data: Vec<PgRow> = query.fetch_all(pool).await?;
-        let rows: Vec<String> = data
-            .iter()
-            .map(|row| {
-                format!(
-                    "aircraft_code: {}, seat_no: {}, fare_conditions: {}",
-                    row.get::<String, _>("aircraft_code"),
-                    row.get::<String, _>("seat_no"),
-                    row.get::<String, _>("fare_conditions"),
-                )
-            })
-            .collect();
-        Ok(rows)
-    }
-
-    async fn query_table_to_json(&self, pool: &PgPool, query: &str) -> Result<String, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let data = query.fetch_all(pool).await?;
-        let res = serde_json::to_string(&data)?;
-        Ok(res)
-    }
-
-    async fn query_table_to_df(
-        &self,
-        pool: &PgPool,
-        query: &str,
-        ctx: &SessionContext,
-    ) -> Result<DataFrame, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let records = query.fetch_all(pool).await?;
-        let df = Self::to_df(ctx, &records)?;
-        Ok(df)
-    }
-}
-
-#[async_trait]
-impl TableWorkerStatic for Seats {
-    async fn query_table(pool: &PgPool, query: &str) -> Result<(), AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let data = query.fetch_all(pool).await?;
-        println!("{:?}", data);
-        Ok(())
-    }
-
-    async fn query_table_to_string(pool: &PgPool, query: &str) -> Result<Vec<String>, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query(&query);
-        let data: Vec<PgRow> = query.fetch_all(pool).await?;
-        let rows: Vec<String> = data
-            .iter()
-            .map(|row| {
-                format!(
-                    "aircraft_code: {}, seat_no: {}, fare_conditions: {}",
-                    row.get::<String, _>("aircraft_code"),
-                    row.get::<String, _>("seat_no"),
-                    row.get::<String, _>("fare_conditions"),
-                )
-            })
-            .collect();
-        Ok(rows)
-    }
-
-    async fn query_table_to_json(pool: &PgPool, query: &str) -> Result<String, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let data = query.fetch_all(pool).await?;
-        let res = serde_json::to_string(&data)?;
-        Ok(res)
-    }
-
-    async fn query_table_to_df(
-        pool: &PgPool,
-        query: &str,
-        ctx: &SessionContext,
-    ) -> Result<DataFrame, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let records = query.fetch_all(pool).await?;
-        let df = Self::to_df(ctx, &records)?;
-        Ok(df)
-    }
-}
@@ -1,23 +1,17 @@
-use crate::table_worker::{TableWorkerDyn, TableWorkerStatic};
-use crate::{prepare_query, AppError, TICKETS_TABLE_NAME};
+use crate::{JsonFields, TableWorker, TICKETS_TABLE_NAME};
 
-use std::sync::Arc;
-
-use async_trait::async_trait;
-use datafusion::arrow::array::{RecordBatch, StringArray};
-use datafusion::arrow::datatypes::{DataType, Field, Schema};
-use datafusion::prelude::*;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use sqlx::types::Json;
-use sqlx::{postgres::PgRow, FromRow, PgPool, Row};
+use sqlx::FromRow;
 
-#[derive(Debug, Default, FromRow, Serialize)]
+#[derive(Debug, Default, FromRow, Serialize, TableWorker)]
+#[table_worker(table = TICKETS_TABLE_NAME)]
 pub struct Tickets {
     pub ticket_no: String,
     pub book_ref: Option<String>,
     pub passenger_id: Option<String>,
     pub passenger_name: Option<String>,
+    #[table_worker(flatten)]
     pub contact_data: Option<Json<ContactData>>,
 }
 
@@ -27,9 +21,11 @@ pub struct ContactData {
     pub phone: Option<String>,
 }
 
-impl AsRef<str> for Tickets {
-    fn as_ref(&self) -> &str {
-        TICKETS_TABLE_NAME
+impl JsonFields for ContactData {
+    const FIELDS: &'static [&'static str] = &["email", "phone"];
+
+    fn values(&self) -> Vec<Option<&str>> {
+        vec![self.email.as_deref(), self.phone.as_deref()]
     }
 }
 
@@ -39,161 +35,75 @@ impl Tickets {
     }
 }
 
-impl Tickets {
-    pub fn schema() -> Schema {
-        Schema::new(vec![
-            Field::new("ticket_no", DataType::Utf8, false),
-            Field::new("book_ref", DataType::Utf8, true),
-            Field::new("passenger_id", DataType::Utf8, true),
-            Field::new("passenger_name", DataType::Utf8, true),
-            Field::new("contact_data", DataType::Utf8, true),
-        ])
-    }
-
-    fn to_record_batch(records: &[Self]) -> Result<RecordBatch, AppError> {
-        let schema = Arc::new(Self::schema());
-        let ticket_nos = records
-            .iter()
-            .map(|r| r.ticket_no.as_str())
-            .collect::<Vec<_>>();
-        let book_refs = records
-            .iter()
-            .map(|r| r.book_ref.as_deref())
-            .collect::<Vec<_>>();
-        let passenger_ids = records
-            .iter()
-            .map(|r| r.passenger_id.as_deref())
-            .collect::<Vec<_>>();
-        let passenger_names = records
-            .iter()
-            .map(|r| r.passenger_name.as_deref())
-            .collect::<Vec<_>>();
-        let contact_data_all = records
-            .iter()
-            .map(|r| {
-                r.contact_data
-                    .as_ref()
-                    .map(|val| serde_json::to_string(val))
-                    .transpose()
-            })
-            .collect::<Result<Vec<_>, serde_json::Error>>()?;
-
-        Ok(RecordBatch::try_new(
-            schema,
-            vec![
-                Arc::new(StringArray::from(ticket_nos)),
-                Arc::new(StringArray::from(book_refs)),
-                Arc::new(StringArray::from(passenger_ids)),
-                Arc::new(StringArray::from(passenger_names)),
-                Arc::new(StringArray::from(contact_data_all)),
-            ],
-        )?)
-    }
-
-    pub fn to_df(ctx: &SessionContext, records: &[Self]) -> Result<DataFrame, AppError> {
-        let batch = Self::to_record_batch(records)?;
-        let df = ctx.read_batch(batch)?;
-        Ok(df)
-    }
-}
-
-#[async_trait]
-impl TableWorkerDyn for Tickets {
-    async fn query_table(&self, pool: &PgPool, query: &str) -> Result<(), AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let data = query.fetch_all(pool).await?;
-        println!("{:?}", data);
-        Ok(())
-    }
-
-    async fn query_table_to_string(
-        &self,
-        pool: &PgPool,
-        query: &str,
-    ) -> Result<Vec<String>, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query(&query);
-        let data: Vec<PgRow> = query.fetch_all(pool).await?;
-        let rows: Vec<String> = data
-            .iter()
-            .map(|row| format!("ticket_no: {}, book_ref: {}, passenger_id: {}, passenger_name: {}, contact_data: {}", 
-                row.get::<String, _>("ticket_no"), 
-                row.get::<String, _>("book_ref"), 
-                row.get::<String, _>("passenger_id"),
-                row.get::<String, _>("passenger_name"),
-                row.get::<Value, _>("contact_data")),
-            )
-            .collect();
-        Ok(rows)
-    }
-
-    async fn query_table_to_json(&self, pool: &PgPool, query: &str) -> Result<String, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let data = query.fetch_all(pool).await?;
-        let res = serde_json::to_string(&data)?;
-        Ok(res)
-    }
-
-    async fn query_table_to_df(
-        &self,
-        pool: &PgPool,
-        query: &str,
-        ctx: &SessionContext,
-    ) -> Result<DataFrame, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let records = query.fetch_all(pool).await?;
-        let df = Self::to_df(ctx, &records)?;
-        Ok(df)
-    }
-}
-
-#[async_trait]
-impl TableWorkerStatic for Tickets {
-    async fn query_table(pool: &PgPool, query: &str) -> Result<(), AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let data = query.fetch_all(pool).await?;
-        println!("{:?}", data);
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::{Array, AsArray};
+    use datafusion::arrow::datatypes::DataType;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{utf8_struct_type, JsonLayout};
+
+    fn records() -> Vec<Tickets> {
+        vec![
+            Tickets {
+                ticket_no: "0005432000284".to_string(),
+                contact_data: Some(Json(ContactData {
+                    email: None,
+                    phone: Some("+70110137563".to_string()),
+                })),
+                ..Default::default()
+            },
+            Tickets {
+                ticket_no: "0005432000285".to_string(),
+                ..Default::default()
+            },
+        ]
     }
 
-    async fn query_table_to_string(pool: &PgPool, query: &str) -> Result<Vec<String>, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query(&query);
-        let data: Vec<PgRow> = query.fetch_all(pool).await?;
-        let rows: Vec<String> = data
-            .iter()
-            .map(|row| format!("ticket_no: {}, book_ref: {}, passenger_id: {}, passenger_name: {}, contact_data: {}", 
-                row.get::<String, _>("ticket_no"), 
-                row.get::<String, _>("book_ref"), 
-                row.get::<String, _>("passenger_id"),
-                row.get::<String, _>("passenger_name"),
-                row.get::<Value, _>("contact_data")),
-            )
-            .collect();
-        Ok(rows)
+    #[rstest]
+    #[case(JsonLayout::Struct, utf8_struct_type(&["email", "phone"]))]
+    #[case(JsonLayout::JsonString, DataType::Utf8)]
+    fn test_contact_data_type(#[case] layout: JsonLayout, #[case] expected: DataType) {
+        let batch = Tickets::to_record_batch_with(&records(), layout).unwrap();
+        let contact_data = batch.column_by_name("contact_data").unwrap();
+        assert_eq!(contact_data.data_type(), &expected);
+        assert_eq!(
+            Tickets::schema_with(layout)
+                .field_with_name("contact_data")
+                .unwrap()
+                .data_type(),
+            &expected
+        );
+        assert!(contact_data.is_null(1));
     }
 
-    async fn query_table_to_json(pool: &PgPool, query: &str) -> Result<String, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let data = query.fetch_all(pool).await?;
-        let res = serde_json::to_string(&data)?;
-        Ok(res)
+    #[test]
+    fn test_contact_data_struct() {
+        let batch = Tickets::to_record_batch(&records()).unwrap();
+        let contact_data = batch.column_by_name("contact_data").unwrap().as_struct();
+        let phones = contact_data
+            .column_by_name("phone")
+            .unwrap()
+            .as_string::<i32>();
+        let emails = contact_data
+            .column_by_name("email")
+            .unwrap()
+            .as_string::<i32>();
+        assert_eq!(phones.value(0), "+70110137563");
+        assert!(emails.is_null(0));
     }
 
-    async fn query_table_to_df(
-        pool: &PgPool,
-        query: &str,
-        ctx: &SessionContext,
-    ) -> Result<DataFrame, AppError> {
-        let query = prepare_query(query)?;
-        let query = sqlx::query_as::<_, Self>(&query);
-        let records = query.fetch_all(pool).await?;
-        let df = Self::to_df(ctx, &records)?;
-        Ok(df)
+    #[test]
+    fn test_contact_data_json_string() {
+        let batch = Tickets::to_record_batch_with(&records(), JsonLayout::JsonString).unwrap();
+        let contact_data = batch
+            .column_by_name("contact_data")
+            .unwrap()
+            .as_string::<i32>();
+        assert_eq!(
+            contact_data.value(0),
+            r#"{"email":null,"phone":"+70110137563"}"#
+        );
     }
 }
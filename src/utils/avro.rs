@@ -0,0 +1,722 @@
+use apache_avro::types::Value as AvroValue;
+use apache_avro::{Codec, Days, Decimal, Duration, Millis, Months, Reader, Writer};
+use datafusion::arrow::array::{
+    Array, ArrayRef, AsArray, GenericListArray, OffsetSizeTrait, RecordBatch, StructArray,
+};
+use datafusion::arrow::datatypes::{
+    DataType, Date32Type, Decimal128Type, Field, Fields, Float32Type, Float64Type, Int16Type,
+    Int32Type, Int64Type, Int8Type, IntervalMonthDayNano, IntervalMonthDayNanoType, IntervalUnit,
+    Schema, TimeUnit, TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+    UInt16Type, UInt32Type, UInt8Type,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum AvroError {
+    #[error("Invalid avro schema: {0}")]
+    InvalidSchema(String),
+
+    #[error("Value does not match avro schema {0}")]
+    InvalidValue(String),
+
+    #[error("Column {0} has a type that can not be written to avro: {1}")]
+    UnsupportedType(String, String),
+
+    #[error("Avro error")]
+    Avro(#[source] Box<apache_avro::Error>),
+}
+
+// boxed as the avro error is large enough to bloat every result carrying it
+impl From<apache_avro::Error> for AvroError {
+    fn from(e: apache_avro::Error) -> Self {
+        Self::Avro(Box::new(e))
+    }
+}
+
+/// Compression of the data blocks in an Avro object container file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AvroCodec {
+    #[default]
+    Null,
+    Deflate,
+    Snappy,
+}
+
+impl From<AvroCodec> for Codec {
+    fn from(codec: AvroCodec) -> Self {
+        match codec {
+            AvroCodec::Null => Codec::Null,
+            AvroCodec::Deflate => Codec::Deflate,
+            AvroCodec::Snappy => Codec::Snappy,
+        }
+    }
+}
+
+/// Writes an Avro object container file piece by piece, the header first and then the data
+/// blocks of each call to [`ContainerWriter::block`].
+pub(crate) struct ContainerWriter {
+    schema: apache_avro::Schema,
+    codec: Codec,
+    marker: [u8; 16],
+}
+
+impl ContainerWriter {
+    pub(crate) fn new(schema: &Value, codec: AvroCodec) -> Result<Self, AvroError> {
+        Ok(Self {
+            schema: apache_avro::Schema::parse(schema)?,
+            codec: codec.into(),
+            marker: *Uuid::new_v4().as_bytes(),
+        })
+    }
+
+    pub(crate) fn header(&self) -> Result<Vec<u8>, AvroError> {
+        let writer = Writer::builder()
+            .schema(&self.schema)
+            .writer(vec![])
+            .codec(self.codec)
+            .marker(self.marker)
+            .build();
+        Ok(writer.into_inner()?)
+    }
+
+    /// Encodes `rows` as data blocks following the header, no rows give no block at all.
+    pub(crate) fn block(&self, rows: &[AvroValue]) -> Result<Vec<u8>, AvroError> {
+        let mut writer =
+            Writer::append_to_with_codec(&self.schema, vec![], self.codec, self.marker);
+        for row in rows {
+            writer.append_value_ref(row)?;
+        }
+        Ok(writer.into_inner()?)
+    }
+}
+
+/// Encodes `rows` as an uncompressed Avro object container file. Rows are resolved against
+/// `schema` first, so values of nullable fields need no union wrapping.
+pub(crate) fn write_container(
+    schema: &Value,
+    metadata: &[(&str, String)],
+    rows: &[AvroValue],
+) -> Result<Vec<u8>, AvroError> {
+    let schema = apache_avro::Schema::parse(schema)?;
+    let mut writer = Writer::new(&schema, vec![]);
+    for (key, value) in metadata {
+        writer.add_user_metadata(key.to_string(), value)?;
+    }
+    for row in rows {
+        writer.append(row.clone().resolve(&schema)?)?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+/// Decodes the rows of an Avro object container file.
+pub(crate) fn read_container(buf: &[u8]) -> Result<Vec<AvroValue>, AvroError> {
+    Ok(Reader::new(buf)?.collect::<Result<_, _>>()?)
+}
+
+/// Derives the Avro schema of a record named `name` from an Arrow schema, in the JSON form a
+/// schema registry accepts. Nullable columns become `["null", type]` unions defaulting to null,
+/// nested records and fixed types are named after their column path so names stay unique.
+pub fn schema_to_avro(
+    schema: &Schema,
+    name: &str,
+    namespace: Option<&str>,
+) -> Result<Value, AvroError> {
+    if let Some(namespace) = namespace {
+        namespace
+            .split('.')
+            .try_for_each(|part| avro_name(part).map(|_| ()))?;
+    }
+    record_to_avro(name, namespace, schema.fields())
+}
+
+fn avro_name(name: &str) -> Result<&str, AvroError> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    match valid {
+        true => Ok(name),
+        false => Err(AvroError::InvalidSchema(format!(
+            "invalid avro name: {}",
+            name
+        ))),
+    }
+}
+
+fn record_to_avro(
+    name: &str,
+    namespace: Option<&str>,
+    fields: &Fields,
+) -> Result<Value, AvroError> {
+    let fullname = match namespace {
+        Some(namespace) => format!("{}.{}", namespace, name),
+        None => name.to_string(),
+    };
+    let fields = fields
+        .iter()
+        .map(|field| {
+            let name = avro_name(field.name())?;
+            let mut value = json!({"name": name, "type": field_to_avro(name, &fullname, field)?});
+            if field.is_nullable() {
+                value["default"] = Value::Null;
+            }
+            Ok(value)
+        })
+        .collect::<Result<Vec<_>, AvroError>>()?;
+    let mut record = json!({"type": "record", "name": avro_name(name)?, "fields": fields});
+    if let Some(namespace) = namespace {
+        record["namespace"] = json!(namespace);
+    }
+    Ok(record)
+}
+
+fn field_to_avro(name: &str, namespace: &str, field: &Field) -> Result<Value, AvroError> {
+    let avro_type = type_to_avro(name, namespace, field.data_type())?;
+    match field.is_nullable() {
+        true => Ok(json!(["null", avro_type])),
+        false => Ok(avro_type),
+    }
+}
+
+fn type_to_avro(name: &str, namespace: &str, data_type: &DataType) -> Result<Value, AvroError> {
+    let logical = |avro_type: &str, logical_type: &str| json!({"type": avro_type, "logicalType": logical_type});
+    let avro_type = match data_type {
+        DataType::Null => json!("null"),
+        DataType::Boolean => json!("boolean"),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            json!("int")
+        }
+        DataType::Int64 | DataType::UInt32 => json!("long"),
+        DataType::Float32 => json!("float"),
+        DataType::Float64 => json!("double"),
+        DataType::Utf8 | DataType::LargeUtf8 => json!("string"),
+        DataType::Binary | DataType::LargeBinary => json!("bytes"),
+        DataType::Date32 => logical("int", "date"),
+        DataType::Timestamp(TimeUnit::Millisecond, None) => {
+            logical("long", "local-timestamp-millis")
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, None) => {
+            logical("long", "local-timestamp-micros")
+        }
+        DataType::Timestamp(TimeUnit::Nanosecond, None) => logical("long", "local-timestamp-nanos"),
+        DataType::Timestamp(TimeUnit::Millisecond, Some(_)) => logical("long", "timestamp-millis"),
+        DataType::Timestamp(TimeUnit::Microsecond, Some(_)) => logical("long", "timestamp-micros"),
+        DataType::Timestamp(TimeUnit::Nanosecond, Some(_)) => logical("long", "timestamp-nanos"),
+        DataType::Decimal128(precision, scale) if *scale >= 0 => json!({
+            "type": "bytes",
+            "logicalType": "decimal",
+            "precision": precision,
+            "scale": scale,
+        }),
+        DataType::Interval(IntervalUnit::MonthDayNano) => json!({
+            "type": "fixed",
+            "name": name,
+            "namespace": namespace,
+            "size": 12,
+            "logicalType": "duration",
+        }),
+        DataType::List(field) | DataType::LargeList(field) => {
+            json!({"type": "array", "items": field_to_avro(name, namespace, field)?})
+        }
+        DataType::Struct(fields) => record_to_avro(name, Some(namespace), fields)?,
+        data_type => {
+            return Err(AvroError::UnsupportedType(
+                name.to_string(),
+                data_type.to_string(),
+            ))
+        }
+    };
+    Ok(avro_type)
+}
+
+/// Converts the rows of `batch` into Avro records matching [`schema_to_avro`].
+pub(crate) fn batch_to_avro(batch: &RecordBatch) -> Result<Vec<AvroValue>, AvroError> {
+    struct_to_avro(&StructArray::from(batch.clone()))
+}
+
+fn array_to_avro(name: &str, array: &ArrayRef) -> Result<Vec<AvroValue>, AvroError> {
+    let values = match array.data_type() {
+        DataType::Null => vec![AvroValue::Null; array.len()],
+        DataType::Boolean => collect(array.as_boolean().iter(), AvroValue::Boolean),
+        DataType::Int8 => collect(array.as_primitive::<Int8Type>().iter(), |value| {
+            AvroValue::Int(value.into())
+        }),
+        DataType::Int16 => collect(array.as_primitive::<Int16Type>().iter(), |value| {
+            AvroValue::Int(value.into())
+        }),
+        DataType::Int32 => collect(array.as_primitive::<Int32Type>().iter(), AvroValue::Int),
+        DataType::Int64 => collect(array.as_primitive::<Int64Type>().iter(), AvroValue::Long),
+        DataType::UInt8 => collect(array.as_primitive::<UInt8Type>().iter(), |value| {
+            AvroValue::Int(value.into())
+        }),
+        DataType::UInt16 => collect(array.as_primitive::<UInt16Type>().iter(), |value| {
+            AvroValue::Int(value.into())
+        }),
+        DataType::UInt32 => collect(array.as_primitive::<UInt32Type>().iter(), |value| {
+            AvroValue::Long(value.into())
+        }),
+        DataType::Float32 => collect(array.as_primitive::<Float32Type>().iter(), AvroValue::Float),
+        DataType::Float64 => collect(
+            array.as_primitive::<Float64Type>().iter(),
+            AvroValue::Double,
+        ),
+        DataType::Utf8 => collect(array.as_string::<i32>().iter(), |value| {
+            AvroValue::String(value.to_string())
+        }),
+        DataType::LargeUtf8 => collect(array.as_string::<i64>().iter(), |value| {
+            AvroValue::String(value.to_string())
+        }),
+        DataType::Binary => collect(array.as_binary::<i32>().iter(), |value| {
+            AvroValue::Bytes(value.to_vec())
+        }),
+        DataType::LargeBinary => collect(array.as_binary::<i64>().iter(), |value| {
+            AvroValue::Bytes(value.to_vec())
+        }),
+        DataType::Date32 => collect(array.as_primitive::<Date32Type>().iter(), AvroValue::Date),
+        DataType::Timestamp(TimeUnit::Millisecond, tz) => collect(
+            array.as_primitive::<TimestampMillisecondType>().iter(),
+            match tz {
+                Some(_) => AvroValue::TimestampMillis,
+                None => AvroValue::LocalTimestampMillis,
+            },
+        ),
+        DataType::Timestamp(TimeUnit::Microsecond, tz) => collect(
+            array.as_primitive::<TimestampMicrosecondType>().iter(),
+            match tz {
+                Some(_) => AvroValue::TimestampMicros,
+                None => AvroValue::LocalTimestampMicros,
+            },
+        ),
+        DataType::Timestamp(TimeUnit::Nanosecond, tz) => collect(
+            array.as_primitive::<TimestampNanosecondType>().iter(),
+            match tz {
+                Some(_) => AvroValue::TimestampNanos,
+                None => AvroValue::LocalTimestampNanos,
+            },
+        ),
+        DataType::Decimal128(_, scale) if *scale >= 0 => {
+            collect(array.as_primitive::<Decimal128Type>().iter(), |value| {
+                AvroValue::Decimal(Decimal::from(decimal_bytes(value)))
+            })
+        }
+        DataType::Interval(IntervalUnit::MonthDayNano) => array
+            .as_primitive::<IntervalMonthDayNanoType>()
+            .iter()
+            .map(|value| value.map_or(Ok(AvroValue::Null), duration))
+            .collect::<Result<_, _>>()?,
+        DataType::List(field) => list_to_avro(name, array.as_list::<i32>(), field)?,
+        DataType::LargeList(field) => list_to_avro(name, array.as_list::<i64>(), field)?,
+        DataType::Struct(_) => struct_to_avro(array.as_struct())?,
+        data_type => {
+            return Err(AvroError::UnsupportedType(
+                name.to_string(),
+                data_type.to_string(),
+            ))
+        }
+    };
+    Ok(values)
+}
+
+fn collect<T>(
+    values: impl Iterator<Item = Option<T>>,
+    value: impl Fn(T) -> AvroValue,
+) -> Vec<AvroValue> {
+    values.map(|v| v.map_or(AvroValue::Null, &value)).collect()
+}
+
+// nullable fields and list items are `["null", type]` unions, whose branch is explicit
+fn nullable(value: AvroValue, field: &Field) -> AvroValue {
+    match (field.is_nullable(), value) {
+        (false, value) => value,
+        (true, AvroValue::Null) => AvroValue::Union(0, Box::new(AvroValue::Null)),
+        (true, value) => AvroValue::Union(1, Box::new(value)),
+    }
+}
+
+fn list_to_avro<O: OffsetSizeTrait>(
+    name: &str,
+    list: &GenericListArray<O>,
+    field: &Field,
+) -> Result<Vec<AvroValue>, AvroError> {
+    let items = array_to_avro(name, list.values())?
+        .into_iter()
+        .map(|item| nullable(item, field))
+        .collect::<Vec<_>>();
+    let values = list
+        .offsets()
+        .windows(2)
+        .enumerate()
+        .map(|(i, offsets)| match list.is_null(i) {
+            true => AvroValue::Null,
+            false => AvroValue::Array(items[offsets[0].as_usize()..offsets[1].as_usize()].to_vec()),
+        })
+        .collect();
+    Ok(values)
+}
+
+fn struct_to_avro(array: &StructArray) -> Result<Vec<AvroValue>, AvroError> {
+    let mut columns = array
+        .fields()
+        .iter()
+        .zip(array.columns())
+        .map(|(field, column)| Ok(array_to_avro(field.name(), column)?.into_iter()))
+        .collect::<Result<Vec<_>, AvroError>>()?;
+    let values = (0..array.len())
+        .map(|i| {
+            let values = array
+                .fields()
+                .iter()
+                .zip(columns.iter_mut())
+                .map(|(field, column)| {
+                    let value = column.next().expect("row");
+                    (field.name().clone(), nullable(value, field))
+                })
+                .collect();
+            match array.is_null(i) {
+                true => AvroValue::Null,
+                false => AvroValue::Record(values),
+            }
+        })
+        .collect();
+    Ok(values)
+}
+
+// minimal big endian two's complement, as the decimal logical type expects
+fn decimal_bytes(value: i128) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let sign = if value < 0 { 0xFF } else { 0x00 };
+    let skip = bytes
+        .windows(2)
+        .take_while(|pair| pair[0] == sign && pair[1] & 0x80 == sign & 0x80)
+        .count();
+    bytes[skip..].to_vec()
+}
+
+// months, days and milliseconds as unsigned ints, sub-millisecond precision is dropped
+fn duration(value: IntervalMonthDayNano) -> Result<AvroValue, AvroError> {
+    let invalid = || AvroError::InvalidValue(format!("duration {:?}", value));
+    let months = u32::try_from(value.months).map_err(|_| invalid())?;
+    let days = u32::try_from(value.days).map_err(|_| invalid())?;
+    let millis = u32::try_from(value.nanoseconds / 1_000_000).map_err(|_| invalid())?;
+    Ok(AvroValue::Duration(Duration::new(
+        Months::new(months),
+        Days::new(days),
+        Millis::new(millis),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{
+        Decimal128Array, Float64Array, Int32Array, IntervalMonthDayNanoArray, ListArray,
+        StringArray, TimestampMicrosecondArray,
+    };
+    use datafusion::arrow::buffer::OffsetBuffer;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+
+    fn field<'a>(row: &'a AvroValue, name: &str) -> &'a AvroValue {
+        let AvroValue::Record(fields) = row else {
+            panic!("not a record: {:?}", row)
+        };
+        fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value)
+            .expect("field")
+    }
+
+    #[test]
+    fn container_test() {
+        let schema = json!({
+            "type": "record",
+            "name": "seat",
+            "fields": [
+                {"name": "aircraft_code", "type": "string"},
+                {"name": "seat_no", "type": ["null", "string"], "default": null},
+                {"name": "row", "type": {"type": "int", "logicalType": "date"}},
+                {"name": "fare_conditions", "type": {"type": "enum", "name": "fare", "symbols": ["Economy", "Business"]}},
+                {"name": "tags", "type": {"type": "array", "items": "long"}},
+                {"name": "props", "type": {"type": "map", "values": "double"}},
+            ]
+        });
+        let rows = vec![
+            AvroValue::Record(vec![
+                (
+                    "aircraft_code".to_string(),
+                    AvroValue::String("319".to_string()),
+                ),
+                ("seat_no".to_string(), AvroValue::String("2A".to_string())),
+                ("row".to_string(), AvroValue::Int(2)),
+                (
+                    "fare_conditions".to_string(),
+                    AvroValue::Enum(1, "Business".to_string()),
+                ),
+                (
+                    "tags".to_string(),
+                    AvroValue::Array(vec![AvroValue::Long(-7)]),
+                ),
+                (
+                    "props".to_string(),
+                    AvroValue::Map([("weight".to_string(), AvroValue::Double(1.5))].into()),
+                ),
+            ]),
+            AvroValue::Record(vec![
+                (
+                    "aircraft_code".to_string(),
+                    AvroValue::String("773".to_string()),
+                ),
+                ("row".to_string(), AvroValue::Int(30)),
+                (
+                    "fare_conditions".to_string(),
+                    AvroValue::Enum(0, "Economy".to_string()),
+                ),
+                ("tags".to_string(), AvroValue::Array(vec![])),
+                ("props".to_string(), AvroValue::Map(Default::default())),
+            ]),
+        ];
+        let buf = write_container(&schema, &[("source", "demo".to_string())], &rows).unwrap();
+        let decoded = read_container(&buf).unwrap();
+        let reader = Reader::new(&buf[..]).unwrap();
+
+        assert_eq!(reader.user_metadata()["source"], b"demo");
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(
+            field(&decoded[0], "seat_no"),
+            &AvroValue::Union(1, Box::new(AvroValue::String("2A".to_string())))
+        );
+        assert_eq!(field(&decoded[0], "row"), &AvroValue::Date(2));
+        assert_eq!(
+            field(&decoded[0], "props"),
+            &AvroValue::Map([("weight".to_string(), AvroValue::Double(1.5))].into())
+        );
+        // missing nullable fields take their default
+        assert_eq!(
+            field(&decoded[1], "seat_no"),
+            &AvroValue::Union(0, Box::new(AvroValue::Null))
+        );
+        assert_eq!(
+            field(&decoded[1], "fare_conditions"),
+            &AvroValue::Enum(0, "Economy".to_string())
+        );
+        assert!(read_container(&buf[..buf.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn encode_invalid_value_test() {
+        let schema = json!({
+            "type": "record",
+            "name": "airport",
+            "fields": [{"name": "timezone", "type": ["null", "long"]}],
+        });
+        let rows = [AvroValue::Record(vec![(
+            "timezone".to_string(),
+            AvroValue::String("DME".to_string()),
+        )])];
+        let writer = ContainerWriter::new(&schema, AvroCodec::Null).unwrap();
+        assert!(writer.block(&rows).is_err());
+        assert!(write_container(&schema, &[], &rows).is_err());
+    }
+
+    #[rstest]
+    #[case(0, &[0x00])]
+    #[case(127, &[0x7F])]
+    #[case(128, &[0x00, 0x80])]
+    #[case(-1, &[0xFF])]
+    #[case(-129, &[0xFF, 0x7F])]
+    #[case(1_234_500, &[0x12, 0xD6, 0x44])]
+    fn decimal_bytes_test(#[case] value: i128, #[case] expected: &[u8]) {
+        assert_eq!(expected, decimal_bytes(value));
+    }
+
+    fn flights_schema() -> Schema {
+        let coordinates = Fields::from(vec![
+            Field::new("x", DataType::Float64, false),
+            Field::new("y", DataType::Float64, false),
+        ]);
+        Schema::new(vec![
+            Field::new("flight_id", DataType::Int32, false),
+            Field::new("flight_no", DataType::Utf8, true),
+            Field::new(
+                "scheduled_departure",
+                DataType::Timestamp(TimeUnit::Microsecond, Some("+00:00".into())),
+                false,
+            ),
+            Field::new("amount", DataType::Decimal128(10, 2), true),
+            Field::new(
+                "duration",
+                DataType::Interval(IntervalUnit::MonthDayNano),
+                true,
+            ),
+            Field::new("coordinates", DataType::Struct(coordinates), true),
+            Field::new_list("seats", Field::new("item", DataType::Utf8, true), true),
+        ])
+    }
+
+    #[test]
+    fn schema_to_avro_test() {
+        let schema = schema_to_avro(&flights_schema(), "flights", Some("demodb.bookings")).unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "record",
+                "name": "flights",
+                "namespace": "demodb.bookings",
+                "fields": [
+                    {"name": "flight_id", "type": "int"},
+                    {"name": "flight_no", "type": ["null", "string"], "default": null},
+                    {"name": "scheduled_departure", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+                    {"name": "amount", "type": ["null", {"type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2}], "default": null},
+                    {"name": "duration", "type": ["null", {"type": "fixed", "name": "duration", "namespace": "demodb.bookings.flights", "size": 12, "logicalType": "duration"}], "default": null},
+                    {"name": "coordinates", "type": ["null", {
+                        "type": "record",
+                        "name": "coordinates",
+                        "namespace": "demodb.bookings.flights",
+                        "fields": [{"name": "x", "type": "double"}, {"name": "y", "type": "double"}],
+                    }], "default": null},
+                    {"name": "seats", "type": ["null", {"type": "array", "items": ["null", "string"]}], "default": null},
+                ]
+            })
+        );
+        assert!(apache_avro::Schema::parse(&schema).is_ok());
+    }
+
+    #[rstest]
+    #[case(
+        Schema::new(vec![Field::new("fare-conditions", DataType::Utf8, false)]),
+        AvroError::InvalidSchema("invalid avro name: fare-conditions".to_string())
+    )]
+    #[case(
+        Schema::new(vec![Field::new("seat_no", DataType::Utf8View, false)]),
+        AvroError::UnsupportedType("seat_no".to_string(), "Utf8View".to_string())
+    )]
+    fn schema_to_avro_invalid_test(#[case] schema: Schema, #[case] expected: AvroError) {
+        assert_eq!(
+            expected.to_string(),
+            schema_to_avro(&schema, "seats", None)
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[rstest]
+    #[case(AvroCodec::Null)]
+    #[case(AvroCodec::Deflate)]
+    #[case(AvroCodec::Snappy)]
+    fn batch_container_test(#[case] codec: AvroCodec) {
+        let schema = Arc::new(flights_schema());
+        let DataType::Struct(coordinates) = schema.field(5).data_type().clone() else {
+            unreachable!()
+        };
+        let seats = ListArray::new(
+            Arc::new(Field::new("item", DataType::Utf8, true)),
+            OffsetBuffer::from_lengths([2, 0]),
+            Arc::new(StringArray::from(vec![Some("1A"), None])),
+            None,
+        );
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Int32Array::from(vec![1, 2])),
+            Arc::new(StringArray::from(vec![Some("PG0403"), None])),
+            Arc::new(
+                TimestampMicrosecondArray::from(vec![1_500_000_000_000_000, 0])
+                    .with_timezone("+00:00"),
+            ),
+            Arc::new(
+                Decimal128Array::from(vec![Some(-129), None])
+                    .with_precision_and_scale(10, 2)
+                    .unwrap(),
+            ),
+            Arc::new(IntervalMonthDayNanoArray::from(vec![
+                Some(IntervalMonthDayNano::new(1, 2, 3_000_000)),
+                None,
+            ])),
+            Arc::new(StructArray::new(
+                coordinates,
+                vec![
+                    Arc::new(Float64Array::from(vec![37.9, 0.0])),
+                    Arc::new(Float64Array::from(vec![55.4, 0.0])),
+                ],
+                Some(vec![true, false].into()),
+            )),
+            Arc::new(seats),
+        ];
+        let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+
+        let avro_schema = schema_to_avro(&schema, "flights", None).unwrap();
+        let writer = ContainerWriter::new(&avro_schema, codec).unwrap();
+        let mut buf = writer.header().unwrap();
+        buf.append(&mut writer.block(&batch_to_avro(&batch).unwrap()).unwrap());
+        buf.append(
+            &mut writer
+                .block(&batch_to_avro(&batch.slice(1, 1)).unwrap())
+                .unwrap(),
+        );
+        let rows = read_container(&buf).unwrap();
+
+        let codec: &str = Codec::from(codec).into();
+        assert!(buf
+            .windows(codec.len())
+            .any(|window| window == codec.as_bytes()));
+        assert_eq!(rows.len(), 3);
+        let some = |value| AvroValue::Union(1, Box::new(value));
+        let null = AvroValue::Union(0, Box::new(AvroValue::Null));
+        assert_eq!(
+            rows[0],
+            AvroValue::Record(vec![
+                ("flight_id".to_string(), AvroValue::Int(1)),
+                (
+                    "flight_no".to_string(),
+                    some(AvroValue::String("PG0403".to_string()))
+                ),
+                (
+                    "scheduled_departure".to_string(),
+                    AvroValue::TimestampMicros(1_500_000_000_000_000)
+                ),
+                (
+                    "amount".to_string(),
+                    some(AvroValue::Decimal(Decimal::from(vec![0xFF, 0x7F])))
+                ),
+                (
+                    "duration".to_string(),
+                    some(AvroValue::Duration(Duration::new(
+                        Months::new(1),
+                        Days::new(2),
+                        Millis::new(3)
+                    )))
+                ),
+                (
+                    "coordinates".to_string(),
+                    some(AvroValue::Record(vec![
+                        ("x".to_string(), AvroValue::Double(37.9)),
+                        ("y".to_string(), AvroValue::Double(55.4)),
+                    ]))
+                ),
+                (
+                    "seats".to_string(),
+                    some(AvroValue::Array(vec![
+                        some(AvroValue::String("1A".to_string())),
+                        null.clone()
+                    ]))
+                ),
+            ])
+        );
+        assert_eq!(rows[1], rows[2]);
+        assert_eq!(field(&rows[1], "coordinates"), &null);
+        assert_eq!(field(&rows[1], "seats"), &some(AvroValue::Array(vec![])));
+    }
+
+    #[test]
+    fn negative_duration_test() {
+        let value = IntervalMonthDayNano::new(0, -1, 0);
+        assert!(duration(value).is_err());
+    }
+}
@@ -5,6 +5,12 @@ use secrecy::Secret;
 
 pub const MAX_DB_CONS: u32 = 100;
 pub const MAX_ROWS: u32 = 10;
+pub const MAX_LIMIT: u32 = 10_000;
+pub const DEFAULT_BATCH_SIZE: usize = 8192;
+pub const DEFAULT_PARALLELISM: usize = 4;
+pub const TIMESTAMP_TZ: &str = "UTC";
+pub const DEMODB_CATALOG_NAME: &str = "demodb";
+pub const DEMODB_SCHEMA_NAME: &str = "bookings";
 
 pub mod tables_names {
     pub const AIRCRAFTS_DATA_TABLE_NAME: &str = "aircrafts_data";
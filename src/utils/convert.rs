@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{ArrayRef, Float64Array, StringArray, StructArray};
+use datafusion::arrow::buffer::NullBuffer;
+use datafusion::arrow::datatypes::{DataType, Field, Fields};
+use datafusion::arrow::error::ArrowError;
+use sqlx::types::Decimal;
+
+/// Returns the unscaled value of `value` at `scale`, as stored by Arrow `Decimal128` arrays.
+pub fn decimal_to_i128(value: &Decimal, scale: i8) -> i128 {
+    let mut value = *value;
+    value.rescale(scale.max(0) as u32);
+    value.mantissa()
+}
+
+/// Struct type with one nullable Utf8 child per name, used for flattened jsonb objects.
+pub fn utf8_struct_type(names: &[&str]) -> DataType {
+    let fields = names
+        .iter()
+        .map(|name| Field::new(*name, DataType::Utf8, true))
+        .collect::<Fields>();
+    DataType::Struct(fields)
+}
+
+/// Builds a [`utf8_struct_type`] array; a `None` row becomes a null struct.
+pub fn utf8_struct_array<'a, R>(
+    names: &[&str],
+    rows: &[Option<R>],
+) -> Result<StructArray, ArrowError>
+where
+    R: AsRef<[Option<&'a str>]>,
+{
+    let DataType::Struct(fields) = utf8_struct_type(names) else {
+        unreachable!()
+    };
+    let columns = (0..names.len())
+        .map(|i| {
+            let values = rows
+                .iter()
+                .map(|row| row.as_ref().and_then(|row| row.as_ref()[i]))
+                .collect::<Vec<_>>();
+            Arc::new(StringArray::from(values)) as ArrayRef
+        })
+        .collect::<Vec<_>>();
+    let nulls = NullBuffer::from(rows.iter().map(Option::is_some).collect::<Vec<_>>());
+    StructArray::try_new(fields, columns, Some(nulls))
+}
+
+/// Struct type for a postgres `point`.
+pub fn point_struct_type() -> DataType {
+    DataType::Struct(Fields::from(vec![
+        Field::new("x", DataType::Float64, false),
+        Field::new("y", DataType::Float64, false),
+    ]))
+}
+
+/// Builds a [`point_struct_type`] array from `(x, y)` pairs; a `None` row becomes a null struct.
+pub fn point_struct_array(points: &[Option<(f64, f64)>]) -> Result<StructArray, ArrowError> {
+    let DataType::Struct(fields) = point_struct_type() else {
+        unreachable!()
+    };
+    let xs = points
+        .iter()
+        .map(|point| point.map(|(x, _)| x).unwrap_or_default())
+        .collect::<Vec<_>>();
+    let ys = points
+        .iter()
+        .map(|point| point.map(|(_, y)| y).unwrap_or_default())
+        .collect::<Vec<_>>();
+    let nulls = NullBuffer::from(points.iter().map(Option::is_some).collect::<Vec<_>>());
+    StructArray::try_new(
+        fields,
+        vec![
+            Arc::new(Float64Array::from(xs)),
+            Arc::new(Float64Array::from(ys)),
+        ],
+        Some(nulls),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use datafusion::arrow::array::Array;
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("55800.00", 2, 5580000)]
+    #[case("0.01", 2, 1)]
+    #[case("-12.5", 2, -1250)]
+    #[case("99999999.99", 2, 9999999999)]
+    #[case("7", 0, 7)]
+    fn decimal_to_i128_test(#[case] input: &str, #[case] scale: i8, #[case] expected: i128) {
+        let value = Decimal::from_str(input).unwrap();
+        assert_eq!(expected, decimal_to_i128(&value, scale));
+    }
+
+    #[test]
+    fn utf8_struct_array_test() {
+        let rows = [
+            Some([Some("Anapa"), Some("Анапа")]),
+            None,
+            Some([None, Some("Сочи")]),
+        ];
+        let array = utf8_struct_array(&["en", "ru"], &rows).unwrap();
+        assert_eq!(array.data_type(), &utf8_struct_type(&["en", "ru"]));
+        assert_eq!(array.null_count(), 1);
+        assert!(array.is_null(1));
+        assert_eq!(array.column(0).null_count(), 2);
+        assert_eq!(array.column(1).null_count(), 1);
+    }
+
+    #[test]
+    fn point_struct_array_test() {
+        let array = point_struct_array(&[Some((37.34, 45.0)), None]).unwrap();
+        assert_eq!(array.data_type(), &point_struct_type());
+        assert!(array.is_valid(0));
+        assert!(array.is_null(1));
+    }
+}
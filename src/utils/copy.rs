@@ -0,0 +1,635 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{
+    ArrayRef, BooleanArray, Decimal128Array, Float32Array, Float64Array, Int16Array, Int32Array,
+    Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use datafusion::arrow::datatypes::{DataType, SchemaRef, TimeUnit};
+use serde_json::Value;
+use sqlx::types::Decimal;
+use thiserror::Error;
+
+use super::convert::{decimal_to_i128, point_struct_array, point_struct_type, utf8_struct_array};
+use super::schema::SchemaError;
+use crate::AppError;
+
+const SIGNATURE: &[u8] = b"PGCOPY\n\xff\r\n\0";
+
+/// Microseconds between the unix epoch and the postgres epoch 2000-01-01.
+const PG_EPOCH_MICROS: i64 = 946_684_800_000_000;
+
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum CopyError {
+    #[error("Invalid binary copy header")]
+    InvalidHeader,
+
+    #[error("Expected {0} fields per copy row, got {1}")]
+    FieldCount(usize, i16),
+
+    #[error("Invalid {1} value in column {0}")]
+    InvalidValue(String, String),
+
+    #[error("Copy data ended without trailer")]
+    UnexpectedEof,
+}
+
+/// Postgres types the binary decoder understands, by `udt_name`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PgType {
+    Bool,
+    Int2,
+    Int4,
+    Int8,
+    Float4,
+    Float8,
+    Text,
+    Timestamptz,
+    Numeric,
+    Json,
+    Jsonb,
+    Point,
+}
+
+impl PgType {
+    fn new(udt_name: &str) -> Option<Self> {
+        let pg_type = match udt_name {
+            "bool" => Self::Bool,
+            "int2" => Self::Int2,
+            "int4" => Self::Int4,
+            "int8" => Self::Int8,
+            "float4" => Self::Float4,
+            "float8" => Self::Float8,
+            // sqlx reports `bpchar` as `char`
+            "text" | "varchar" | "bpchar" | "char" | "name" => Self::Text,
+            "timestamptz" => Self::Timestamptz,
+            "numeric" => Self::Numeric,
+            "json" => Self::Json,
+            "jsonb" => Self::Jsonb,
+            "point" => Self::Point,
+            _ => return None,
+        };
+        Some(pg_type)
+    }
+}
+
+#[derive(Debug)]
+enum Values {
+    Boolean(Vec<Option<bool>>),
+    Int16(Vec<Option<i16>>),
+    Int32(Vec<Option<i32>>),
+    Int64(Vec<Option<i64>>),
+    Float32(Vec<Option<f32>>),
+    Float64(Vec<Option<f64>>),
+    Utf8(Vec<Option<String>>),
+    Timestamp(Vec<Option<i64>>),
+    Decimal(Vec<Option<i128>>),
+    Struct(Vec<Option<Vec<Option<String>>>>),
+    Point(Vec<Option<(f64, f64)>>),
+}
+
+/// Decodes one result column into the values of its Arrow field.
+#[derive(Debug)]
+struct ColumnDecoder {
+    name: String,
+    pg_type: PgType,
+    data_type: DataType,
+    values: Values,
+}
+
+impl ColumnDecoder {
+    fn try_new(name: &str, pg_type: &str, data_type: &DataType) -> Result<Self, SchemaError> {
+        let unsupported = || SchemaError::UnsupportedType(name.to_string(), pg_type.to_string());
+        let pg_type = PgType::new(pg_type).ok_or_else(unsupported)?;
+        let values = match (pg_type, data_type) {
+            (PgType::Bool, DataType::Boolean) => Values::Boolean(vec![]),
+            (PgType::Int2, DataType::Int16) => Values::Int16(vec![]),
+            (PgType::Int4, DataType::Int32) => Values::Int32(vec![]),
+            (PgType::Int8, DataType::Int64) => Values::Int64(vec![]),
+            (PgType::Float4, DataType::Float32) => Values::Float32(vec![]),
+            (PgType::Float8, DataType::Float64) => Values::Float64(vec![]),
+            (PgType::Timestamptz, DataType::Timestamp(TimeUnit::Microsecond, Some(_))) => {
+                Values::Timestamp(vec![])
+            }
+            (PgType::Numeric, DataType::Decimal128(_, _)) => Values::Decimal(vec![]),
+            (PgType::Json | PgType::Jsonb, DataType::Struct(fields))
+                if fields
+                    .iter()
+                    .all(|field| field.data_type() == &DataType::Utf8) =>
+            {
+                Values::Struct(vec![])
+            }
+            (PgType::Point, DataType::Struct(_)) if data_type == &point_struct_type() => {
+                Values::Point(vec![])
+            }
+            (
+                PgType::Text | PgType::Numeric | PgType::Json | PgType::Jsonb | PgType::Point,
+                DataType::Utf8,
+            ) => Values::Utf8(vec![]),
+            _ => return Err(unsupported()),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            pg_type,
+            data_type: data_type.clone(),
+            values,
+        })
+    }
+
+    fn push(&mut self, value: Option<&[u8]>) -> Result<(), CopyError> {
+        self.push_value(value).ok_or_else(|| {
+            CopyError::InvalidValue(
+                self.name.clone(),
+                format!("{:?}", self.pg_type).to_lowercase(),
+            )
+        })
+    }
+
+    // returns `None` for a value that does not decode as the column type
+    fn push_value(&mut self, value: Option<&[u8]>) -> Option<()> {
+        let Some(value) = value else {
+            match &mut self.values {
+                Values::Boolean(values) => values.push(None),
+                Values::Int16(values) => values.push(None),
+                Values::Int32(values) => values.push(None),
+                Values::Int64(values) => values.push(None),
+                Values::Float32(values) => values.push(None),
+                Values::Float64(values) => values.push(None),
+                Values::Utf8(values) => values.push(None),
+                Values::Timestamp(values) => values.push(None),
+                Values::Decimal(values) => values.push(None),
+                Values::Struct(values) => values.push(None),
+                Values::Point(values) => values.push(None),
+            }
+            return Some(());
+        };
+        match &mut self.values {
+            Values::Boolean(values) => values.push(Some(be_bytes::<1>(value)?[0] != 0)),
+            Values::Int16(values) => values.push(Some(i16::from_be_bytes(be_bytes(value)?))),
+            Values::Int32(values) => values.push(Some(i32::from_be_bytes(be_bytes(value)?))),
+            Values::Int64(values) => values.push(Some(i64::from_be_bytes(be_bytes(value)?))),
+            Values::Float32(values) => values.push(Some(f32::from_be_bytes(be_bytes(value)?))),
+            Values::Float64(values) => values.push(Some(f64::from_be_bytes(be_bytes(value)?))),
+            Values::Timestamp(values) => {
+                let micros = i64::from_be_bytes(be_bytes(value)?);
+                values.push(Some(micros.checked_add(PG_EPOCH_MICROS)?));
+            }
+            Values::Decimal(values) => {
+                let DataType::Decimal128(_, scale) = self.data_type else {
+                    unreachable!()
+                };
+                values.push(Some(decimal_to_i128(&decode_numeric(value)?, scale)));
+            }
+            Values::Struct(values) => {
+                let DataType::Struct(fields) = &self.data_type else {
+                    unreachable!()
+                };
+                let json = decode_json(self.pg_type, value)?;
+                let children = fields
+                    .iter()
+                    .map(|field| match json.get(field.name()) {
+                        None | Some(Value::Null) => None,
+                        Some(Value::String(val)) => Some(val.clone()),
+                        Some(val) => Some(val.to_string()),
+                    })
+                    .collect();
+                values.push(Some(children));
+            }
+            Values::Point(values) => values.push(Some(decode_point(value)?)),
+            Values::Utf8(values) => {
+                let text = match self.pg_type {
+                    PgType::Numeric => decode_numeric(value)?.to_string(),
+                    PgType::Json | PgType::Jsonb => decode_json(self.pg_type, value)?.to_string(),
+                    PgType::Point => {
+                        let (x, y) = decode_point(value)?;
+                        serde_json::json!({ "x": x, "y": y }).to_string()
+                    }
+                    _ => String::from_utf8(value.to_vec()).ok()?,
+                };
+                values.push(Some(text));
+            }
+        }
+        Some(())
+    }
+
+    /// Builds the array of the values pushed so far and starts a new one.
+    fn finish(&mut self) -> Result<ArrayRef, AppError> {
+        let array: ArrayRef = match &mut self.values {
+            Values::Boolean(values) => Arc::new(BooleanArray::from(std::mem::take(values))),
+            Values::Int16(values) => Arc::new(Int16Array::from(std::mem::take(values))),
+            Values::Int32(values) => Arc::new(Int32Array::from(std::mem::take(values))),
+            Values::Int64(values) => Arc::new(Int64Array::from(std::mem::take(values))),
+            Values::Float32(values) => Arc::new(Float32Array::from(std::mem::take(values))),
+            Values::Float64(values) => Arc::new(Float64Array::from(std::mem::take(values))),
+            Values::Utf8(values) => Arc::new(StringArray::from(std::mem::take(values))),
+            Values::Timestamp(values) => {
+                let DataType::Timestamp(_, Some(tz)) = &self.data_type else {
+                    unreachable!()
+                };
+                Arc::new(
+                    TimestampMicrosecondArray::from(std::mem::take(values))
+                        .with_timezone(tz.clone()),
+                )
+            }
+            Values::Decimal(values) => {
+                let DataType::Decimal128(precision, scale) = self.data_type else {
+                    unreachable!()
+                };
+                Arc::new(
+                    Decimal128Array::from(std::mem::take(values))
+                        .with_precision_and_scale(precision, scale)?,
+                )
+            }
+            Values::Struct(values) => {
+                let DataType::Struct(fields) = &self.data_type else {
+                    unreachable!()
+                };
+                let names = fields
+                    .iter()
+                    .map(|field| field.name().as_str())
+                    .collect::<Vec<_>>();
+                let rows = values
+                    .iter()
+                    .map(|row| {
+                        row.as_ref()
+                            .map(|row| row.iter().map(Option::as_deref).collect::<Vec<_>>())
+                    })
+                    .collect::<Vec<_>>();
+                let array = utf8_struct_array(&names, &rows)?;
+                values.clear();
+                Arc::new(array)
+            }
+            Values::Point(values) => Arc::new(point_struct_array(&std::mem::take(values))?),
+        };
+        Ok(array)
+    }
+}
+
+fn be_bytes<const N: usize>(value: &[u8]) -> Option<[u8; N]> {
+    value.try_into().ok()
+}
+
+/// Decodes the binary `numeric` representation: digit count, weight of the first base 10000
+/// digit, sign, display scale and the digits themselves.
+fn decode_numeric(value: &[u8]) -> Option<Decimal> {
+    let header = value.get(..8)?;
+    let ndigits = u16::from_be_bytes([header[0], header[1]]) as usize;
+    let weight = i16::from_be_bytes([header[2], header[3]]) as i32;
+    let sign = u16::from_be_bytes([header[4], header[5]]);
+    let dscale = u16::from_be_bytes([header[6], header[7]]) as u32;
+    let digits = value.get(8..8 + ndigits * 2)?;
+    if sign != 0x0000 && sign != 0x4000 {
+        // NaN and infinities have no decimal representation
+        return None;
+    }
+    let mut mantissa: i128 = 0;
+    for (i, digit) in digits.chunks_exact(2).enumerate() {
+        let digit = i16::from_be_bytes([digit[0], digit[1]]) as i128;
+        let exponent = 4 * (weight - i as i32) + dscale as i32;
+        let term = if exponent >= 0 {
+            digit.checked_mul(10i128.checked_pow(exponent as u32)?)?
+        } else {
+            digit / 10i128.checked_pow(exponent.unsigned_abs())?
+        };
+        mantissa = mantissa.checked_add(term)?;
+    }
+    if sign == 0x4000 {
+        mantissa = -mantissa;
+    }
+    Decimal::try_from_i128_with_scale(mantissa, dscale).ok()
+}
+
+fn decode_json(pg_type: PgType, value: &[u8]) -> Option<Value> {
+    let text = match (pg_type, value.split_first()) {
+        // jsonb is prefixed with its format version
+        (PgType::Jsonb, Some((1, text))) => text,
+        (PgType::Jsonb, _) => return None,
+        _ => value,
+    };
+    serde_json::from_slice(text).ok()
+}
+
+fn decode_point(value: &[u8]) -> Option<(f64, f64)> {
+    let value: [u8; 16] = value.try_into().ok()?;
+    let x = f64::from_be_bytes(value[..8].try_into().ok()?);
+    let y = f64::from_be_bytes(value[8..].try_into().ok()?);
+    Some((x, y))
+}
+
+/// Incremental decoder of `COPY ... TO STDOUT WITH (FORMAT binary)` output into record batches.
+///
+/// Result columns are matched to `schema` fields by name, columns missing from the schema are
+/// skipped.
+#[derive(Debug)]
+pub struct BinaryCopyDecoder {
+    schema: SchemaRef,
+    // per result column, the schema field index and its decoder
+    columns: Vec<Option<(usize, ColumnDecoder)>>,
+    buf: Vec<u8>,
+    header_read: bool,
+    finished: bool,
+    num_rows: usize,
+}
+
+impl BinaryCopyDecoder {
+    /// `columns` are the `(name, udt_name)` pairs of the copied query result, in order.
+    pub fn try_new(schema: SchemaRef, columns: &[(String, String)]) -> Result<Self, SchemaError> {
+        for field in schema.fields() {
+            if !columns.iter().any(|(name, _)| name == field.name()) {
+                return Err(SchemaError::ColumnNotFound(field.name().to_string()));
+            }
+        }
+        let columns = columns
+            .iter()
+            .map(|(name, pg_type)| {
+                schema
+                    .index_of(name)
+                    .ok()
+                    .map(|index| {
+                        let decoder =
+                            ColumnDecoder::try_new(name, pg_type, schema.field(index).data_type())?;
+                        Ok((index, decoder))
+                    })
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>, SchemaError>>()?;
+        Ok(Self {
+            schema,
+            columns,
+            buf: vec![],
+            header_read: false,
+            finished: false,
+            num_rows: 0,
+        })
+    }
+
+    /// Number of decoded rows not yet taken by [`BinaryCopyDecoder::flush`].
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    /// Decodes the complete rows of `chunk` and any data left over from previous chunks,
+    /// stopping once `max_rows` rows are buffered. Call again with an empty chunk to continue.
+    pub fn decode(&mut self, chunk: &[u8], max_rows: usize) -> Result<(), CopyError> {
+        self.buf.extend_from_slice(chunk);
+        let mut pos = 0;
+        if !self.header_read {
+            let Some(len) = header_len(&self.buf)? else {
+                return Ok(());
+            };
+            pos = len;
+            self.header_read = true;
+        }
+        while !self.finished && self.num_rows < max_rows {
+            match self.decode_row(pos)? {
+                Some(next) => pos = next,
+                None => break,
+            }
+        }
+        self.buf.drain(..pos);
+        Ok(())
+    }
+
+    // decodes the row at `pos` if it is complete, returning the position after it
+    fn decode_row(&mut self, pos: usize) -> Result<Option<usize>, CopyError> {
+        let mut cursor = pos;
+        let Some(count) = read_i16(&self.buf, &mut cursor) else {
+            return Ok(None);
+        };
+        if count == -1 {
+            self.finished = true;
+            return Ok(Some(cursor));
+        }
+        if count as usize != self.columns.len() {
+            return Err(CopyError::FieldCount(self.columns.len(), count));
+        }
+        let mut values = Vec::with_capacity(self.columns.len());
+        for _ in 0..count {
+            let Some(len) = read_i32(&self.buf, &mut cursor) else {
+                return Ok(None);
+            };
+            if len < 0 {
+                values.push(None);
+                continue;
+            }
+            let end = cursor + len as usize;
+            if end > self.buf.len() {
+                return Ok(None);
+            }
+            values.push(Some(cursor..end));
+            cursor = end;
+        }
+        for (column, value) in self.columns.iter_mut().zip(values) {
+            if let Some((_, decoder)) = column {
+                decoder.push(value.map(|range| &self.buf[range]))?;
+            }
+        }
+        self.num_rows += 1;
+        Ok(Some(cursor))
+    }
+
+    /// Takes the buffered rows as a record batch.
+    pub fn flush(&mut self) -> Result<RecordBatch, AppError> {
+        let mut arrays = vec![None; self.schema.fields().len()];
+        for (index, decoder) in self.columns.iter_mut().flatten() {
+            arrays[*index] = Some(decoder.finish()?);
+        }
+        self.num_rows = 0;
+        let arrays = arrays.into_iter().flatten().collect();
+        Ok(RecordBatch::try_new(self.schema.clone(), arrays)?)
+    }
+
+    /// Checks the copy data was complete once the input is exhausted.
+    pub fn finish(&self) -> Result<(), CopyError> {
+        if self.finished {
+            Ok(())
+        } else {
+            Err(CopyError::UnexpectedEof)
+        }
+    }
+}
+
+// returns the header length once the whole header is buffered
+fn header_len(buf: &[u8]) -> Result<Option<usize>, CopyError> {
+    let mut cursor = SIGNATURE.len();
+    if buf.len() < cursor {
+        return Ok(None);
+    }
+    if &buf[..cursor] != SIGNATURE {
+        return Err(CopyError::InvalidHeader);
+    }
+    // flags, then the header extension length
+    cursor += 4;
+    let Some(ext_len) = read_i32(buf, &mut cursor) else {
+        return Ok(None);
+    };
+    let len = cursor + ext_len.max(0) as usize;
+    Ok((buf.len() >= len).then_some(len))
+}
+
+fn read_i16(buf: &[u8], cursor: &mut usize) -> Option<i16> {
+    let bytes = buf.get(*cursor..*cursor + 2)?;
+    *cursor += 2;
+    Some(i16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_i32(buf: &[u8], cursor: &mut usize) -> Option<i32> {
+    let bytes = buf.get(*cursor..*cursor + 4)?;
+    *cursor += 4;
+    Some(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use datafusion::arrow::array::{Array, AsArray};
+    use datafusion::arrow::datatypes::{Decimal128Type, Field, Int32Type, Schema};
+    use rstest::rstest;
+
+    use super::*;
+
+    fn numeric(weight: i16, sign: u16, dscale: u16, digits: &[i16]) -> Vec<u8> {
+        let mut buf = vec![];
+        buf.extend_from_slice(&(digits.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&weight.to_be_bytes());
+        buf.extend_from_slice(&sign.to_be_bytes());
+        buf.extend_from_slice(&dscale.to_be_bytes());
+        for digit in digits {
+            buf.extend_from_slice(&digit.to_be_bytes());
+        }
+        buf
+    }
+
+    fn copy_data(rows: &[Vec<Option<Vec<u8>>>]) -> Vec<u8> {
+        let mut buf = SIGNATURE.to_vec();
+        buf.extend_from_slice(&0i32.to_be_bytes());
+        buf.extend_from_slice(&0i32.to_be_bytes());
+        for row in rows {
+            buf.extend_from_slice(&(row.len() as i16).to_be_bytes());
+            for value in row {
+                match value {
+                    Some(value) => {
+                        buf.extend_from_slice(&(value.len() as i32).to_be_bytes());
+                        buf.extend_from_slice(value);
+                    }
+                    None => buf.extend_from_slice(&(-1i32).to_be_bytes()),
+                }
+            }
+        }
+        buf.extend_from_slice(&(-1i16).to_be_bytes());
+        buf
+    }
+
+    #[rstest]
+    #[case(numeric(1, 0x0000, 2, &[5, 5800]), Some("55800.00"))]
+    #[case(numeric(-1, 0x0000, 2, &[100]), Some("0.01"))]
+    #[case(numeric(0, 0x4000, 2, &[12, 5000]), Some("-12.50"))]
+    #[case(numeric(0, 0x0000, 0, &[]), Some("0"))]
+    #[case(numeric(0, 0xC000, 0, &[]), None)]
+    fn decode_numeric_test(#[case] input: Vec<u8>, #[case] expected: Option<&str>) {
+        let expected = expected.map(|val| Decimal::from_str(val).unwrap());
+        assert_eq!(expected, decode_numeric(&input));
+    }
+
+    #[test]
+    fn binary_copy_decoder_test() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("range", DataType::Int32, true),
+            Field::new("total_amount", DataType::Decimal128(10, 2), true),
+        ]));
+        let columns = [
+            ("aircraft_code".to_string(), "bpchar".to_string()),
+            ("range".to_string(), "int4".to_string()),
+            ("total_amount".to_string(), "numeric".to_string()),
+        ];
+        let data = copy_data(&[
+            vec![
+                Some(b"773".to_vec()),
+                Some(11100i32.to_be_bytes().to_vec()),
+                Some(numeric(0, 0x0000, 2, &[12, 5000])),
+            ],
+            vec![Some(b"SU9".to_vec()), None, None],
+            vec![
+                Some(b"CN1".to_vec()),
+                Some(1200i32.to_be_bytes().to_vec()),
+                Some(numeric(0, 0x0000, 0, &[7])),
+            ],
+        ]);
+
+        let mut decoder = BinaryCopyDecoder::try_new(schema, &columns).unwrap();
+        let mut batches = vec![];
+        // feed the data in small chunks that split the header and the rows
+        for chunk in data.chunks(5) {
+            decoder.decode(chunk, 2).unwrap();
+            if decoder.num_rows() == 2 {
+                batches.push(decoder.flush().unwrap());
+            }
+        }
+        decoder.finish().unwrap();
+        batches.push(decoder.flush().unwrap());
+
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+        let range = batches[0].column(0).as_primitive::<Int32Type>();
+        assert_eq!(range.value(0), 11100);
+        assert!(range.is_null(1));
+        assert_eq!(
+            batches[0].column(1).data_type(),
+            &DataType::Decimal128(10, 2)
+        );
+        assert_eq!(
+            batches[1]
+                .column(1)
+                .as_primitive::<Decimal128Type>()
+                .value(0),
+            700
+        );
+    }
+
+    #[test]
+    fn binary_copy_decoder_errors_test() {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "range",
+            DataType::Int32,
+            true,
+        )]));
+        let columns = [("range".to_string(), "int4".to_string())];
+
+        let mut decoder = BinaryCopyDecoder::try_new(schema.clone(), &columns).unwrap();
+        assert_eq!(
+            decoder.decode(b"COPY binary data", 10),
+            Err(CopyError::InvalidHeader)
+        );
+
+        let mut decoder = BinaryCopyDecoder::try_new(schema.clone(), &columns).unwrap();
+        let data = copy_data(&[vec![Some(vec![1, 2])]]);
+        assert_eq!(
+            decoder.decode(&data, 10),
+            Err(CopyError::InvalidValue(
+                "range".to_string(),
+                "int4".to_string()
+            ))
+        );
+
+        let mut decoder = BinaryCopyDecoder::try_new(schema.clone(), &columns).unwrap();
+        let data = copy_data(&[vec![None, None]]);
+        assert_eq!(decoder.decode(&data, 10), Err(CopyError::FieldCount(1, 2)));
+
+        let mut decoder = BinaryCopyDecoder::try_new(schema.clone(), &columns).unwrap();
+        let data = copy_data(&[]);
+        decoder.decode(&data[..data.len() - 2], 10).unwrap();
+        assert_eq!(decoder.finish(), Err(CopyError::UnexpectedEof));
+
+        assert_eq!(
+            BinaryCopyDecoder::try_new(schema.clone(), &[]).unwrap_err(),
+            SchemaError::ColumnNotFound("range".to_string())
+        );
+        assert_eq!(
+            BinaryCopyDecoder::try_new(schema, &[("range".to_string(), "tsvector".to_string())])
+                .unwrap_err(),
+            SchemaError::UnsupportedType("range".to_string(), "tsvector".to_string())
+        );
+    }
+}
@@ -0,0 +1,633 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use datafusion::arrow::array::RecordBatch;
+use datafusion::arrow::datatypes::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use datafusion::datasource::file_format::parquet::ParquetFormat;
+use datafusion::datasource::listing::{
+    ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+};
+use datafusion::physical_plan::SendableRecordBatchStream;
+use datafusion::prelude::*;
+use object_store::{PutMode, PutOptions, PutPayload};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::types::chrono::Utc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::constants::TIMESTAMP_TZ;
+use super::store::LakeStore;
+use super::utils::write_stream_to_store;
+use super::write_options::ParquetWriteOptions;
+use crate::AppError;
+
+const DELTA_LOG_DIR: &str = "_delta_log";
+const READER_FEATURES: [&str; 1] = ["timestampNtz"];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum DeltaError {
+    #[error("Not a delta table: {0}")]
+    TableNotFound(String),
+
+    #[error("Version not found: {0}")]
+    VersionNotFound(i64),
+
+    #[error("Version {0} was already committed")]
+    VersionConflict(i64),
+
+    #[error("Schema does not match the table schema")]
+    SchemaMismatch,
+
+    #[error("Unsupported delta type {1} of column {0}")]
+    UnsupportedType(String, String),
+
+    #[error("Invalid delta log: {0}")]
+    InvalidLog(String),
+}
+
+/// How a write treats the data already in the table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SaveMode {
+    #[default]
+    Append,
+    Overwrite,
+}
+
+/// State of a delta table at one version.
+#[derive(Debug, Clone)]
+pub struct DeltaSnapshot {
+    version: i64,
+    id: String,
+    schema: SchemaRef,
+    files: BTreeSet<String>,
+}
+
+impl DeltaSnapshot {
+    pub fn version(&self) -> i64 {
+        self.version
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Data files of the version, relative to the table directory.
+    pub fn files(&self) -> Vec<String> {
+        self.files.iter().cloned().collect()
+    }
+}
+
+/// Delta Lake table at `path` of a lake store: Parquet data files plus the JSON commits of
+/// `_delta_log`, each write becoming one new version.
+///
+/// A commit only succeeds if its log entry does not exist yet, S3 needs conditional puts
+/// enabled for that, e.g. `AWS_CONDITIONAL_PUT=etag`. Checkpoints and partitioned tables are
+/// not supported.
+#[derive(Debug, Clone)]
+pub struct DeltaTable {
+    store: LakeStore,
+    path: String,
+}
+
+impl DeltaTable {
+    pub fn new(store: LakeStore, path: &str) -> Self {
+        Self {
+            store,
+            path: path.trim_matches('/').to_string(),
+        }
+    }
+
+    fn location(&self, path: &str) -> String {
+        if self.path.is_empty() {
+            path.to_string()
+        } else {
+            format!("{}/{}", self.path, path)
+        }
+    }
+
+    fn log_location(&self, version: i64) -> String {
+        self.location(&format!("{}/{:020}.json", DELTA_LOG_DIR, version))
+    }
+
+    async fn versions(&self) -> Result<Vec<i64>, AppError> {
+        let prefix = self.store.path(&self.location(DELTA_LOG_DIR));
+        let objects = match self.store.store().list_with_delimiter(Some(&prefix)).await {
+            Ok(list) => list.objects,
+            Err(object_store::Error::NotFound { .. }) => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        let mut versions = objects
+            .iter()
+            .filter_map(|meta| {
+                let name = meta.location.filename()?.strip_suffix(".json")?;
+                name.parse::<i64>().ok()
+            })
+            .collect::<Vec<_>>();
+        versions.sort_unstable();
+        Ok(versions)
+    }
+
+    /// Latest committed version, `None` if the table does not exist yet.
+    pub async fn version(&self) -> Result<Option<i64>, AppError> {
+        Ok(self.versions().await?.pop())
+    }
+
+    /// Replays the log up to `version`, the latest one by default.
+    pub async fn snapshot(&self, version: Option<i64>) -> Result<DeltaSnapshot, AppError> {
+        let versions = self.versions().await?;
+        let Some(latest) = versions.last() else {
+            return Err(DeltaError::TableNotFound(self.store.url(&self.path)).into());
+        };
+        let version = version.unwrap_or(*latest);
+        if !versions.contains(&version) {
+            return Err(DeltaError::VersionNotFound(version).into());
+        }
+
+        let mut metadata = None;
+        let mut files = BTreeSet::new();
+        for version in 0..=version {
+            let location = self.store.path(&self.log_location(version));
+            let buf = self.store.store().get(&location).await?.bytes().await?;
+            for line in buf.split(|b| *b == b'\n').filter(|line| !line.is_empty()) {
+                let action = serde_json::from_slice::<Value>(line)?;
+                if let Some(protocol) = action.get("protocol") {
+                    check_protocol(protocol)?;
+                }
+                if let Some(value) = action.get("metaData") {
+                    metadata = Some(parse_metadata(value)?);
+                }
+                if let Some(path) = action.pointer("/add/path").and_then(Value::as_str) {
+                    files.insert(path.to_string());
+                }
+                if let Some(path) = action.pointer("/remove/path").and_then(Value::as_str) {
+                    files.remove(path);
+                }
+            }
+        }
+        let (id, schema) =
+            metadata.ok_or_else(|| DeltaError::InvalidLog("missing metaData".to_string()))?;
+
+        Ok(DeltaSnapshot {
+            version,
+            id,
+            schema: Arc::new(schema),
+            files,
+        })
+    }
+
+    /// Creates the table without data as version 0, e.g. with `Flights::schema()`. Fails with
+    /// [`DeltaError::VersionConflict`] if the table exists.
+    pub async fn create(&self, schema: &Schema) -> Result<i64, AppError> {
+        let now = Utc::now().timestamp_millis();
+        let actions = vec![
+            commit_info(now, "CREATE TABLE", None),
+            protocol(schema),
+            metadata(&Uuid::new_v4().to_string(), schema, now)?,
+        ];
+        self.commit(0, &actions).await?;
+        Ok(0)
+    }
+
+    pub async fn write_df(
+        &self,
+        df: DataFrame,
+        mode: SaveMode,
+        options: &ParquetWriteOptions,
+    ) -> Result<i64, AppError> {
+        let stream = df.execute_stream().await?;
+        self.write_stream(stream, mode, options).await
+    }
+
+    /// Writes the batches to one new data file and commits it, returning the new version. A
+    /// missing table is created with the stream schema, appends must keep the table schema and
+    /// an overwrite replaces both the data and the schema.
+    pub async fn write_stream(
+        &self,
+        stream: SendableRecordBatchStream,
+        mode: SaveMode,
+        options: &ParquetWriteOptions,
+    ) -> Result<i64, AppError> {
+        let schema = stream.schema();
+        let current = match self.version().await? {
+            Some(version) => Some(self.snapshot(Some(version)).await?),
+            None => None,
+        };
+        if let (SaveMode::Append, Some(snapshot)) = (mode, &current) {
+            if schema_to_delta(&snapshot.schema)? != schema_to_delta(&schema)? {
+                return Err(DeltaError::SchemaMismatch.into());
+            }
+        }
+
+        let file = format!("part-00000-{}-c000.parquet", Uuid::new_v4());
+        let location = self.location(&file);
+        write_stream_to_store(stream, &self.store, &location, options).await?;
+        let meta = self.store.store().head(&self.store.path(&location)).await?;
+
+        let now = Utc::now().timestamp_millis();
+        let mut actions = vec![commit_info(now, "WRITE", Some(mode))];
+        match &current {
+            None => {
+                actions.push(protocol(&schema));
+                actions.push(metadata(&Uuid::new_v4().to_string(), &schema, now)?);
+            }
+            Some(snapshot) if mode == SaveMode::Overwrite => {
+                actions.push(protocol(&schema));
+                actions.push(metadata(&snapshot.id, &schema, now)?);
+                for path in &snapshot.files {
+                    actions.push(json!({"remove": {
+                        "path": path,
+                        "deletionTimestamp": now,
+                        "dataChange": true,
+                    }}));
+                }
+            }
+            Some(_) => {}
+        }
+        actions.push(json!({"add": {
+            "path": file,
+            "partitionValues": {},
+            "size": meta.size,
+            "modificationTime": meta.last_modified.timestamp_millis(),
+            "dataChange": true,
+        }}));
+
+        let version = current.map_or(0, |snapshot| snapshot.version + 1);
+        if let Err(e) = self.commit(version, &actions).await {
+            let _ = self.store.store().delete(&self.store.path(&location)).await;
+            return Err(e);
+        }
+        Ok(version)
+    }
+
+    /// Returns a dataframe of the table at `version`, the latest one by default, registering
+    /// the store with `ctx`.
+    pub async fn read_to_df(
+        &self,
+        ctx: &SessionContext,
+        version: Option<i64>,
+    ) -> Result<DataFrame, AppError> {
+        let snapshot = self.snapshot(version).await?;
+        if snapshot.files.is_empty() {
+            return Ok(ctx.read_batch(RecordBatch::new_empty(snapshot.schema))?);
+        }
+
+        self.store.register(ctx);
+        let urls = snapshot
+            .files
+            .iter()
+            .map(|file| ListingTableUrl::parse(self.store.url(&self.location(file))))
+            .collect::<Result<Vec<_>, _>>()?;
+        let options = ListingOptions::new(Arc::new(ParquetFormat::default()));
+        let config = ListingTableConfig::new_with_multi_paths(urls)
+            .with_listing_options(options)
+            .with_schema(snapshot.schema);
+        let df = ctx.read_table(Arc::new(ListingTable::try_new(config)?))?;
+
+        Ok(df)
+    }
+
+    // the entry is only created if missing, so of two writers racing for a version one fails
+    async fn commit(&self, version: i64, actions: &[Value]) -> Result<(), AppError> {
+        let mut buf = vec![];
+        for action in actions {
+            serde_json::to_writer(&mut buf, action)?;
+            buf.push(b'\n');
+        }
+        let location = self.store.path(&self.log_location(version));
+        let opts = PutOptions::from(PutMode::Create);
+        match self
+            .store
+            .store()
+            .put_opts(&location, PutPayload::from(buf), opts)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(object_store::Error::AlreadyExists { .. }) => {
+                Err(DeltaError::VersionConflict(version).into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn commit_info(timestamp: i64, operation: &str, mode: Option<SaveMode>) -> Value {
+    let parameters = match mode {
+        Some(SaveMode::Append) => json!({"mode": "Append"}),
+        Some(SaveMode::Overwrite) => json!({"mode": "Overwrite"}),
+        None => json!({}),
+    };
+    json!({"commitInfo": {
+        "timestamp": timestamp,
+        "operation": operation,
+        "operationParameters": parameters,
+        "engineInfo": concat!("demodb-to-datalake/", env!("CARGO_PKG_VERSION")),
+    }})
+}
+
+fn protocol(schema: &Schema) -> Value {
+    let timestamp_ntz = schema
+        .fields()
+        .iter()
+        .any(|field| has_timestamp_ntz(field.data_type()));
+    if timestamp_ntz {
+        json!({"protocol": {
+            "minReaderVersion": 3,
+            "minWriterVersion": 7,
+            "readerFeatures": READER_FEATURES,
+            "writerFeatures": READER_FEATURES,
+        }})
+    } else {
+        json!({"protocol": {"minReaderVersion": 1, "minWriterVersion": 2}})
+    }
+}
+
+fn has_timestamp_ntz(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::Timestamp(_, None) => true,
+        DataType::List(field) => has_timestamp_ntz(field.data_type()),
+        DataType::Struct(fields) => fields
+            .iter()
+            .any(|field| has_timestamp_ntz(field.data_type())),
+        _ => false,
+    }
+}
+
+fn metadata(id: &str, schema: &Schema, created_time: i64) -> Result<Value, AppError> {
+    Ok(json!({"metaData": {
+        "id": id,
+        "format": {"provider": "parquet", "options": {}},
+        "schemaString": schema_to_delta(schema)?.to_string(),
+        "partitionColumns": [],
+        "configuration": {},
+        "createdTime": created_time,
+    }}))
+}
+
+// tables using features this reader does not know, like deletion vectors, must not be read
+fn check_protocol(protocol: &Value) -> Result<(), DeltaError> {
+    let features = protocol["readerFeatures"].as_array().into_iter().flatten();
+    for feature in features {
+        let feature = feature.as_str().unwrap_or_default();
+        if !READER_FEATURES.contains(&feature) {
+            return Err(DeltaError::InvalidLog(format!(
+                "unsupported reader feature {}",
+                feature
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn parse_metadata(value: &Value) -> Result<(String, Schema), AppError> {
+    let invalid = |field: &str| DeltaError::InvalidLog(format!("invalid metaData.{}", field));
+    if value["partitionColumns"]
+        .as_array()
+        .is_some_and(|columns| !columns.is_empty())
+    {
+        return Err(
+            DeltaError::InvalidLog("partitioned tables are not supported".to_string()).into(),
+        );
+    }
+    let id = value["id"].as_str().ok_or_else(|| invalid("id"))?;
+    let schema_string = value["schemaString"]
+        .as_str()
+        .ok_or_else(|| invalid("schemaString"))?;
+    let schema = delta_to_schema(&serde_json::from_str(schema_string)?)?;
+    Ok((id.to_string(), schema))
+}
+
+/// Delta schema of `schema`, as stored in `metaData.schemaString`.
+pub fn schema_to_delta(schema: &Schema) -> Result<Value, DeltaError> {
+    fields_to_delta(schema.fields())
+}
+
+fn fields_to_delta(fields: &Fields) -> Result<Value, DeltaError> {
+    let fields = fields
+        .iter()
+        .map(|field| {
+            Ok(json!({
+                "name": field.name(),
+                "type": type_to_delta(field.name(), field.data_type())?,
+                "nullable": field.is_nullable(),
+                "metadata": {},
+            }))
+        })
+        .collect::<Result<Vec<_>, DeltaError>>()?;
+    Ok(json!({"type": "struct", "fields": fields}))
+}
+
+fn type_to_delta(name: &str, data_type: &DataType) -> Result<Value, DeltaError> {
+    let primitive = match data_type {
+        DataType::Utf8 | DataType::LargeUtf8 => "string",
+        DataType::Int8 => "byte",
+        DataType::Int16 => "short",
+        DataType::Int32 => "integer",
+        DataType::Int64 => "long",
+        DataType::Float32 => "float",
+        DataType::Float64 => "double",
+        DataType::Boolean => "boolean",
+        DataType::Binary | DataType::LargeBinary => "binary",
+        DataType::Date32 => "date",
+        DataType::Timestamp(TimeUnit::Microsecond, Some(_)) => "timestamp",
+        DataType::Timestamp(TimeUnit::Microsecond, None) => "timestamp_ntz",
+        DataType::Decimal128(precision, scale) if *scale >= 0 => {
+            return Ok(Value::String(format!("decimal({},{})", precision, scale)))
+        }
+        DataType::List(field) => {
+            return Ok(json!({
+                "type": "array",
+                "elementType": type_to_delta(name, field.data_type())?,
+                "containsNull": field.is_nullable(),
+            }))
+        }
+        DataType::Struct(fields) => return fields_to_delta(fields),
+        other => {
+            return Err(DeltaError::UnsupportedType(
+                name.to_string(),
+                other.to_string(),
+            ))
+        }
+    };
+    Ok(Value::String(primitive.to_string()))
+}
+
+/// Arrow schema of a delta schema, timestamps are read in [`TIMESTAMP_TZ`].
+pub fn delta_to_schema(value: &Value) -> Result<Schema, DeltaError> {
+    Ok(Schema::new(delta_to_fields(value)?))
+}
+
+fn delta_to_fields(value: &Value) -> Result<Fields, DeltaError> {
+    let invalid = || DeltaError::InvalidLog(format!("invalid struct type {}", value));
+    let fields = value["fields"].as_array().ok_or_else(invalid)?;
+    fields
+        .iter()
+        .map(|field| {
+            let name = field["name"].as_str().ok_or_else(invalid)?;
+            let data_type = delta_to_type(name, &field["type"])?;
+            let nullable = field["nullable"].as_bool().unwrap_or(true);
+            Ok(Field::new(name, data_type, nullable))
+        })
+        .collect()
+}
+
+fn delta_to_type(name: &str, value: &Value) -> Result<DataType, DeltaError> {
+    let unsupported = || DeltaError::UnsupportedType(name.to_string(), value.to_string());
+    let data_type = match value {
+        Value::String(primitive) => match primitive.as_str() {
+            "string" => DataType::Utf8,
+            "byte" => DataType::Int8,
+            "short" => DataType::Int16,
+            "integer" => DataType::Int32,
+            "long" => DataType::Int64,
+            "float" => DataType::Float32,
+            "double" => DataType::Float64,
+            "boolean" => DataType::Boolean,
+            "binary" => DataType::Binary,
+            "date" => DataType::Date32,
+            "timestamp" => DataType::Timestamp(TimeUnit::Microsecond, Some(TIMESTAMP_TZ.into())),
+            "timestamp_ntz" => DataType::Timestamp(TimeUnit::Microsecond, None),
+            decimal => {
+                let (precision, scale) = decimal
+                    .strip_prefix("decimal(")
+                    .and_then(|decimal| decimal.strip_suffix(')'))
+                    .and_then(|decimal| decimal.split_once(','))
+                    .ok_or_else(unsupported)?;
+                let precision = precision.trim().parse().map_err(|_| unsupported())?;
+                let scale = scale.trim().parse().map_err(|_| unsupported())?;
+                DataType::Decimal128(precision, scale)
+            }
+        },
+        Value::Object(_) => match value["type"].as_str() {
+            Some("array") => {
+                let element = delta_to_type(name, &value["elementType"])?;
+                let nullable = value["containsNull"].as_bool().unwrap_or(true);
+                DataType::List(Arc::new(Field::new_list_field(element, nullable)))
+            }
+            Some("struct") => DataType::Struct(delta_to_fields(value)?),
+            _ => return Err(unsupported()),
+        },
+        _ => return Err(unsupported()),
+    };
+    Ok(data_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::{Int32Array, StringArray};
+    use datafusion::arrow::datatypes::IntervalUnit;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::point_struct_type;
+
+    fn seats(codes: &[&str], rows: &[i32]) -> DataFrame {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("aircraft_code", DataType::Utf8, false),
+            Field::new("row", DataType::Int32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(codes.to_vec())),
+                Arc::new(Int32Array::from(rows.to_vec())),
+            ],
+        )
+        .unwrap();
+        SessionContext::new().read_batch(batch).unwrap()
+    }
+
+    #[rstest]
+    #[case(DataType::Utf8, json!("string"))]
+    #[case(DataType::Int16, json!("short"))]
+    #[case(DataType::Float64, json!("double"))]
+    #[case(DataType::Decimal128(10, 2), json!("decimal(10,2)"))]
+    #[case(DataType::Timestamp(TimeUnit::Microsecond, Some(TIMESTAMP_TZ.into())), json!("timestamp"))]
+    #[case(DataType::Timestamp(TimeUnit::Microsecond, None), json!("timestamp_ntz"))]
+    #[case(
+        DataType::List(Arc::new(Field::new_list_field(DataType::Int32, true))),
+        json!({"type": "array", "elementType": "integer", "containsNull": true})
+    )]
+    #[case(
+        point_struct_type(),
+        json!({"type": "struct", "fields": [
+            {"name": "x", "type": "double", "nullable": false, "metadata": {}},
+            {"name": "y", "type": "double", "nullable": false, "metadata": {}},
+        ]})
+    )]
+    fn delta_type_test(#[case] data_type: DataType, #[case] expected: Value) {
+        assert_eq!(expected, type_to_delta("column", &data_type).unwrap());
+        assert_eq!(data_type, delta_to_type("column", &expected).unwrap());
+    }
+
+    #[test]
+    fn delta_type_unsupported_test() {
+        assert_eq!(
+            Err(DeltaError::UnsupportedType(
+                "duration".to_string(),
+                "Interval(MonthDayNano)".to_string()
+            )),
+            type_to_delta("duration", &DataType::Interval(IntervalUnit::MonthDayNano))
+        );
+        assert!(delta_to_type("tags", &json!({"type": "map"})).is_err());
+    }
+
+    #[tokio::test]
+    async fn delta_table_test() {
+        let store = LakeStore::from_url("memory:///lake").unwrap();
+        let table = DeltaTable::new(store.clone(), "seats");
+        let options = ParquetWriteOptions::default();
+        assert_eq!(table.version().await.unwrap(), None);
+
+        let df = seats(&["319", "319"], &[1, 2]);
+        assert_eq!(table.create(df.schema().as_arrow()).await.unwrap(), 0);
+        assert!(matches!(
+            table.create(df.schema().as_arrow()).await,
+            Err(AppError::DeltaError(DeltaError::VersionConflict(0)))
+        ));
+        let append = SaveMode::Append;
+        assert_eq!(table.write_df(df, append, &options).await.unwrap(), 1);
+        let df = seats(&["773"], &[1]);
+        assert_eq!(table.write_df(df, append, &options).await.unwrap(), 2);
+        let df = seats(&["CN1"], &[1]);
+        let overwrite = SaveMode::Overwrite;
+        assert_eq!(table.write_df(df, overwrite, &options).await.unwrap(), 3);
+
+        let schema = Arc::new(Schema::new(vec![Field::new("row", DataType::Int32, true)]));
+        let batch = RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(vec![1]))]);
+        let df = SessionContext::new().read_batch(batch.unwrap()).unwrap();
+        assert!(matches!(
+            table.write_df(df, append, &options).await,
+            Err(AppError::DeltaError(DeltaError::SchemaMismatch))
+        ));
+
+        let ctx = SessionContext::new();
+        let mut counts = vec![];
+        for version in [Some(0), Some(1), Some(2), None] {
+            let df = table.read_to_df(&ctx, version).await.unwrap();
+            counts.push(df.count().await.unwrap());
+        }
+        assert_eq!(counts, vec![0, 2, 3, 1]);
+
+        let snapshot = table.snapshot(None).await.unwrap();
+        assert_eq!(snapshot.version(), 3);
+        assert_eq!(snapshot.files().len(), 1);
+        assert_eq!(table.snapshot(Some(2)).await.unwrap().files().len(), 2);
+        assert!(matches!(
+            table.snapshot(Some(4)).await,
+            Err(AppError::DeltaError(DeltaError::VersionNotFound(4)))
+        ));
+        let log = store
+            .store()
+            .get(&store.path("seats/_delta_log/00000000000000000003.json"))
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        let log = String::from_utf8(log.to_vec()).unwrap();
+        assert_eq!(log.matches("\"remove\"").count(), 2);
+        assert_eq!(log.matches("\"add\"").count(), 1);
+    }
+}
@@ -0,0 +1,615 @@
+use arrow_ipc::writer::{FileWriter, IpcWriteOptions};
+use arrow_ipc::CompressionType;
+use arrow_json::writer::LineDelimited;
+use async_compression::tokio::write::{GzipEncoder, ZstdEncoder};
+use datafusion::arrow::array::RecordBatch;
+use datafusion::arrow::csv::WriterBuilder;
+use datafusion::arrow::datatypes::{Schema, SchemaRef};
+use datafusion::physical_plan::SendableRecordBatchStream;
+use datafusion::prelude::*;
+use object_store::buffered::BufWriter;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::fs::File;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio_stream::StreamExt;
+
+use super::avro::{batch_to_avro, schema_to_avro, AvroCodec, ContainerWriter};
+use super::store::LakeStore;
+use super::utils::write_stream_to_writer;
+use super::write_options::ParquetWriteOptions;
+use crate::AppError;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum FormatError {
+    #[error("CSV {0} must be a single ASCII character: {1}")]
+    InvalidCsvCharacter(String, char),
+}
+
+/// Compression of text formats, applied to the whole file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileCompression {
+    #[default]
+    Uncompressed,
+    Gzip,
+    Zstd,
+}
+
+impl FileCompression {
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Uncompressed => "",
+            Self::Gzip => ".gz",
+            Self::Zstd => ".zst",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CsvOptions {
+    delimiter: char,
+    header: bool,
+    quote: char,
+    null_value: String,
+    compression: FileCompression,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            header: true,
+            quote: '"',
+            null_value: String::new(),
+            compression: FileCompression::default(),
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_delimiter(self, delimiter: char) -> Self {
+        Self { delimiter, ..self }
+    }
+
+    pub fn with_header(self, header: bool) -> Self {
+        Self { header, ..self }
+    }
+
+    pub fn with_quote(self, quote: char) -> Self {
+        Self { quote, ..self }
+    }
+
+    pub fn with_null_value(self, null_value: &str) -> Self {
+        Self {
+            null_value: null_value.to_string(),
+            ..self
+        }
+    }
+
+    pub fn with_compression(self, compression: FileCompression) -> Self {
+        Self {
+            compression,
+            ..self
+        }
+    }
+
+    fn writer_builder(&self) -> Result<WriterBuilder, FormatError> {
+        let ascii = |name: &str, c: char| {
+            u8::try_from(c)
+                .ok()
+                .filter(u8::is_ascii)
+                .ok_or_else(|| FormatError::InvalidCsvCharacter(name.to_string(), c))
+        };
+        Ok(WriterBuilder::new()
+            .with_delimiter(ascii("delimiter", self.delimiter)?)
+            .with_quote(ascii("quote", self.quote)?)
+            .with_null(self.null_value.clone()))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NdJsonOptions {
+    explicit_nulls: bool,
+    compression: FileCompression,
+}
+
+impl NdJsonOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes null values as `"key": null` instead of leaving the key out.
+    pub fn with_explicit_nulls(self, explicit_nulls: bool) -> Self {
+        Self {
+            explicit_nulls,
+            ..self
+        }
+    }
+
+    pub fn with_compression(self, compression: FileCompression) -> Self {
+        Self {
+            compression,
+            ..self
+        }
+    }
+}
+
+/// Buffer compression of Arrow IPC files, done per buffer so readers can still seek.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpcCompression {
+    Lz4,
+    Zstd,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArrowIpcOptions {
+    compression: Option<IpcCompression>,
+}
+
+impl ArrowIpcOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_compression(self, compression: IpcCompression) -> Self {
+        Self {
+            compression: Some(compression),
+        }
+    }
+
+    fn write_options(&self) -> Result<IpcWriteOptions, AppError> {
+        let compression = self.compression.map(|compression| match compression {
+            IpcCompression::Lz4 => CompressionType::LZ4_FRAME,
+            IpcCompression::Zstd => CompressionType::ZSTD,
+        });
+        Ok(IpcWriteOptions::default().try_with_compression(compression)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AvroOptions {
+    codec: AvroCodec,
+    name: String,
+    namespace: Option<String>,
+}
+
+impl Default for AvroOptions {
+    fn default() -> Self {
+        Self {
+            codec: AvroCodec::default(),
+            name: "topLevelRecord".to_string(),
+            namespace: None,
+        }
+    }
+}
+
+impl AvroOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_codec(self, codec: AvroCodec) -> Self {
+        Self { codec, ..self }
+    }
+
+    /// Name of the top level record, usually the table name.
+    pub fn with_name(self, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..self
+        }
+    }
+
+    pub fn with_namespace(self, namespace: &str) -> Self {
+        Self {
+            namespace: Some(namespace.to_string()),
+            ..self
+        }
+    }
+
+    /// Avro schema the rows of `schema` are written with.
+    pub fn avro_schema(&self, schema: &Schema) -> Result<serde_json::Value, AppError> {
+        Ok(schema_to_avro(
+            schema,
+            &self.name,
+            self.namespace.as_deref(),
+        )?)
+    }
+}
+
+/// File format of an extract with its writer options. In configs the format is selected by
+/// a `format` key next to the options, e.g. `{"format": "csv", "delimiter": ";"}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum OutputFormat {
+    Parquet(ParquetWriteOptions),
+    Csv(CsvOptions),
+    NdJson(NdJsonOptions),
+    /// Arrow IPC file format, also known as Feather V2.
+    #[serde(alias = "feather")]
+    ArrowIpc(ArrowIpcOptions),
+    Avro(AvroOptions),
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::Parquet(ParquetWriteOptions::default())
+    }
+}
+
+impl OutputFormat {
+    /// File extension without the leading dot, including the compression suffix.
+    pub fn extension(&self) -> String {
+        match self {
+            Self::Parquet(_) => "parquet".to_string(),
+            Self::Csv(options) => format!("csv{}", options.compression.extension()),
+            Self::NdJson(options) => format!("ndjson{}", options.compression.extension()),
+            Self::ArrowIpc(_) => "arrow".to_string(),
+            Self::Avro(_) => "avro".to_string(),
+        }
+    }
+}
+
+pub async fn write_df_to_file_as(
+    df: DataFrame,
+    file_path: &str,
+    format: &OutputFormat,
+) -> Result<(), AppError> {
+    let stream = df.execute_stream().await?;
+    write_stream_to_file_as(stream, file_path, format).await
+}
+
+/// Streams the batches to `<file_path>.tmp` and renames it to `file_path` once the file is
+/// complete and synced, so a failed or interrupted write never leaves a truncated file behind.
+pub async fn write_stream_to_file_as(
+    stream: SendableRecordBatchStream,
+    file_path: &str,
+    format: &OutputFormat,
+) -> Result<(), AppError> {
+    let tmp_path = format!("{}.tmp", file_path);
+    let res = async {
+        let mut file = File::create(&tmp_path).await?;
+        write_stream_to_writer_as(stream, &mut file, format).await?;
+        file.sync_all().await?;
+        Ok::<_, AppError>(())
+    }
+    .await;
+    match res {
+        Ok(()) => {
+            tokio::fs::rename(&tmp_path, file_path).await?;
+            Ok(())
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            Err(e)
+        }
+    }
+}
+
+pub async fn write_df_to_store_as(
+    df: DataFrame,
+    store: &LakeStore,
+    path: &str,
+    format: &OutputFormat,
+) -> Result<(), AppError> {
+    let stream = df.execute_stream().await?;
+    write_stream_to_store_as(stream, store, path, format).await
+}
+
+/// Uploads the batches to `path` in the lake store, switching to a multipart upload once the
+/// data outgrows one buffer. The object only becomes visible when the upload completes, a failed
+/// write aborts it.
+pub async fn write_stream_to_store_as(
+    stream: SendableRecordBatchStream,
+    store: &LakeStore,
+    path: &str,
+    format: &OutputFormat,
+) -> Result<(), AppError> {
+    let mut writer = BufWriter::new(store.store(), store.path(path));
+    if let Err(e) = write_stream_to_writer_as(stream, &mut writer, format).await {
+        let _ = writer.abort().await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Writes the standalone `.avsc` schema of Avro files written from `schema` with `options`, so
+/// it can be registered ahead of the data.
+pub async fn write_avro_schema_to_file(
+    schema: &Schema,
+    options: &AvroOptions,
+    file_path: &str,
+) -> Result<(), AppError> {
+    let schema = serde_json::to_string_pretty(&options.avro_schema(schema)?)?;
+    tokio::fs::write(file_path, schema).await?;
+    Ok(())
+}
+
+/// Writes the batches in `format` to any async sink one at a time and shuts the sink down.
+pub async fn write_stream_to_writer_as<W: AsyncWrite + Unpin + Send>(
+    stream: SendableRecordBatchStream,
+    writer: W,
+    format: &OutputFormat,
+) -> Result<(), AppError> {
+    let (encoder, compression) = match format {
+        OutputFormat::Parquet(options) => {
+            return write_stream_to_writer(stream, writer, options).await
+        }
+        OutputFormat::Csv(options) => {
+            let builder = options.writer_builder()?;
+            let encoder = BatchEncoder::Csv {
+                builder,
+                header: options.header,
+            };
+            (encoder, options.compression)
+        }
+        OutputFormat::NdJson(options) => {
+            let encoder = BatchEncoder::NdJson {
+                explicit_nulls: options.explicit_nulls,
+            };
+            (encoder, options.compression)
+        }
+        OutputFormat::ArrowIpc(options) => {
+            let writer = FileWriter::try_new_with_options(
+                vec![],
+                &stream.schema(),
+                options.write_options()?,
+            )?;
+            (BatchEncoder::Ipc(writer), FileCompression::Uncompressed)
+        }
+        OutputFormat::Avro(options) => {
+            let schema = options.avro_schema(&stream.schema())?;
+            let writer = ContainerWriter::new(&schema, options.codec)?;
+            let header = Some(writer.header()?);
+            (
+                BatchEncoder::Avro { writer, header },
+                FileCompression::Uncompressed,
+            )
+        }
+    };
+    match compression {
+        FileCompression::Uncompressed => write_encoded(stream, writer, encoder).await,
+        FileCompression::Gzip => write_encoded(stream, GzipEncoder::new(writer), encoder).await,
+        FileCompression::Zstd => write_encoded(stream, ZstdEncoder::new(writer), encoder).await,
+    }
+}
+
+/// Encodes batches of the row based formats into bytes, one batch at a time.
+enum BatchEncoder {
+    Csv {
+        builder: WriterBuilder,
+        header: bool,
+    },
+    NdJson {
+        explicit_nulls: bool,
+    },
+    Ipc(FileWriter<Vec<u8>>),
+    Avro {
+        writer: ContainerWriter,
+        header: Option<Vec<u8>>,
+    },
+}
+
+impl BatchEncoder {
+    fn encode(&mut self, batch: &RecordBatch) -> Result<Vec<u8>, AppError> {
+        match self {
+            Self::Csv { builder, header } => {
+                let mut writer = builder.clone().with_header(*header).build(vec![]);
+                writer.write(batch)?;
+                *header = false;
+                Ok(writer.into_inner())
+            }
+            Self::NdJson { explicit_nulls } => {
+                let mut writer = arrow_json::WriterBuilder::new()
+                    .with_explicit_nulls(*explicit_nulls)
+                    .build::<_, LineDelimited>(vec![]);
+                writer.write(batch)?;
+                writer.finish()?;
+                Ok(writer.into_inner())
+            }
+            Self::Ipc(writer) => {
+                writer.write(batch)?;
+                Ok(std::mem::take(writer.get_mut()))
+            }
+            Self::Avro { writer, header } => {
+                let mut buf = header.take().unwrap_or_default();
+                buf.append(&mut writer.block(&batch_to_avro(batch)?)?);
+                Ok(buf)
+            }
+        }
+    }
+
+    // csv and avro headers are written even without rows
+    fn finish(&mut self, schema: SchemaRef) -> Result<Vec<u8>, AppError> {
+        match self {
+            Self::Csv { header: true, .. } => self.encode(&RecordBatch::new_empty(schema)),
+            Self::Csv { .. } | Self::NdJson { .. } => Ok(vec![]),
+            Self::Ipc(writer) => {
+                writer.finish()?;
+                Ok(std::mem::take(writer.get_mut()))
+            }
+            Self::Avro { header, .. } => Ok(header.take().unwrap_or_default()),
+        }
+    }
+}
+
+async fn write_encoded<W: AsyncWrite + Unpin + Send>(
+    mut stream: SendableRecordBatchStream,
+    mut writer: W,
+    mut encoder: BatchEncoder,
+) -> Result<(), AppError> {
+    while let Some(batch) = stream.next().await.transpose()? {
+        writer.write_all(&encoder.encode(&batch)?).await?;
+    }
+    writer.write_all(&encoder.finish(stream.schema())?).await?;
+    writer.shutdown().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{Int32Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::datasource::file_format::file_compression_type::FileCompressionType;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn seats() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("seat_no", DataType::Utf8, false),
+            Field::new("fare_conditions", DataType::Utf8, true),
+            Field::new("row", DataType::Int32, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["1A", "1B", "20C"])),
+                Arc::new(StringArray::from(vec![
+                    Some("Business"),
+                    None,
+                    Some("Eco;nomy"),
+                ])),
+                Arc::new(Int32Array::from(vec![Some(1), Some(1), None])),
+            ],
+        )
+        .unwrap()
+    }
+
+    async fn write(format: &OutputFormat) -> Vec<u8> {
+        let df = SessionContext::new().read_batch(seats()).unwrap();
+        let stream = df.execute_stream().await.unwrap();
+        let mut buf = vec![];
+        write_stream_to_writer_as(stream, &mut buf, format)
+            .await
+            .unwrap();
+        buf
+    }
+
+    #[rstest]
+    #[case(
+        CsvOptions::new(),
+        "seat_no,fare_conditions,row\n1A,Business,1\n1B,,1\n20C,Eco;nomy,\n"
+    )]
+    #[case(
+        CsvOptions::new().with_delimiter(';').with_header(false).with_quote('\'').with_null_value("NULL"),
+        "1A;Business;1\n1B;NULL;1\n20C;'Eco;nomy';NULL\n"
+    )]
+    #[tokio::test]
+    async fn csv_test(#[case] options: CsvOptions, #[case] expected: &str) {
+        let buf = write(&OutputFormat::Csv(options)).await;
+        assert_eq!(String::from_utf8(buf).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn ndjson_test() {
+        let options = NdJsonOptions::new().with_explicit_nulls(true);
+        let buf = write(&OutputFormat::NdJson(options)).await;
+        let lines = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            lines.lines().nth(1).unwrap(),
+            r#"{"seat_no":"1B","fare_conditions":null,"row":1}"#
+        );
+        assert_eq!(lines.lines().count(), 3);
+    }
+
+    #[rstest]
+    #[case(FileCompression::Gzip, FileCompressionType::GZIP)]
+    #[case(FileCompression::Zstd, FileCompressionType::ZSTD)]
+    #[tokio::test]
+    async fn compressed_csv_test(
+        #[case] compression: FileCompression,
+        #[case] compression_type: FileCompressionType,
+    ) {
+        let format = OutputFormat::Csv(CsvOptions::new().with_compression(compression));
+        let path = std::env::temp_dir()
+            .join(format!(
+                "seats_{}.{}",
+                std::process::id(),
+                format.extension()
+            ))
+            .to_string_lossy()
+            .into_owned();
+        let df = SessionContext::new().read_batch(seats()).unwrap();
+        write_df_to_file_as(df, &path, &format).await.unwrap();
+
+        let options = CsvReadOptions::new()
+            .file_extension(&path[path.rfind(".csv").unwrap()..])
+            .file_compression_type(compression_type);
+        let df = SessionContext::new()
+            .read_csv(&path, options)
+            .await
+            .unwrap();
+        let rows = df.count().await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert_eq!(rows, 3);
+    }
+
+    #[rstest]
+    #[case(None)]
+    #[case(Some(IpcCompression::Lz4))]
+    #[case(Some(IpcCompression::Zstd))]
+    #[tokio::test]
+    async fn arrow_ipc_test(#[case] compression: Option<IpcCompression>) {
+        let options = ArrowIpcOptions { compression };
+        let buf = write(&OutputFormat::ArrowIpc(options)).await;
+        let reader =
+            datafusion::arrow::ipc::reader::FileReader::try_new(std::io::Cursor::new(buf), None)
+                .unwrap();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches, vec![seats()]);
+    }
+
+    #[test]
+    fn output_format_test() {
+        let format = serde_json::from_str::<OutputFormat>(
+            r#"{"format": "csv", "delimiter": ";", "compression": "gzip"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            format,
+            OutputFormat::Csv(
+                CsvOptions::new()
+                    .with_delimiter(';')
+                    .with_compression(FileCompression::Gzip)
+            )
+        );
+        assert_eq!(format.extension(), "csv.gz");
+        let format = serde_json::from_str::<OutputFormat>(r#"{"format": "feather"}"#).unwrap();
+        assert_eq!(format, OutputFormat::ArrowIpc(ArrowIpcOptions::new()));
+        let format = serde_json::from_str::<OutputFormat>(
+            r#"{"format": "avro", "codec": "snappy", "name": "seats"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            format,
+            OutputFormat::Avro(
+                AvroOptions::new()
+                    .with_codec(AvroCodec::Snappy)
+                    .with_name("seats")
+            )
+        );
+        assert_eq!(format.extension(), "avro");
+        assert!(serde_json::from_str::<OutputFormat>(r#"{"format": "xml"}"#).is_err());
+        assert_eq!(
+            CsvOptions::new().with_delimiter('→').writer_builder().err(),
+            Some(FormatError::InvalidCsvCharacter(
+                "delimiter".to_string(),
+                '→'
+            ))
+        );
+    }
+}
@@ -0,0 +1,497 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use datafusion::arrow::array::{Array, AsArray, RecordBatch, UInt32Array};
+use datafusion::arrow::compute::{cast, take_record_batch};
+use datafusion::arrow::datatypes::{DataType, SchemaRef};
+use datafusion::common::DFSchema;
+use datafusion::functions::expr_fn::to_char;
+use datafusion::parquet::arrow::AsyncArrowWriter;
+use datafusion::physical_plan::{PhysicalExpr, SendableRecordBatchStream};
+use datafusion::prelude::*;
+use tokio::fs::{File, OpenOptions};
+use tokio_stream::StreamExt;
+
+use super::write_options::ParquetWriteOptions;
+use crate::AppError;
+
+/// Directory name used for rows whose partition value is null.
+pub const HIVE_DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// Most part files the hive writer keeps open, each holds a file and a buffered row group.
+pub const MAX_OPEN_HIVE_FILES: usize = 64;
+
+/// Column of a hive-style dataset layout, either an existing column or an expression over one.
+#[derive(Debug, Clone)]
+pub struct PartitionColumn {
+    name: String,
+    expr: Option<Expr>,
+}
+
+impl PartitionColumn {
+    pub fn new(column: &str) -> Self {
+        Self {
+            name: column.to_string(),
+            expr: None,
+        }
+    }
+
+    /// Partitions by the value of `expr`, stored under `name`.
+    pub fn derived(name: &str, expr: Expr) -> Self {
+        Self {
+            name: name.to_string(),
+            expr: Some(expr),
+        }
+    }
+
+    /// Day of a timestamp column as `YYYY-MM-DD`.
+    pub fn day(name: &str, column: &str) -> Self {
+        Self::derived(name, to_char(col(column), lit("%Y-%m-%d")))
+    }
+
+    /// Month of a timestamp column as `YYYY-MM`.
+    pub fn month(name: &str, column: &str) -> Self {
+        Self::derived(name, to_char(col(column), lit("%Y-%m")))
+    }
+}
+
+pub async fn write_df_to_hive_dir(
+    df: DataFrame,
+    dir: &str,
+    partition_by: &[PartitionColumn],
+    max_rows_per_file: usize,
+) -> Result<Vec<String>, AppError> {
+    let options = ParquetWriteOptions::default();
+    write_df_to_hive_dir_with(df, dir, partition_by, max_rows_per_file, &options).await
+}
+
+/// Writes `df` as `dir/col=value/.../part-N.parquet`, see [`write_stream_to_hive_dir_with`].
+pub async fn write_df_to_hive_dir_with(
+    df: DataFrame,
+    dir: &str,
+    partition_by: &[PartitionColumn],
+    max_rows_per_file: usize,
+    options: &ParquetWriteOptions,
+) -> Result<Vec<String>, AppError> {
+    let stream = df.execute_stream().await?;
+    write_stream_to_hive_dir_with(stream, dir, partition_by, max_rows_per_file, options).await
+}
+
+/// Writes `stream` as `dir/col=value/.../part-N.parquet`, starting a new part file every
+/// `max_rows_per_file` rows. Partition columns are only kept in the directory names.
+///
+/// The stream is read once. At most [`MAX_OPEN_HIVE_FILES`] part files are open at a time,
+/// the least recently written one is closed first and its partition continues in a new part
+/// file, so input clustered by the partition columns gives the fewest files. The part files a
+/// previous run left in a partition directory are removed before the first new one is written.
+pub async fn write_stream_to_hive_dir_with(
+    mut stream: SendableRecordBatchStream,
+    dir: &str,
+    partition_by: &[PartitionColumn],
+    max_rows_per_file: usize,
+    options: &ParquetWriteOptions,
+) -> Result<Vec<String>, AppError> {
+    let schema = stream.schema();
+    let df_schema = DFSchema::try_from(schema.as_ref().clone())?;
+    let ctx = SessionContext::new();
+    let keys = partition_by
+        .iter()
+        .map(|column| match &column.expr {
+            Some(expr) => Ok(PartitionKey::Expr(
+                ctx.create_physical_expr(expr.clone(), &df_schema)?,
+            )),
+            None => Ok(PartitionKey::Column(schema.index_of(&column.name)?)),
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+    // a derived column replaces an existing column of the same name
+    let data_indices = (0..schema.fields().len())
+        .filter(|&i| {
+            let name = schema.field(i).name();
+            !partition_by.iter().any(|column| &column.name == name)
+        })
+        .collect::<Vec<_>>();
+    let data_schema = SchemaRef::new(schema.project(&data_indices)?);
+
+    let mut partitions = HashMap::<String, PartitionFiles>::new();
+    let mut writes = 0;
+    let res = async {
+        while let Some(batch) = stream.next().await.transpose()? {
+            let keys = keys
+                .iter()
+                .map(|key| {
+                    let values = match key {
+                        PartitionKey::Column(i) => batch.column(*i).clone(),
+                        PartitionKey::Expr(expr) => {
+                            expr.evaluate(&batch)?.into_array(batch.num_rows())?
+                        }
+                    };
+                    Ok(cast(&values, &DataType::Utf8)?)
+                })
+                .collect::<Result<Vec<_>, AppError>>()?;
+            let mut groups = HashMap::<String, Vec<u32>>::new();
+            for row in 0..batch.num_rows() {
+                let path = partition_by
+                    .iter()
+                    .zip(&keys)
+                    .map(|(column, key)| {
+                        let key = key.as_string::<i32>();
+                        let value = if key.is_null(row) {
+                            HIVE_DEFAULT_PARTITION.to_string()
+                        } else {
+                            escape_partition_value(key.value(row))
+                        };
+                        format!("{}={}", column.name, value)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/");
+                groups.entry(path).or_default().push(row as u32);
+            }
+
+            let data = batch.project(&data_indices)?;
+            for (path, rows) in groups {
+                let rows = take_record_batch(&data, &UInt32Array::from(rows))?;
+                if !partitions.get(&path).is_some_and(PartitionFiles::is_open) {
+                    close_least_recent(&mut partitions).await?;
+                }
+                let files = match partitions.entry(path) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let files =
+                            PartitionFiles::create(Path::new(dir).join(entry.key())).await?;
+                        entry.insert(files)
+                    }
+                };
+                writes += 1;
+                files.last_write = writes;
+                files
+                    .write(&rows, &data_schema, max_rows_per_file.max(1), options)
+                    .await?;
+            }
+        }
+        let mut paths = vec![];
+        for files in partitions.values_mut() {
+            files.close().await?;
+            paths.append(&mut files.paths);
+        }
+        Ok::<_, AppError>(paths)
+    }
+    .await;
+
+    match res {
+        Ok(mut paths) => {
+            paths.sort();
+            Ok(paths)
+        }
+        Err(e) => {
+            for files in partitions.values_mut() {
+                files.abort().await;
+            }
+            Err(e)
+        }
+    }
+}
+
+// closes the least recently written part file once the open ones reach the limit
+async fn close_least_recent(
+    partitions: &mut HashMap<String, PartitionFiles>,
+) -> Result<(), AppError> {
+    let mut open = partitions
+        .values_mut()
+        .filter(|files| files.is_open())
+        .collect::<Vec<_>>();
+    if open.len() < MAX_OPEN_HIVE_FILES {
+        return Ok(());
+    }
+    if let Some(files) = open.iter_mut().min_by_key(|files| files.last_write) {
+        files.close().await?;
+    }
+    Ok(())
+}
+
+// where the partition value of a row comes from
+enum PartitionKey {
+    Column(usize),
+    Expr(Arc<dyn PhysicalExpr>),
+}
+
+/// Part files of one partition directory, written to a temporary path until complete.
+struct PartitionFiles {
+    dir: PathBuf,
+    paths: Vec<String>,
+    current: Option<PartFile>,
+    // position of the latest write among all partitions
+    last_write: usize,
+}
+
+struct PartFile {
+    writer: AsyncArrowWriter<File>,
+    path: String,
+    tmp_path: String,
+    rows: usize,
+}
+
+impl PartitionFiles {
+    /// Creates `dir` and removes the part files an earlier run left in it.
+    async fn create(dir: PathBuf) -> Result<Self, AppError> {
+        tokio::fs::create_dir_all(&dir).await?;
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_name().to_string_lossy().starts_with("part-")
+                && entry.file_type().await?.is_file()
+            {
+                tokio::fs::remove_file(entry.path()).await?;
+            }
+        }
+        Ok(Self {
+            dir,
+            paths: vec![],
+            current: None,
+            last_write: 0,
+        })
+    }
+
+    fn is_open(&self) -> bool {
+        self.current.is_some()
+    }
+
+    async fn write(
+        &mut self,
+        batch: &RecordBatch,
+        schema: &SchemaRef,
+        max_rows_per_file: usize,
+        options: &ParquetWriteOptions,
+    ) -> Result<(), AppError> {
+        let mut offset = 0;
+        while offset < batch.num_rows() {
+            let file = match &mut self.current {
+                Some(file) => file,
+                None => {
+                    let file_name = format!("part-{:05}.parquet", self.paths.len());
+                    let path = self.dir.join(file_name).to_string_lossy().into_owned();
+                    let tmp_path = format!("{}.tmp", path);
+                    let props = options.writer_properties()?;
+                    let file = File::create(&tmp_path).await?;
+                    let writer = AsyncArrowWriter::try_new(file, schema.clone(), Some(props))?;
+                    self.current.insert(PartFile {
+                        writer,
+                        path,
+                        tmp_path,
+                        rows: 0,
+                    })
+                }
+            };
+            let len = (max_rows_per_file - file.rows).min(batch.num_rows() - offset);
+            file.writer.write(&batch.slice(offset, len)).await?;
+            file.rows += len;
+            offset += len;
+            if file.rows == max_rows_per_file {
+                self.close().await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<(), AppError> {
+        if let Some(file) = self.current.take() {
+            file.writer.close().await?;
+            // fsync applies to the file, so a new handle flushes what the writer wrote
+            OpenOptions::new()
+                .write(true)
+                .open(&file.tmp_path)
+                .await?
+                .sync_all()
+                .await?;
+            tokio::fs::rename(&file.tmp_path, &file.path).await?;
+            self.paths.push(file.path);
+        }
+        Ok(())
+    }
+
+    async fn abort(&mut self) {
+        if let Some(file) = self.current.take() {
+            drop(file.writer);
+            let _ = tokio::fs::remove_file(&file.tmp_path).await;
+        }
+    }
+}
+
+/// Percent-encodes the characters hive escapes in partition directory names.
+pub(crate) fn escape_partition_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\u{00}'..='\u{1F}'
+            | '"'
+            | '#'
+            | '%'
+            | '\''
+            | '*'
+            | '/'
+            | ':'
+            | '='
+            | '?'
+            | '\\'
+            | '\u{7F}'
+            | '{'
+            | '['
+            | ']'
+            | '^' => escaped.push_str(&format!("%{:02X}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::{Int32Array, StringArray, TimestampMicrosecondArray};
+    use datafusion::arrow::datatypes::{Field, Schema, TimeUnit};
+    use rstest::rstest;
+
+    use super::*;
+    use crate::read_file_to_df;
+
+    #[rstest]
+    #[case("DME", "DME")]
+    #[case("2017-08-15T15:00:00Z", "2017-08-15T15%3A00%3A00Z")]
+    #[case("a/b=c", "a%2Fb%3Dc")]
+    #[case("Москва", "Москва")]
+    fn escape_partition_value_test(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(expected, escape_partition_value(input));
+    }
+
+    #[tokio::test]
+    async fn write_df_to_hive_dir_test() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("flight_id", DataType::Int32, false),
+            Field::new("departure_airport", DataType::Utf8, true),
+            Field::new(
+                "scheduled_departure",
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                false,
+            ),
+        ]));
+        // 2017-08-15, 2017-08-16 and 2017-09-01 in microseconds
+        let days = [
+            1_502_755_200_000_000,
+            1_502_841_600_000_000,
+            1_504_224_000_000_000,
+        ];
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3, 4, 5])),
+                Arc::new(StringArray::from(vec![
+                    Some("DME"),
+                    Some("DME"),
+                    Some("DME"),
+                    Some("LED"),
+                    None,
+                ])),
+                Arc::new(
+                    TimestampMicrosecondArray::from(vec![
+                        days[0], days[1], days[0], days[2], days[2],
+                    ])
+                    .with_timezone("UTC"),
+                ),
+            ],
+        )
+        .unwrap();
+        let df = SessionContext::new().read_batch(batch).unwrap();
+        let dir = std::env::temp_dir().join(format!("hive_{}", std::process::id()));
+        let partition_by = [
+            PartitionColumn::new("departure_airport"),
+            PartitionColumn::month("month", "scheduled_departure"),
+        ];
+        let paths = write_df_to_hive_dir(df, dir.to_str().unwrap(), &partition_by, 2)
+            .await
+            .unwrap();
+
+        let relative = paths
+            .iter()
+            .map(|path| {
+                Path::new(path)
+                    .strip_prefix(&dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>();
+        let first = read_file_to_df(&paths[0]).await.unwrap();
+        let fields = first
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>();
+        let rows = first.count().await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            relative,
+            vec![
+                "departure_airport=DME/month=2017-08/part-00000.parquet",
+                "departure_airport=DME/month=2017-08/part-00001.parquet",
+                "departure_airport=LED/month=2017-09/part-00000.parquet",
+                "departure_airport=__HIVE_DEFAULT_PARTITION__/month=2017-09/part-00000.parquet",
+            ]
+        );
+        assert_eq!(fields, vec!["flight_id", "scheduled_departure"]);
+        assert_eq!(rows, 2);
+    }
+
+    #[tokio::test]
+    async fn write_stream_to_hive_dir_many_partitions_test() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("flight_id", DataType::Int32, false),
+            Field::new("key", DataType::Int32, false),
+        ]));
+        let partitions = MAX_OPEN_HIVE_FILES as i32 + 36;
+        let batch = |ids: std::ops::Range<i32>| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(ids.clone())),
+                    Arc::new(Int32Array::from_iter_values(ids.map(|id| id % partitions))),
+                ],
+            )
+            .unwrap()
+        };
+        let dir = std::env::temp_dir().join(format!("hive_many_{}", std::process::id()));
+        let partition_by = [PartitionColumn::new("key")];
+        let write = |batches: Vec<RecordBatch>| {
+            let df = SessionContext::new().read_batches(batches).unwrap();
+            write_df_to_hive_dir(df, dir.to_str().unwrap(), &partition_by, 10)
+        };
+
+        // every batch has rows of every partition, so closed files are continued in new ones
+        let paths = write(vec![
+            batch(0..partitions),
+            batch(partitions..2 * partitions),
+        ])
+        .await
+        .unwrap();
+        let mut rows = 0;
+        for path in &paths {
+            rows += read_file_to_df(path).await.unwrap().count().await.unwrap();
+        }
+        assert!(paths.len() > partitions as usize);
+        assert_eq!(rows, 2 * partitions as usize);
+
+        // a rerun replaces the part files of the partitions it writes
+        let paths = write(vec![batch(0..partitions)]).await.unwrap();
+        let mut files = vec![];
+        for partition in std::fs::read_dir(&dir).unwrap() {
+            for file in std::fs::read_dir(partition.unwrap().path()).unwrap() {
+                files.push(file.unwrap().path().to_string_lossy().into_owned());
+            }
+        }
+        files.sort();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(paths.len(), partitions as usize);
+        assert_eq!(files, paths);
+    }
+}
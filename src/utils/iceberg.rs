@@ -0,0 +1,1354 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{Array, ArrayRef, AsArray, RecordBatch, UInt32Array};
+use datafusion::arrow::compute::{cast, date_part, take_record_batch, DatePart};
+use datafusion::arrow::datatypes::{
+    DataType, Date32Type, Field, Fields, Int32Type, Int64Type, Schema, SchemaRef, TimeUnit,
+    TimestampMicrosecondType,
+};
+use datafusion::datasource::file_format::parquet::ParquetFormat;
+use datafusion::datasource::listing::{
+    ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+};
+use datafusion::parquet::arrow::AsyncArrowWriter;
+use datafusion::prelude::*;
+use object_store::buffered::BufWriter;
+use object_store::path::Path;
+use object_store::{PutMode, PutOptions, PutPayload};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::types::chrono::{NaiveDate, Utc};
+use thiserror::Error;
+use tokio_stream::StreamExt;
+use url::Url;
+use uuid::Uuid;
+
+use apache_avro::types::Value as AvroValue;
+
+use super::avro::{read_container, write_container, AvroError};
+use super::constants::TIMESTAMP_TZ;
+use super::delta::SaveMode;
+use super::hive::escape_partition_value;
+use super::store::{LakeStore, LakeStoreError};
+use super::write_options::ParquetWriteOptions;
+use crate::AppError;
+
+const FORMAT_VERSION: i32 = 2;
+const PARTITION_FIELD_ID_START: i32 = 1000;
+const PARQUET_FIELD_ID: &str = "PARQUET:field_id";
+const DAY_MICROS: i64 = 86_400_000_000;
+const UNIX_EPOCH_DAYS_FROM_CE: i32 = 719_163;
+// manifest entry statuses
+const STATUS_ADDED: i32 = 1;
+const STATUS_DELETED: i32 = 2;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum IcebergError {
+    #[error("Table not found: {0}")]
+    TableNotFound(String),
+
+    #[error("Table already exists: {0}")]
+    TableExists(String),
+
+    #[error("Snapshot not found: {0}")]
+    SnapshotNotFound(i64),
+
+    #[error("Metadata version {0} was already committed")]
+    CommitConflict(i64),
+
+    #[error("Schema does not match the table schema")]
+    SchemaMismatch,
+
+    #[error("Unsupported iceberg type {1} of column {0}")]
+    UnsupportedType(String, String),
+
+    #[error("Invalid partition field: {0}")]
+    InvalidPartitionField(String),
+
+    #[error("Invalid table metadata: {0}")]
+    InvalidMetadata(String),
+}
+
+/// Partition transforms supported by the writer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IcebergTransform {
+    Identity,
+    Year,
+    Month,
+    Day,
+}
+
+impl fmt::Display for IcebergTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Identity => "identity",
+            Self::Year => "year",
+            Self::Month => "month",
+            Self::Day => "day",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for IcebergTransform {
+    type Err = IcebergError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "identity" => Ok(Self::Identity),
+            "year" => Ok(Self::Year),
+            "month" => Ok(Self::Month),
+            "day" => Ok(Self::Day),
+            _ => Err(IcebergError::InvalidPartitionField(s.to_string())),
+        }
+    }
+}
+
+/// Field of a partition spec, parsed from `book_date` or `day(book_date)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IcebergPartitionField {
+    name: String,
+    column: String,
+    transform: IcebergTransform,
+}
+
+impl IcebergPartitionField {
+    /// Partition field named `<column>_<transform>`, or `column` for identity.
+    pub fn new(column: &str, transform: IcebergTransform) -> Self {
+        let name = match transform {
+            IcebergTransform::Identity => column.to_string(),
+            transform => format!("{}_{}", column, transform),
+        };
+        Self {
+            name,
+            column: column.to_string(),
+            transform,
+        }
+    }
+
+    pub fn with_name(self, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..self
+        }
+    }
+}
+
+impl FromStr for IcebergPartitionField {
+    type Err = IcebergError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || IcebergError::InvalidPartitionField(s.to_string());
+        let (transform, column) = match s.trim().split_once('(') {
+            Some((transform, column)) => {
+                let column = column.strip_suffix(')').ok_or_else(invalid)?;
+                let transform = transform.trim().parse().map_err(|_| invalid())?;
+                (transform, column.trim())
+            }
+            None => (IcebergTransform::Identity, s.trim()),
+        };
+        if column.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self::new(column, transform))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PartitionSpec {
+    spec_id: i32,
+    fields: Vec<PartitionSpecField>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PartitionSpecField {
+    name: String,
+    transform: String,
+    source_id: i32,
+    field_id: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Snapshot {
+    snapshot_id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent_snapshot_id: Option<i64>,
+    sequence_number: i64,
+    timestamp_ms: i64,
+    manifest_list: String,
+    summary: BTreeMap<String, String>,
+    schema_id: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TableMetadata {
+    format_version: i32,
+    table_uuid: String,
+    location: String,
+    last_sequence_number: i64,
+    last_updated_ms: i64,
+    last_column_id: i32,
+    current_schema_id: i32,
+    schemas: Vec<Value>,
+    default_spec_id: i32,
+    partition_specs: Vec<PartitionSpec>,
+    last_partition_id: i32,
+    default_sort_order_id: i32,
+    sort_orders: Vec<Value>,
+    #[serde(default)]
+    properties: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    current_snapshot_id: Option<i64>,
+    #[serde(default)]
+    refs: BTreeMap<String, Value>,
+    #[serde(default)]
+    snapshots: Vec<Snapshot>,
+    #[serde(default)]
+    snapshot_log: Vec<Value>,
+    #[serde(default)]
+    metadata_log: Vec<Value>,
+}
+
+impl TableMetadata {
+    fn schema(&self) -> Result<&Value, IcebergError> {
+        let schema_id = Some(self.current_schema_id as i64);
+        self.schemas
+            .iter()
+            .find(|schema| schema["schema-id"].as_i64() == schema_id)
+            .ok_or_else(|| IcebergError::InvalidMetadata("missing current schema".to_string()))
+    }
+
+    fn spec(&self) -> Result<&PartitionSpec, IcebergError> {
+        self.partition_specs
+            .iter()
+            .find(|spec| spec.spec_id == self.default_spec_id)
+            .ok_or_else(|| IcebergError::InvalidMetadata("missing default spec".to_string()))
+    }
+
+    fn snapshot(&self, snapshot_id: i64) -> Result<&Snapshot, IcebergError> {
+        self.snapshots
+            .iter()
+            .find(|snapshot| snapshot.snapshot_id == snapshot_id)
+            .ok_or(IcebergError::SnapshotNotFound(snapshot_id))
+    }
+}
+
+fn metadata_location(path: &str, version: i64) -> String {
+    format!("{}/metadata/v{}.metadata.json", path, version)
+}
+
+/// File-based Iceberg catalog: table `<namespace>.<name>` lives in `<namespace>/<name>` of the
+/// warehouse store, its current metadata is the highest `metadata/v<N>.metadata.json`, which
+/// `version-hint.text` points other readers to.
+///
+/// Like [`DeltaTable`](crate::DeltaTable), commits rely on the store refusing to overwrite an
+/// existing metadata file.
+#[derive(Debug, Clone)]
+pub struct IcebergCatalog {
+    store: LakeStore,
+}
+
+impl IcebergCatalog {
+    pub fn new(store: LakeStore) -> Self {
+        Self { store }
+    }
+
+    /// Creates an empty table with `schema`, e.g. `Bookings::schema()`, partitioned by
+    /// `partition_by`.
+    pub async fn create_table(
+        &self,
+        namespace: &str,
+        name: &str,
+        schema: &Schema,
+        partition_by: &[IcebergPartitionField],
+    ) -> Result<IcebergTable, AppError> {
+        let path = format!("{}/{}", namespace, name);
+        let (iceberg_schema, last_column_id) = schema_to_iceberg(schema)?;
+        let fields = partition_by
+            .iter()
+            .zip(PARTITION_FIELD_ID_START..)
+            .map(|(field, field_id)| {
+                let invalid = || IcebergError::InvalidPartitionField(field.name.clone());
+                let (index, source) = schema.column_with_name(&field.column).ok_or_else(invalid)?;
+                partition_type(field.transform, source.data_type()).ok_or_else(invalid)?;
+                let source_id = iceberg_schema["fields"][index]["id"].as_i64();
+                Ok(PartitionSpecField {
+                    name: field.name.clone(),
+                    transform: field.transform.to_string(),
+                    source_id: source_id.ok_or_else(invalid)? as i32,
+                    field_id,
+                })
+            })
+            .collect::<Result<Vec<_>, IcebergError>>()?;
+
+        let metadata = TableMetadata {
+            format_version: FORMAT_VERSION,
+            table_uuid: Uuid::new_v4().to_string(),
+            location: self.store.url(&path),
+            last_sequence_number: 0,
+            last_updated_ms: Utc::now().timestamp_millis(),
+            last_column_id,
+            current_schema_id: 0,
+            schemas: vec![iceberg_schema],
+            default_spec_id: 0,
+            last_partition_id: PARTITION_FIELD_ID_START + fields.len() as i32 - 1,
+            partition_specs: vec![PartitionSpec { spec_id: 0, fields }],
+            default_sort_order_id: 0,
+            sort_orders: vec![json!({"order-id": 0, "fields": []})],
+            properties: BTreeMap::new(),
+            current_snapshot_id: None,
+            refs: BTreeMap::new(),
+            snapshots: vec![],
+            snapshot_log: vec![],
+            metadata_log: vec![],
+        };
+        let mut table = IcebergTable {
+            store: self.store.clone(),
+            path,
+            version: 0,
+            metadata: metadata.clone(),
+        };
+        match table.commit(metadata).await {
+            Ok(()) => Ok(table),
+            Err(AppError::IcebergError(IcebergError::CommitConflict(_))) => {
+                Err(IcebergError::TableExists(format!("{}.{}", namespace, name)).into())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn load_table(&self, namespace: &str, name: &str) -> Result<IcebergTable, AppError> {
+        let path = format!("{}/{}", namespace, name);
+        let prefix = self.store.path(&format!("{}/metadata", path));
+        let objects = match self.store.store().list_with_delimiter(Some(&prefix)).await {
+            Ok(list) => list.objects,
+            Err(object_store::Error::NotFound { .. }) => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        let version = objects
+            .iter()
+            .filter_map(|meta| {
+                let name = meta.location.filename()?.strip_prefix('v')?;
+                name.strip_suffix(".metadata.json")?.parse::<i64>().ok()
+            })
+            .max()
+            .ok_or_else(|| IcebergError::TableNotFound(format!("{}.{}", namespace, name)))?;
+
+        let location = self.store.path(&metadata_location(&path, version));
+        let buf = self.store.store().get(&location).await?.bytes().await?;
+        Ok(IcebergTable {
+            store: self.store.clone(),
+            path,
+            version,
+            metadata: serde_json::from_slice(&buf)?,
+        })
+    }
+}
+
+/// Iceberg table of an [`IcebergCatalog`], as of its last loaded or committed metadata.
+#[derive(Debug, Clone)]
+pub struct IcebergTable {
+    store: LakeStore,
+    path: String,
+    version: i64,
+    metadata: TableMetadata,
+}
+
+impl IcebergTable {
+    pub fn schema(&self) -> Result<SchemaRef, AppError> {
+        let fields = iceberg_to_fields(self.metadata.schema()?, false)?;
+        Ok(Arc::new(Schema::new(fields)))
+    }
+
+    pub fn current_snapshot_id(&self) -> Option<i64> {
+        self.metadata.current_snapshot_id
+    }
+
+    /// Snapshot ids, oldest first.
+    pub fn snapshot_ids(&self) -> Vec<i64> {
+        self.metadata
+            .snapshots
+            .iter()
+            .map(|snapshot| snapshot.snapshot_id)
+            .collect()
+    }
+
+    /// Writes `df` as one data file per partition and commits them as a new snapshot, whose
+    /// id is returned. The dataframe must have the table schema, an overwrite leaves the files
+    /// of earlier snapshots out of the new one.
+    pub async fn write_df(
+        &mut self,
+        df: DataFrame,
+        mode: SaveMode,
+        options: &ParquetWriteOptions,
+    ) -> Result<i64, AppError> {
+        let table_schema = self.metadata.schema()?.clone();
+        if schema_to_iceberg(df.schema().as_arrow())?.0["fields"] != table_schema["fields"] {
+            return Err(IcebergError::SchemaMismatch.into());
+        }
+        let write_schema = Arc::new(Schema::new(iceberg_to_fields(&table_schema, true)?));
+        let partition_by = resolve_partition_spec(&table_schema, self.metadata.spec()?)?;
+        let snapshot_id = (Uuid::new_v4().as_u64_pair().0 >> 1) as i64;
+
+        let mut stream = df.execute_stream().await?;
+        let mut files = HashMap::<Vec<PartitionValue>, DataFile>::new();
+        let res = async {
+            while let Some(batch) = stream.next().await.transpose()? {
+                let columns = batch
+                    .columns()
+                    .iter()
+                    .zip(write_schema.fields())
+                    .map(|(column, field)| cast(column, field.data_type()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let batch = RecordBatch::try_new(write_schema.clone(), columns)?;
+                let keys = partition_by
+                    .iter()
+                    .map(|field| partition_values(batch.column(field.index), field.transform))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut groups = HashMap::<Vec<PartitionValue>, Vec<u32>>::new();
+                for row in 0..batch.num_rows() {
+                    let key = keys.iter().map(|values| values[row].clone()).collect();
+                    groups.entry(key).or_default().push(row as u32);
+                }
+
+                for (key, rows) in groups {
+                    let rows = take_record_batch(&batch, &UInt32Array::from(rows))?;
+                    if !files.contains_key(&key) {
+                        let location = format!(
+                            "{}/data/{}{}.parquet",
+                            self.path,
+                            partition_dir(&partition_by, &key),
+                            Uuid::new_v4()
+                        );
+                        let file =
+                            DataFile::try_new(&self.store, location, &write_schema, options)?;
+                        files.insert(key.clone(), file);
+                    }
+                    let file = files.get_mut(&key).expect("file was just inserted");
+                    file.writer.write(&rows).await?;
+                    file.rows += rows.num_rows() as i64;
+                }
+            }
+
+            let mut entries = vec![];
+            for (key, file) in files.drain() {
+                file.writer.close().await?;
+                let meta = self
+                    .store
+                    .store()
+                    .head(&self.store.path(&file.location))
+                    .await?;
+                let partition = partition_by
+                    .iter()
+                    .zip(key)
+                    .map(|(field, value)| (field.name.clone(), value.into()))
+                    .collect();
+                let data_file = AvroValue::Record(vec![
+                    ("content".to_string(), AvroValue::Int(0)),
+                    (
+                        "file_path".to_string(),
+                        AvroValue::String(self.store.url(&file.location)),
+                    ),
+                    (
+                        "file_format".to_string(),
+                        AvroValue::String("PARQUET".to_string()),
+                    ),
+                    ("partition".to_string(), AvroValue::Record(partition)),
+                    ("record_count".to_string(), AvroValue::Long(file.rows)),
+                    (
+                        "file_size_in_bytes".to_string(),
+                        AvroValue::Long(meta.size as i64),
+                    ),
+                ]);
+                entries.push(AvroValue::Record(vec![
+                    ("status".to_string(), AvroValue::Int(STATUS_ADDED)),
+                    ("snapshot_id".to_string(), AvroValue::Long(snapshot_id)),
+                    ("data_file".to_string(), data_file),
+                ]));
+            }
+            Ok::<_, AppError>(entries)
+        }
+        .await;
+
+        let entries = match res {
+            Ok(entries) => entries,
+            Err(e) => {
+                // the uploads of unfinished files are dropped with their writers
+                files.clear();
+                return Err(e);
+            }
+        };
+        self.commit_snapshot(snapshot_id, entries, mode, &partition_by)
+            .await?;
+        Ok(snapshot_id)
+    }
+
+    async fn commit_snapshot(
+        &mut self,
+        snapshot_id: i64,
+        entries: Vec<AvroValue>,
+        mode: SaveMode,
+        partition_by: &[PartitionBy],
+    ) -> Result<(), AppError> {
+        let now = Utc::now().timestamp_millis();
+        let sequence_number = self.metadata.last_sequence_number + 1;
+        let parent = self.metadata.current_snapshot_id;
+        let spec = self.metadata.spec()?;
+        let added_files = entries.len() as i32;
+        let added_rows = entries
+            .iter()
+            .map(|entry| Ok(from_avro::<ManifestEntry>(entry)?.data_file.record_count))
+            .sum::<Result<i64, AppError>>()?;
+
+        let manifest_location = format!("{}/metadata/{}-m0.avro", self.path, Uuid::new_v4());
+        let manifest = write_container(
+            &manifest_schema(partition_by),
+            &[
+                ("schema", self.metadata.schema()?.to_string()),
+                ("schema-id", self.metadata.current_schema_id.to_string()),
+                ("partition-spec", serde_json::to_string(&spec.fields)?),
+                ("partition-spec-id", spec.spec_id.to_string()),
+                ("format-version", FORMAT_VERSION.to_string()),
+                ("content", "data".to_string()),
+            ],
+            &entries,
+        )?;
+        let manifest_length = manifest.len() as i64;
+        self.put(&manifest_location, manifest).await?;
+
+        let mut manifests = match (mode, parent) {
+            (SaveMode::Append, Some(parent)) => self.manifests(parent).await?,
+            _ => vec![],
+        };
+        manifests.push(AvroValue::Record(vec![
+            (
+                "manifest_path".to_string(),
+                AvroValue::String(self.store.url(&manifest_location)),
+            ),
+            (
+                "manifest_length".to_string(),
+                AvroValue::Long(manifest_length),
+            ),
+            (
+                "partition_spec_id".to_string(),
+                AvroValue::Int(spec.spec_id),
+            ),
+            ("content".to_string(), AvroValue::Int(0)),
+            (
+                "sequence_number".to_string(),
+                AvroValue::Long(sequence_number),
+            ),
+            (
+                "min_sequence_number".to_string(),
+                AvroValue::Long(sequence_number),
+            ),
+            (
+                "added_snapshot_id".to_string(),
+                AvroValue::Long(snapshot_id),
+            ),
+            ("added_files_count".to_string(), AvroValue::Int(added_files)),
+            ("existing_files_count".to_string(), AvroValue::Int(0)),
+            ("deleted_files_count".to_string(), AvroValue::Int(0)),
+            ("added_rows_count".to_string(), AvroValue::Long(added_rows)),
+            ("existing_rows_count".to_string(), AvroValue::Long(0)),
+            ("deleted_rows_count".to_string(), AvroValue::Long(0)),
+        ]));
+        let list_location = format!(
+            "{}/metadata/snap-{}-1-{}.avro",
+            self.path,
+            snapshot_id,
+            Uuid::new_v4()
+        );
+        let parent_id = parent.map_or("null".to_string(), |id| id.to_string());
+        let list = write_container(
+            &manifest_list_schema(),
+            &[
+                ("snapshot-id", snapshot_id.to_string()),
+                ("parent-snapshot-id", parent_id),
+                ("sequence-number", sequence_number.to_string()),
+                ("format-version", FORMAT_VERSION.to_string()),
+            ],
+            &manifests,
+        )?;
+        self.put(&list_location, list).await?;
+
+        let operation = match mode {
+            SaveMode::Append => "append",
+            SaveMode::Overwrite => "overwrite",
+        };
+        let mut metadata = self.metadata.clone();
+        metadata.last_sequence_number = sequence_number;
+        metadata.last_updated_ms = now;
+        metadata.current_snapshot_id = Some(snapshot_id);
+        metadata.snapshots.push(Snapshot {
+            snapshot_id,
+            parent_snapshot_id: parent,
+            sequence_number,
+            timestamp_ms: now,
+            manifest_list: self.store.url(&list_location),
+            summary: BTreeMap::from([
+                ("operation".to_string(), operation.to_string()),
+                ("added-data-files".to_string(), added_files.to_string()),
+                ("added-records".to_string(), added_rows.to_string()),
+            ]),
+            schema_id: metadata.current_schema_id,
+        });
+        metadata.refs.insert(
+            "main".to_string(),
+            json!({"snapshot-id": snapshot_id, "type": "branch"}),
+        );
+        metadata
+            .snapshot_log
+            .push(json!({"timestamp-ms": now, "snapshot-id": snapshot_id}));
+        metadata.metadata_log.push(json!({
+            "timestamp-ms": self.metadata.last_updated_ms,
+            "metadata-file": self.store.url(&metadata_location(&self.path, self.version)),
+        }));
+
+        self.version += 1;
+        if let Err(e) = self.commit(metadata).await {
+            self.version -= 1;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // writes the metadata of `self.version`, which only succeeds if the file is missing, so of
+    // two writers racing for a version one fails
+    async fn commit(&mut self, metadata: TableMetadata) -> Result<(), AppError> {
+        let location = self
+            .store
+            .path(&metadata_location(&self.path, self.version));
+        let buf = serde_json::to_vec_pretty(&metadata)?;
+        let opts = PutOptions::from(PutMode::Create);
+        match self
+            .store
+            .store()
+            .put_opts(&location, PutPayload::from(buf), opts)
+            .await
+        {
+            Ok(_) => {}
+            Err(object_store::Error::AlreadyExists { .. }) => {
+                return Err(IcebergError::CommitConflict(self.version).into())
+            }
+            Err(e) => return Err(e.into()),
+        }
+        self.metadata = metadata;
+        let hint = format!("{}/metadata/version-hint.text", self.path);
+        self.put(&hint, self.version.to_string().into_bytes()).await
+    }
+
+    async fn put(&self, location: &str, buf: Vec<u8>) -> Result<(), AppError> {
+        let location = self.store.path(location);
+        self.store
+            .store()
+            .put(&location, PutPayload::from(buf))
+            .await?;
+        Ok(())
+    }
+
+    // metadata files reference each other by full url
+    async fn get(&self, url: &str) -> Result<Vec<u8>, AppError> {
+        let url = Url::parse(url).map_err(|_| LakeStoreError::InvalidUrl(url.to_string()))?;
+        let location = Path::from_url_path(url.path())?;
+        let buf = self.store.store().get(&location).await?.bytes().await?;
+        Ok(buf.to_vec())
+    }
+
+    async fn manifests(&self, snapshot_id: i64) -> Result<Vec<AvroValue>, AppError> {
+        let snapshot = self.metadata.snapshot(snapshot_id)?;
+        Ok(read_container(&self.get(&snapshot.manifest_list).await?)?)
+    }
+
+    /// Data file urls of a snapshot, the current one by default.
+    pub async fn data_files(&self, snapshot_id: Option<i64>) -> Result<Vec<String>, AppError> {
+        let Some(snapshot_id) = snapshot_id.or(self.metadata.current_snapshot_id) else {
+            return Ok(vec![]);
+        };
+        let mut files = vec![];
+        for manifest in self.manifests(snapshot_id).await? {
+            let manifest = from_avro::<ManifestFile>(&manifest)?;
+            for entry in read_container(&self.get(&manifest.manifest_path).await?)? {
+                let entry = from_avro::<ManifestEntry>(&entry)?;
+                if entry.status != STATUS_DELETED {
+                    files.push(entry.data_file.file_path);
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Returns a dataframe of the table at a snapshot, the current one by default, registering
+    /// the store with `ctx`.
+    pub async fn read_to_df(
+        &self,
+        ctx: &SessionContext,
+        snapshot_id: Option<i64>,
+    ) -> Result<DataFrame, AppError> {
+        let schema = self.schema()?;
+        let files = self.data_files(snapshot_id).await?;
+        if files.is_empty() {
+            return Ok(ctx.read_batch(RecordBatch::new_empty(schema))?);
+        }
+
+        self.store.register(ctx);
+        let urls = files
+            .iter()
+            .map(ListingTableUrl::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        let options = ListingOptions::new(Arc::new(ParquetFormat::default()));
+        let config = ListingTableConfig::new_with_multi_paths(urls)
+            .with_listing_options(options)
+            .with_schema(schema);
+        let df = ctx.read_table(Arc::new(ListingTable::try_new(config)?))?;
+
+        Ok(df)
+    }
+}
+
+struct DataFile {
+    writer: AsyncArrowWriter<BufWriter>,
+    location: String,
+    rows: i64,
+}
+
+impl DataFile {
+    fn try_new(
+        store: &LakeStore,
+        location: String,
+        schema: &SchemaRef,
+        options: &ParquetWriteOptions,
+    ) -> Result<Self, AppError> {
+        let writer = BufWriter::new(store.store(), store.path(&location));
+        let props = options.writer_properties()?;
+        let writer = AsyncArrowWriter::try_new(writer, schema.clone(), Some(props))?;
+        Ok(Self {
+            writer,
+            location,
+            rows: 0,
+        })
+    }
+}
+
+/// Partition field of the table spec, resolved against the table schema.
+struct PartitionBy {
+    name: String,
+    field_id: i32,
+    index: usize,
+    transform: IcebergTransform,
+    result_type: &'static str,
+}
+
+fn resolve_partition_spec(
+    table_schema: &Value,
+    spec: &PartitionSpec,
+) -> Result<Vec<PartitionBy>, IcebergError> {
+    let fields = iceberg_to_fields(table_schema, false)?;
+    spec.fields
+        .iter()
+        .map(|field| {
+            let invalid = || IcebergError::InvalidPartitionField(field.name.clone());
+            let index = table_schema["fields"]
+                .as_array()
+                .and_then(|columns| {
+                    columns
+                        .iter()
+                        .position(|column| column["id"] == json!(field.source_id))
+                })
+                .ok_or_else(invalid)?;
+            let transform = field.transform.parse::<IcebergTransform>()?;
+            let result_type =
+                partition_type(transform, fields[index].data_type()).ok_or_else(invalid)?;
+            Ok(PartitionBy {
+                name: field.name.clone(),
+                field_id: field.field_id,
+                index,
+                transform,
+                result_type,
+            })
+        })
+        .collect()
+}
+
+// the fields of manifest list and manifest entries that are read back
+#[derive(Deserialize)]
+struct ManifestFile {
+    manifest_path: String,
+}
+
+#[derive(Deserialize)]
+struct ManifestEntry {
+    status: i32,
+    data_file: ManifestDataFile,
+}
+
+#[derive(Deserialize)]
+struct ManifestDataFile {
+    file_path: String,
+    record_count: i64,
+}
+
+fn from_avro<T: serde::de::DeserializeOwned>(value: &AvroValue) -> Result<T, AvroError> {
+    Ok(apache_avro::from_value(value)?)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum PartitionValue {
+    Null,
+    Int(i32),
+    Long(i64),
+    String(String),
+}
+
+impl From<PartitionValue> for AvroValue {
+    fn from(value: PartitionValue) -> Self {
+        match value {
+            PartitionValue::Null => AvroValue::Null,
+            PartitionValue::Int(value) => AvroValue::Int(value),
+            PartitionValue::Long(value) => AvroValue::Long(value),
+            PartitionValue::String(value) => AvroValue::String(value),
+        }
+    }
+}
+
+// iceberg type of the partition values, None if the transform does not apply to the column
+fn partition_type(transform: IcebergTransform, data_type: &DataType) -> Option<&'static str> {
+    match (transform, data_type) {
+        (IcebergTransform::Identity, DataType::Utf8) => Some("string"),
+        (IcebergTransform::Identity, DataType::Int16 | DataType::Int32) => Some("int"),
+        (IcebergTransform::Identity, DataType::Int64) => Some("long"),
+        (IcebergTransform::Identity, DataType::Date32) => Some("date"),
+        (_, DataType::Date32 | DataType::Timestamp(TimeUnit::Microsecond, _)) => Some("int"),
+        _ => None,
+    }
+}
+
+fn partition_values(
+    column: &ArrayRef,
+    transform: IcebergTransform,
+) -> Result<Vec<PartitionValue>, AppError> {
+    let values = match (transform, column.data_type()) {
+        (IcebergTransform::Identity, DataType::Utf8) => column
+            .as_string::<i32>()
+            .iter()
+            .map(|value| value.map(|value| PartitionValue::String(value.to_string())))
+            .collect::<Vec<_>>(),
+        (IcebergTransform::Identity, DataType::Int32) => column
+            .as_primitive::<Int32Type>()
+            .iter()
+            .map(|value| value.map(PartitionValue::Int))
+            .collect(),
+        (IcebergTransform::Identity, DataType::Int64) => column
+            .as_primitive::<Int64Type>()
+            .iter()
+            .map(|value| value.map(PartitionValue::Long))
+            .collect(),
+        (IcebergTransform::Identity | IcebergTransform::Day, DataType::Date32) => column
+            .as_primitive::<Date32Type>()
+            .iter()
+            .map(|value| value.map(PartitionValue::Int))
+            .collect(),
+        (IcebergTransform::Day, DataType::Timestamp(TimeUnit::Microsecond, _)) => column
+            .as_primitive::<TimestampMicrosecondType>()
+            .iter()
+            .map(|value| {
+                value.map(|micros| PartitionValue::Int(micros.div_euclid(DAY_MICROS) as i32))
+            })
+            .collect(),
+        // years or months since 1970
+        (
+            IcebergTransform::Year | IcebergTransform::Month,
+            DataType::Date32 | DataType::Timestamp(TimeUnit::Microsecond, _),
+        ) => {
+            let years = date_part(column, DatePart::Year)?;
+            let months = date_part(column, DatePart::Month)?;
+            years
+                .as_primitive::<Int32Type>()
+                .iter()
+                .zip(months.as_primitive::<Int32Type>().iter())
+                .map(|(year, month)| {
+                    let years = year? - 1970;
+                    let value = match transform {
+                        IcebergTransform::Month => years * 12 + month? - 1,
+                        _ => years,
+                    };
+                    Some(PartitionValue::Int(value))
+                })
+                .collect()
+        }
+        (transform, data_type) => {
+            let field = format!("{}({})", transform, data_type);
+            return Err(IcebergError::InvalidPartitionField(field).into());
+        }
+    };
+    Ok(values
+        .into_iter()
+        .map(|value| value.unwrap_or(PartitionValue::Null))
+        .collect())
+}
+
+fn epoch_day_to_string(days: i32) -> String {
+    NaiveDate::from_num_days_from_ce_opt(days + UNIX_EPOCH_DAYS_FROM_CE)
+        .map_or_else(|| days.to_string(), |date| date.to_string())
+}
+
+/// Directory of a partition under `data/`, e.g. `book_date_day=2017-08-15/`.
+fn partition_dir(partition_by: &[PartitionBy], key: &[PartitionValue]) -> String {
+    partition_by
+        .iter()
+        .zip(key)
+        .map(|(field, value)| {
+            let value = match (value, field.transform) {
+                (PartitionValue::Null, _) => "null".to_string(),
+                (PartitionValue::Int(days), IcebergTransform::Day) => epoch_day_to_string(*days),
+                (PartitionValue::Int(days), _) if field.result_type == "date" => {
+                    epoch_day_to_string(*days)
+                }
+                (PartitionValue::Int(months), IcebergTransform::Month) => format!(
+                    "{:04}-{:02}",
+                    1970 + months.div_euclid(12),
+                    months.rem_euclid(12) + 1
+                ),
+                (PartitionValue::Int(years), IcebergTransform::Year) => {
+                    format!("{:04}", 1970 + years)
+                }
+                (PartitionValue::Int(value), _) => value.to_string(),
+                (PartitionValue::Long(value), _) => value.to_string(),
+                (PartitionValue::String(value), _) => escape_partition_value(value),
+            };
+            format!("{}={}/", field.name, value)
+        })
+        .collect()
+}
+
+fn manifest_schema(partition_by: &[PartitionBy]) -> Value {
+    let partition_fields = partition_by
+        .iter()
+        .map(|field| {
+            let result_type = match field.result_type {
+                "date" => json!({"type": "int", "logicalType": "date"}),
+                result_type => json!(result_type),
+            };
+            json!({
+                "name": field.name,
+                "type": ["null", result_type],
+                "default": null,
+                "field-id": field.field_id,
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "type": "record",
+        "name": "manifest_entry",
+        "fields": [
+            {"name": "status", "type": "int", "field-id": 0},
+            {"name": "snapshot_id", "type": ["null", "long"], "default": null, "field-id": 1},
+            {"name": "sequence_number", "type": ["null", "long"], "default": null, "field-id": 3},
+            {"name": "file_sequence_number", "type": ["null", "long"], "default": null, "field-id": 4},
+            {"name": "data_file", "field-id": 2, "type": {
+                "type": "record",
+                "name": "r2",
+                "fields": [
+                    {"name": "content", "type": "int", "field-id": 134},
+                    {"name": "file_path", "type": "string", "field-id": 100},
+                    {"name": "file_format", "type": "string", "field-id": 101},
+                    {"name": "partition", "field-id": 102, "type": {
+                        "type": "record",
+                        "name": "r102",
+                        "fields": partition_fields,
+                    }},
+                    {"name": "record_count", "type": "long", "field-id": 103},
+                    {"name": "file_size_in_bytes", "type": "long", "field-id": 104},
+                ],
+            }},
+        ],
+    })
+}
+
+fn manifest_list_schema() -> Value {
+    json!({
+        "type": "record",
+        "name": "manifest_file",
+        "fields": [
+            {"name": "manifest_path", "type": "string", "field-id": 500},
+            {"name": "manifest_length", "type": "long", "field-id": 501},
+            {"name": "partition_spec_id", "type": "int", "field-id": 502},
+            {"name": "content", "type": "int", "field-id": 517},
+            {"name": "sequence_number", "type": "long", "field-id": 515},
+            {"name": "min_sequence_number", "type": "long", "field-id": 516},
+            {"name": "added_snapshot_id", "type": "long", "field-id": 503},
+            {"name": "added_files_count", "type": "int", "field-id": 504},
+            {"name": "existing_files_count", "type": "int", "field-id": 505},
+            {"name": "deleted_files_count", "type": "int", "field-id": 506},
+            {"name": "added_rows_count", "type": "long", "field-id": 512},
+            {"name": "existing_rows_count", "type": "long", "field-id": 513},
+            {"name": "deleted_rows_count", "type": "long", "field-id": 514},
+        ],
+    })
+}
+
+/// Iceberg schema of `schema` with field ids assigned from 1, and the last assigned id.
+fn schema_to_iceberg(schema: &Schema) -> Result<(Value, i32), IcebergError> {
+    let mut last_id = 0;
+    let fields = fields_to_iceberg(schema.fields(), &mut last_id)?;
+    let schema = json!({"type": "struct", "schema-id": 0, "fields": fields});
+    Ok((schema, last_id))
+}
+
+// the fields of a struct get their ids before the fields nested in them
+fn fields_to_iceberg(fields: &Fields, last_id: &mut i32) -> Result<Vec<Value>, IcebergError> {
+    let first_id = *last_id + 1;
+    *last_id += fields.len() as i32;
+    fields
+        .iter()
+        .zip(first_id..)
+        .map(|(field, id)| {
+            Ok(json!({
+                "id": id,
+                "name": field.name(),
+                "required": !field.is_nullable(),
+                "type": type_to_iceberg(field.name(), field.data_type(), last_id)?,
+            }))
+        })
+        .collect()
+}
+
+fn type_to_iceberg(
+    name: &str,
+    data_type: &DataType,
+    last_id: &mut i32,
+) -> Result<Value, IcebergError> {
+    let primitive = match data_type {
+        DataType::Boolean => "boolean",
+        DataType::Int8 | DataType::Int16 | DataType::Int32 => "int",
+        DataType::Int64 => "long",
+        DataType::Float32 => "float",
+        DataType::Float64 => "double",
+        DataType::Date32 => "date",
+        DataType::Timestamp(TimeUnit::Microsecond, Some(_)) => "timestamptz",
+        DataType::Timestamp(TimeUnit::Microsecond, None) => "timestamp",
+        DataType::Utf8 | DataType::LargeUtf8 => "string",
+        DataType::Binary | DataType::LargeBinary => "binary",
+        DataType::Decimal128(precision, scale) if *scale >= 0 => {
+            return Ok(json!(format!("decimal({}, {})", precision, scale)))
+        }
+        DataType::List(field) => {
+            *last_id += 1;
+            let element_id = *last_id;
+            return Ok(json!({
+                "type": "list",
+                "element-id": element_id,
+                "element": type_to_iceberg(name, field.data_type(), last_id)?,
+                "element-required": !field.is_nullable(),
+            }));
+        }
+        DataType::Struct(fields) => {
+            return Ok(json!({"type": "struct", "fields": fields_to_iceberg(fields, last_id)?}))
+        }
+        other => {
+            return Err(IcebergError::UnsupportedType(
+                name.to_string(),
+                other.to_string(),
+            ))
+        }
+    };
+    Ok(json!(primitive))
+}
+
+// with `field_ids` the ids are kept in the field metadata, so Parquet files carry them
+fn iceberg_to_fields(value: &Value, field_ids: bool) -> Result<Fields, IcebergError> {
+    let invalid = || IcebergError::InvalidMetadata(format!("invalid struct type {}", value));
+    let fields = value["fields"].as_array().ok_or_else(invalid)?;
+    fields
+        .iter()
+        .map(|field| {
+            let name = field["name"].as_str().ok_or_else(invalid)?;
+            let data_type = iceberg_to_type(name, &field["type"], field_ids)?;
+            let nullable = !field["required"].as_bool().unwrap_or(false);
+            let column = Field::new(name, data_type, nullable);
+            Ok(with_field_id(column, &field["id"], field_ids))
+        })
+        .collect()
+}
+
+fn with_field_id(field: Field, id: &Value, field_ids: bool) -> Field {
+    match id.as_i64() {
+        Some(id) if field_ids => field.with_metadata(HashMap::from([(
+            PARQUET_FIELD_ID.to_string(),
+            id.to_string(),
+        )])),
+        _ => field,
+    }
+}
+
+fn iceberg_to_type(name: &str, value: &Value, field_ids: bool) -> Result<DataType, IcebergError> {
+    let unsupported = || IcebergError::UnsupportedType(name.to_string(), value.to_string());
+    let data_type = match value {
+        Value::String(primitive) => match primitive.as_str() {
+            "boolean" => DataType::Boolean,
+            "int" => DataType::Int32,
+            "long" => DataType::Int64,
+            "float" => DataType::Float32,
+            "double" => DataType::Float64,
+            "date" => DataType::Date32,
+            "timestamptz" => DataType::Timestamp(TimeUnit::Microsecond, Some(TIMESTAMP_TZ.into())),
+            "timestamp" => DataType::Timestamp(TimeUnit::Microsecond, None),
+            "string" => DataType::Utf8,
+            "binary" => DataType::Binary,
+            decimal => {
+                let (precision, scale) = decimal
+                    .strip_prefix("decimal(")
+                    .and_then(|decimal| decimal.strip_suffix(')'))
+                    .and_then(|decimal| decimal.split_once(','))
+                    .ok_or_else(unsupported)?;
+                let precision = precision.trim().parse().map_err(|_| unsupported())?;
+                let scale = scale.trim().parse().map_err(|_| unsupported())?;
+                DataType::Decimal128(precision, scale)
+            }
+        },
+        Value::Object(_) => match value["type"].as_str() {
+            Some("list") => {
+                let element = iceberg_to_type(name, &value["element"], field_ids)?;
+                let nullable = !value["element-required"].as_bool().unwrap_or(false);
+                let field = Field::new_list_field(element, nullable);
+                DataType::List(Arc::new(with_field_id(
+                    field,
+                    &value["element-id"],
+                    field_ids,
+                )))
+            }
+            Some("struct") => DataType::Struct(iceberg_to_fields(value, field_ids)?),
+            _ => return Err(unsupported()),
+        },
+        _ => return Err(unsupported()),
+    };
+    Ok(data_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::array::{Int16Array, StringArray, TimestampMicrosecondArray};
+    use rstest::rstest;
+
+    use super::*;
+    use crate::point_struct_type;
+
+    fn bookings_schema() -> Schema {
+        Schema::new(vec![
+            Field::new("book_ref", DataType::Utf8, false),
+            Field::new(
+                "book_date",
+                DataType::Timestamp(TimeUnit::Microsecond, Some(TIMESTAMP_TZ.into())),
+                true,
+            ),
+            Field::new("seats", DataType::Int16, true),
+        ])
+    }
+
+    // 2017-08-15 10:00, 2017-08-15 23:00 and 2017-09-01 00:00 in microseconds
+    fn bookings_batch(refs: &[&str]) -> RecordBatch {
+        let dates = [
+            1_502_791_200_000_000,
+            1_502_838_000_000_000,
+            1_504_224_000_000_000,
+        ];
+        RecordBatch::try_new(
+            Arc::new(bookings_schema()),
+            vec![
+                Arc::new(StringArray::from(refs.to_vec())),
+                Arc::new(
+                    TimestampMicrosecondArray::from(
+                        (0..refs.len())
+                            .map(|i| (i < 3).then(|| dates[i]))
+                            .collect::<Vec<_>>(),
+                    )
+                    .with_timezone(TIMESTAMP_TZ),
+                ),
+                Arc::new(Int16Array::from(vec![2; refs.len()])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn bookings(refs: &[&str]) -> DataFrame {
+        SessionContext::new()
+            .read_batch(bookings_batch(refs))
+            .unwrap()
+    }
+
+    #[rstest]
+    #[case("book_date", "book_date", IcebergTransform::Identity)]
+    #[case("day(book_date)", "book_date_day", IcebergTransform::Day)]
+    #[case(
+        " month( scheduled_departure ) ",
+        "scheduled_departure_month",
+        IcebergTransform::Month
+    )]
+    fn partition_field_test(
+        #[case] input: &str,
+        #[case] name: &str,
+        #[case] transform: IcebergTransform,
+    ) {
+        let field = input.parse::<IcebergPartitionField>().unwrap();
+        assert_eq!(field.name, name);
+        assert_eq!(field.transform, transform);
+    }
+
+    #[rstest]
+    #[case("bucket(16, book_ref)")]
+    #[case("day(book_date")]
+    #[case("day()")]
+    #[case("")]
+    fn partition_field_invalid_test(#[case] input: &str) {
+        assert!(input.parse::<IcebergPartitionField>().is_err());
+    }
+
+    #[test]
+    fn schema_to_iceberg_test() {
+        let schema = Schema::new(vec![
+            Field::new("airport_code", DataType::Utf8, false),
+            Field::new("coordinates", point_struct_type(), true),
+            Field::new(
+                "days_of_week",
+                DataType::List(Arc::new(Field::new_list_field(DataType::Int32, true))),
+                true,
+            ),
+            Field::new("amount", DataType::Decimal128(10, 2), true),
+        ]);
+        let (iceberg, last_id) = schema_to_iceberg(&schema).unwrap();
+        assert_eq!(last_id, 7);
+        assert_eq!(
+            iceberg["fields"][1]["type"],
+            json!({"type": "struct", "fields": [
+                {"id": 5, "name": "x", "required": true, "type": "double"},
+                {"id": 6, "name": "y", "required": true, "type": "double"},
+            ]})
+        );
+        assert_eq!(
+            iceberg["fields"][2]["type"],
+            json!({"type": "list", "element-id": 7, "element": "int", "element-required": false})
+        );
+        assert_eq!(iceberg["fields"][3]["type"], json!("decimal(10, 2)"));
+        assert_eq!(
+            iceberg_to_fields(&iceberg, false).unwrap(),
+            *schema.fields()
+        );
+
+        let fields = iceberg_to_fields(&iceberg, true).unwrap();
+        let DataType::Struct(point) = fields[1].data_type() else {
+            panic!("coordinates is not a struct");
+        };
+        assert_eq!(fields[3].metadata()[PARQUET_FIELD_ID], "4");
+        assert_eq!(point[1].metadata()[PARQUET_FIELD_ID], "6");
+    }
+
+    #[rstest]
+    #[case(IcebergTransform::Day, vec![17393, 17393, 17410])]
+    #[case(IcebergTransform::Month, vec![571, 571, 572])]
+    #[case(IcebergTransform::Year, vec![47, 47, 47])]
+    fn partition_values_test(#[case] transform: IcebergTransform, #[case] expected: Vec<i32>) {
+        let batch = bookings_batch(&["A", "B", "C", "D"]);
+        let mut expected = expected
+            .into_iter()
+            .map(PartitionValue::Int)
+            .collect::<Vec<_>>();
+        expected.push(PartitionValue::Null);
+        assert_eq!(
+            partition_values(batch.column(1), transform).unwrap(),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn iceberg_table_test() {
+        let store = LakeStore::from_url("memory:///warehouse").unwrap();
+        let catalog = IcebergCatalog::new(store.clone());
+        let partition_by = ["day(book_date)".parse::<IcebergPartitionField>().unwrap()];
+        let mut table = catalog
+            .create_table("bookings", "bookings", &bookings_schema(), &partition_by)
+            .await
+            .unwrap();
+        assert!(matches!(
+            catalog
+                .create_table("bookings", "bookings", &bookings_schema(), &[])
+                .await,
+            Err(AppError::IcebergError(IcebergError::TableExists(_)))
+        ));
+        assert!(matches!(
+            catalog.load_table("bookings", "tickets").await,
+            Err(AppError::IcebergError(IcebergError::TableNotFound(_)))
+        ));
+
+        let options = ParquetWriteOptions::default();
+        let df = bookings(&["A", "B", "C", "D"]);
+        let first = table
+            .write_df(df, SaveMode::Append, &options)
+            .await
+            .unwrap();
+        let df = bookings(&["E", "F"]);
+        let second = table
+            .write_df(df, SaveMode::Append, &options)
+            .await
+            .unwrap();
+        let df = bookings(&["G"]);
+        let third = table
+            .write_df(df, SaveMode::Overwrite, &options)
+            .await
+            .unwrap();
+        let df = table
+            .read_to_df(&SessionContext::new(), None)
+            .await
+            .unwrap();
+        let mismatch = df.select_columns(&["book_ref"]).unwrap();
+        assert!(matches!(
+            table.write_df(mismatch, SaveMode::Append, &options).await,
+            Err(AppError::IcebergError(IcebergError::SchemaMismatch))
+        ));
+
+        let table = catalog.load_table("bookings", "bookings").await.unwrap();
+        assert_eq!(table.snapshot_ids(), vec![first, second, third]);
+        assert_eq!(table.current_snapshot_id(), Some(third));
+        let ctx = SessionContext::new();
+        let mut counts = vec![];
+        for snapshot_id in [first, second, third] {
+            let df = table.read_to_df(&ctx, Some(snapshot_id)).await.unwrap();
+            counts.push(df.count().await.unwrap());
+        }
+        assert_eq!(counts, vec![4, 6, 1]);
+
+        let files = table.data_files(Some(first)).await.unwrap();
+        let dirs = files
+            .iter()
+            .map(|file| file.rsplit_once('/').unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            dirs,
+            vec![
+                "memory:///warehouse/bookings/bookings/data/book_date_day=2017-08-15",
+                "memory:///warehouse/bookings/bookings/data/book_date_day=2017-09-01",
+                "memory:///warehouse/bookings/bookings/data/book_date_day=null",
+            ]
+        );
+        let hint = store
+            .store()
+            .get(&store.path("bookings/bookings/metadata/version-hint.text"))
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(hint.as_ref(), b"3");
+        assert_eq!(
+            table.schema().unwrap().field(2).data_type(),
+            &DataType::Int32
+        );
+    }
+}
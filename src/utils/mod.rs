@@ -1,8 +1,31 @@
+mod avro;
 mod constants;
+mod convert;
+mod copy;
+mod delta;
+mod format;
+mod hive;
+mod iceberg;
+mod query_policy;
 mod queryparser;
+mod schema;
+mod store;
+#[allow(clippy::module_inception)]
 mod utils;
+mod write_options;
 
+pub use avro::*;
 pub use constants::*;
+pub use convert::*;
+pub use copy::*;
+pub use delta::*;
+pub use format::*;
+pub use hive::*;
+pub use iceberg::*;
+pub use query_policy::*;
 pub use queryparser::*;
+pub use schema::*;
+pub use store::*;
 pub use tables_names::*;
 pub use utils::*;
+pub use write_options::*;
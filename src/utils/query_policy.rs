@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::constants::{MAX_LIMIT, MAX_ROWS};
+use super::queryparser::ALL_TABLE_NAMES;
+
+/// Functions [`QueryPolicy::default`] refuses, they sleep, run SQL of their own, read server
+/// files, reach other servers or change server state.
+pub const DENIED_FUNCTIONS: &[&str] = &[
+    "pg_sleep",
+    "pg_sleep_for",
+    "pg_sleep_until",
+    // run the query or read the table they are given, past the table allow-list
+    "query_to_xml",
+    "query_to_xmlschema",
+    "query_to_xml_and_xmlschema",
+    "table_to_xml",
+    "table_to_xmlschema",
+    "table_to_xml_and_xmlschema",
+    "cursor_to_xml",
+    "cursor_to_xmlschema",
+    "schema_to_xml",
+    "schema_to_xmlschema",
+    "schema_to_xml_and_xmlschema",
+    "database_to_xml",
+    "database_to_xmlschema",
+    "database_to_xml_and_xmlschema",
+    "ts_stat",
+    "pg_read_file",
+    "pg_read_binary_file",
+    "pg_ls_dir",
+    "pg_stat_file",
+    "lo_import",
+    "lo_export",
+    "lo_create",
+    "lo_creat",
+    "lo_open",
+    "lo_get",
+    "lo_put",
+    "lo_from_bytea",
+    "lo_unlink",
+    "lo_truncate",
+    "lowrite",
+    "loread",
+    "dblink",
+    "dblink_exec",
+    "dblink_connect",
+    "dblink_open",
+    "dblink_send_query",
+    "nextval",
+    "setval",
+    "pg_notify",
+    "pg_logical_emit_message",
+    "pg_terminate_backend",
+    "pg_cancel_backend",
+    "pg_reload_conf",
+    "pg_rotate_logfile",
+    "pg_switch_wal",
+    "pg_create_restore_point",
+    "set_config",
+    "pg_advisory_lock",
+    "pg_advisory_lock_shared",
+    "pg_advisory_xact_lock",
+    "pg_advisory_xact_lock_shared",
+    "pg_try_advisory_lock",
+    "pg_try_advisory_xact_lock",
+];
+
+/// Rules user supplied queries are checked against before they reach postgres.
+///
+/// The default allows every table of [`ALL_TABLE_NAMES`] and all their columns, refuses the
+/// [`DENIED_FUNCTIONS`] and locking clauses such as `FOR UPDATE`, adds `LIMIT` [`MAX_ROWS`] to
+/// queries without one and lowers larger limits to [`MAX_LIMIT`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPolicy {
+    pub(crate) tables: Vec<String>,
+    pub(crate) schemas: Option<Vec<String>>,
+    pub(crate) columns: BTreeMap<String, Vec<String>>,
+    pub(crate) allowed_functions: Option<Vec<String>>,
+    pub(crate) denied_functions: Vec<String>,
+    pub(crate) default_limit: u64,
+    pub(crate) max_limit: Option<u64>,
+    pub(crate) allow_locking: bool,
+    pub(crate) max_joins: Option<usize>,
+    statement_timeout: Option<Duration>,
+}
+
+impl Default for QueryPolicy {
+    fn default() -> Self {
+        Self {
+            tables: to_strings(ALL_TABLE_NAMES),
+            schemas: None,
+            columns: BTreeMap::new(),
+            allowed_functions: None,
+            denied_functions: to_strings(DENIED_FUNCTIONS),
+            default_limit: MAX_ROWS.into(),
+            max_limit: Some(MAX_LIMIT.into()),
+            allow_locking: false,
+            max_joins: None,
+            statement_timeout: None,
+        }
+    }
+}
+
+impl QueryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tables(self, tables: &[&str]) -> Self {
+        Self {
+            tables: to_strings(tables),
+            ..self
+        }
+    }
+
+    /// Tables may only be qualified with one of `schemas`, an empty list refuses qualified
+    /// names. Any schema is accepted by default.
+    pub fn with_schemas(self, schemas: &[&str]) -> Self {
+        Self {
+            schemas: Some(to_strings(schemas)),
+            ..self
+        }
+    }
+
+    /// Restricts `table` to `columns`, also refusing `*` on it. Columns of other tables must be
+    /// qualified in queries that read a restricted table.
+    pub fn with_columns(self, table: &str, columns: &[&str]) -> Self {
+        let mut restricted = self.columns;
+        restricted.insert(table.to_string(), to_strings(columns));
+        Self {
+            columns: restricted,
+            ..self
+        }
+    }
+
+    /// Only `functions` may be called, the denied ones are still refused.
+    pub fn with_allowed_functions(self, functions: &[&str]) -> Self {
+        Self {
+            allowed_functions: Some(to_lowercase(functions)),
+            ..self
+        }
+    }
+
+    pub fn with_denied_functions(self, functions: &[&str]) -> Self {
+        Self {
+            denied_functions: to_lowercase(functions),
+            ..self
+        }
+    }
+
+    /// Limit added to queries without one.
+    pub fn with_default_limit(self, default_limit: u64) -> Self {
+        Self {
+            default_limit,
+            ..self
+        }
+    }
+
+    /// Larger limits are lowered to `max_limit`.
+    pub fn with_max_limit(self, max_limit: u64) -> Self {
+        Self {
+            max_limit: Some(max_limit),
+            ..self
+        }
+    }
+
+    pub fn with_locking(self, allow_locking: bool) -> Self {
+        Self {
+            allow_locking,
+            ..self
+        }
+    }
+
+    /// Joins are counted over the whole query, a comma in `FROM` counts as one.
+    pub fn with_max_joins(self, max_joins: usize) -> Self {
+        Self {
+            max_joins: Some(max_joins),
+            ..self
+        }
+    }
+
+    /// Applied by the server to every statement of a pool built with
+    /// [`PostgresDbBuilder::with_query_policy`].
+    ///
+    /// [`PostgresDbBuilder::with_query_policy`]: crate::PostgresDbBuilder::with_query_policy
+    pub fn with_statement_timeout(self, statement_timeout: Duration) -> Self {
+        Self {
+            statement_timeout: Some(statement_timeout),
+            ..self
+        }
+    }
+
+    pub fn statement_timeout(&self) -> Option<Duration> {
+        self.statement_timeout
+    }
+}
+
+fn to_strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+// function names are compared in lower case, as postgres folds unquoted names
+fn to_lowercase(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_lowercase()).collect()
+}
@@ -1,9 +1,18 @@
-use super::constants::{tables_names::*, MAX_ROWS};
+use super::constants::tables_names::*;
+use super::query_policy::QueryPolicy;
+use crate::Table;
 
-use sqlparser::ast::{Expr, Ident, LimitClause, SetExpr, Statement, TableFactor, Value};
+use std::ops::ControlFlow;
+
+use sqlparser::ast::{
+    BinaryOperator, Expr, Ident, LimitClause, ObjectName, OrderBy, Query, Select, SelectItem,
+    SelectItemQualifiedWildcardKind, SetExpr, Statement, TableFactor, TableWithJoins, Value, Visit,
+    Visitor,
+};
 use sqlparser::dialect::GenericDialect;
 use sqlparser::parser::Parser;
 use sqlparser::parser::ParserError;
+use sqlparser::tokenizer::Token;
 use thiserror::Error;
 
 pub const ALL_TABLE_NAMES: &[&str] = &[
@@ -30,52 +39,760 @@ pub enum QueryParserError {
 
     #[error("Unsupported query type")]
     UnsupportedQueryType,
+
+    #[error("Invalid query: schema {0} is not allowed")]
+    SchemaNotAllowed(String),
+
+    #[error("Invalid query: column {1} of table {0} is not allowed")]
+    ColumnNotAllowed(String, String),
+
+    #[error("Invalid query: function {0} is not allowed")]
+    FunctionNotAllowed(String),
+
+    #[error("Invalid query: locking clauses are not allowed")]
+    LockingClauseNotAllowed,
+
+    #[error("Invalid query: more than {0} joins")]
+    TooManyJoins(usize),
+
+    #[error("Invalid query: limit must be a number of rows")]
+    InvalidLimit,
+
+    #[error("Invalid query: column {} does not exist in table {}{}", .1, .0, did_you_mean(.2))]
+    UnknownColumn(String, String, Vec<String>),
 }
 
 pub fn prepare_query(query: &str) -> Result<String, QueryParserError> {
+    prepare_query_with_policy(query, &QueryPolicy::default())
+}
+
+/// Validates `query` and caps it at [`MAX_ROWS`] rows unless it has its own LIMIT.
+///
+/// [`MAX_ROWS`]: super::constants::MAX_ROWS
+pub fn prepare_query_with_tables(query: &str, tables: &[&str]) -> Result<String, QueryParserError> {
+    prepare_query_with_policy(query, &QueryPolicy::default().with_tables(tables))
+}
+
+/// Checks `query` against `policy` and applies its row limits, see [`QueryPolicy`].
+pub fn prepare_query_with_policy(
+    query: &str,
+    policy: &QueryPolicy,
+) -> Result<String, QueryParserError> {
+    let mut statement = parse_query(query, policy)?;
+    if let Statement::Query(query) = &mut statement {
+        apply_limit(query, policy)?;
+    }
+    Ok(statement.to_string())
+}
+
+pub fn validate_query(query: &str) -> Result<String, QueryParserError> {
+    validate_query_with_tables(query, ALL_TABLE_NAMES)
+}
+
+/// Validates `query` like [`prepare_query_with_tables`] without capping its rows, used by exports.
+pub fn validate_query_with_tables(
+    query: &str,
+    tables: &[&str],
+) -> Result<String, QueryParserError> {
+    validate_query_with_policy(query, &QueryPolicy::default().with_tables(tables))
+}
+
+/// Checks `query` against `policy` leaving its rows as they are.
+pub fn validate_query_with_policy(
+    query: &str,
+    policy: &QueryPolicy,
+) -> Result<String, QueryParserError> {
+    Ok(parse_query(query, policy)?.to_string())
+}
+
+/// Returns the first table `query` reads from, once it passed [`validate_query`].
+pub fn query_table_name(query: &str) -> Result<String, QueryParserError> {
+    let (_, tables) = parse_query_tables(query, &QueryPolicy::default())?;
+    tables
+        .into_iter()
+        .next()
+        .ok_or(QueryParserError::InvalidTableName)
+}
+
+/// Restricts `query` to rows with `low < column <= high`, used by incremental loads to read only
+/// the rows past the previous watermark. `column` must be a column of the first table `query`
+/// reads from. Bounds are quoted literals, so postgres casts them to the column type, a missing
+/// bound is left open.
+pub fn add_watermark_filter(
+    query: &str,
+    column: &str,
+    low: Option<&str>,
+    high: Option<&str>,
+) -> Result<String, QueryParserError> {
+    let (mut statement, tables) = parse_query_tables(query, &QueryPolicy::default())?;
+    let table = tables
+        .into_iter()
+        .next()
+        .ok_or(QueryParserError::InvalidTableName)?;
+    let schema = Table::new(&table)
+        .ok_or(QueryParserError::InvalidTableName)?
+        .schema();
+    if schema.field_with_name(column).is_err() {
+        let columns = schema.fields().iter().map(|field| field.name().as_str());
+        let similar = similar_columns(column, columns);
+        return Err(QueryParserError::UnknownColumn(
+            table,
+            column.to_string(),
+            similar,
+        ));
+    }
+    let bound = |op, value: &str| Expr::BinaryOp {
+        left: Box::new(Expr::Identifier(Ident::with_quote('"', column))),
+        op,
+        right: Box::new(Expr::Value(
+            Value::SingleQuotedString(value.to_string()).into(),
+        )),
+    };
+    add_selection(
+        &mut statement,
+        [
+            low.map(|low| bound(BinaryOperator::Gt, low)),
+            high.map(|high| bound(BinaryOperator::LtEq, high)),
+        ],
+    )?;
+    Ok(statement.to_string())
+}
+
+/// Adds `filter`, a SQL condition such as `status = 'Arrived'`, to the `WHERE` clause of `query`.
+pub fn add_filter(query: &str, filter: &str) -> Result<String, QueryParserError> {
+    let dialect = GenericDialect {};
+    let mut parser = Parser::new(&dialect).try_with_sql(filter)?;
+    let filter = parser.parse_expr()?;
+    parser.expect_token(&Token::EOF)?;
+    let mut statement = parse_query(query, &QueryPolicy::default())?;
+    add_selection(&mut statement, [Some(filter)])?;
+    Ok(statement.to_string())
+}
+
+// ANDs the conditions to the existing selection, which is kept in parentheses
+fn add_selection<const N: usize>(
+    statement: &mut Statement,
+    conditions: [Option<Expr>; N],
+) -> Result<(), QueryParserError> {
+    let Statement::Query(query) = statement else {
+        return Err(QueryParserError::UnsupportedQueryType);
+    };
+    let SetExpr::Select(select) = &mut *query.body else {
+        return Err(QueryParserError::SelectQueryNotFound);
+    };
+    let selection = select
+        .selection
+        .take()
+        .map(|expr| Expr::Nested(Box::new(expr)));
+    select.selection = std::iter::once(selection)
+        .chain(conditions)
+        .flatten()
+        .reduce(|left, right| Expr::BinaryOp {
+            left: Box::new(left),
+            op: BinaryOperator::And,
+            right: Box::new(right),
+        });
+    Ok(())
+}
+
+// lowers the limit to the policy maximum, a query without one gets the policy default
+fn apply_limit(query: &mut Query, policy: &QueryPolicy) -> Result<(), QueryParserError> {
+    let cap = |limit: &mut Expr| -> Result<(), QueryParserError> {
+        if let Some(max_limit) = policy.max_limit {
+            *limit = limit_expr(limit_rows(limit)?.min(max_limit));
+        }
+        Ok(())
+    };
+    if let Some(fetch) = &mut query.fetch {
+        if fetch.percent && policy.max_limit.is_some() {
+            return Err(QueryParserError::InvalidLimit);
+        }
+        if let Some(quantity) = &mut fetch.quantity {
+            cap(quantity)?;
+        }
+    }
+    match &mut query.limit_clause {
+        Some(LimitClause::LimitOffset {
+            limit: Some(limit), ..
+        })
+        | Some(LimitClause::OffsetCommaLimit { limit, .. }) => cap(limit)?,
+        // LIMIT ALL or only an OFFSET
+        Some(LimitClause::LimitOffset { limit, .. }) if query.fetch.is_none() => {
+            *limit = Some(limit_expr(policy.default_limit))
+        }
+        None if query.fetch.is_none() => {
+            // for set operations the limit caps the combined rows
+            query.limit_clause = Some(LimitClause::LimitOffset {
+                limit: Some(limit_expr(policy.default_limit)),
+                offset: None,
+                limit_by: vec![],
+            })
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn limit_rows(limit: &Expr) -> Result<u64, QueryParserError> {
+    match limit {
+        Expr::Value(value) => match &value.value {
+            Value::Number(rows, _) => rows.parse().map_err(|_| QueryParserError::InvalidLimit),
+            _ => Err(QueryParserError::InvalidLimit),
+        },
+        _ => Err(QueryParserError::InvalidLimit),
+    }
+}
+
+fn limit_expr(rows: u64) -> Expr {
+    Expr::Value(Value::Number(rows.to_string(), false).into())
+}
+
+fn parse_query(query: &str, policy: &QueryPolicy) -> Result<Statement, QueryParserError> {
+    Ok(parse_query_tables(query, policy)?.0)
+}
+
+// parses a single select statement and returns it with the tables it reads, in query order
+fn parse_query_tables(
+    query: &str,
+    policy: &QueryPolicy,
+) -> Result<(Statement, Vec<String>), QueryParserError> {
     let dialect = GenericDialect {};
     let mut ast = Parser::parse_sql(&dialect, query)?;
-    if let Some(Statement::Query(query)) = ast.get_mut(0) {
-        // check query contains correct table name
-        let valid_table = match &*query.body {
-            SetExpr::Select(select) => {
-                if let Some(from_table) = select.from.get(0) {
-                    if let TableFactor::Table { name, .. } = &from_table.relation {
-                        name.0
-                            .last()
-                            .map(|ident| ALL_TABLE_NAMES.contains(&ident.to_string().as_str()))
-                            .unwrap_or(false)
-                    } else {
-                        false
+    if ast.len() != 1 {
+        return Err(QueryParserError::UnsupportedQueryType);
+    }
+    let statement = ast.swap_remove(0);
+    let Statement::Query(query) = &statement else {
+        return Err(QueryParserError::UnsupportedQueryType);
+    };
+    let mut checker = QueryChecker {
+        policy,
+        ctes: vec![],
+        scopes: vec![],
+        ordering: false,
+        joins: 0,
+        relations: vec![],
+    };
+    checker.check_query(query)?;
+    if checker.relations.is_empty() {
+        return Err(QueryParserError::InvalidTableName);
+    }
+    Ok((statement, checker.relations))
+}
+
+/// Checks a query against a [`QueryPolicy`], going through joins, subqueries, set operations
+/// and CTEs. Every relation must be an allowed table or a CTE in scope.
+struct QueryChecker<'a> {
+    policy: &'a QueryPolicy,
+    ctes: Vec<String>,
+    // `FROM` of the selects enclosing the expression being checked, innermost last
+    scopes: Vec<Scope>,
+    // select aliases can be used in ORDER BY
+    ordering: bool,
+    joins: usize,
+    relations: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    bindings: Vec<Binding>,
+    aliases: Vec<String>,
+}
+
+impl Scope {
+    fn restricted(&self) -> impl Iterator<Item = &str> {
+        self.bindings
+            .iter()
+            .filter(|binding| binding.restricted)
+            .filter_map(|binding| binding.table.as_deref())
+    }
+}
+
+/// Relation of a `FROM` clause.
+#[derive(Debug, Default)]
+struct Binding {
+    // name the relation is referred to by, its alias or table name
+    name: String,
+    // `None` for CTEs and subqueries
+    table: Option<String>,
+    // columns are restricted by the policy
+    restricted: bool,
+    // columns of the table schema, `None` when they are not known
+    columns: Option<Vec<String>>,
+}
+
+impl QueryChecker<'_> {
+    fn check_query(&mut self, query: &Query) -> Result<(), QueryParserError> {
+        if !self.policy.allow_locking && !query.locks.is_empty() {
+            return Err(QueryParserError::LockingClauseNotAllowed);
+        }
+        let ctes = self.ctes.len();
+        let res = self.check_query_in_scope(query);
+        self.ctes.truncate(ctes);
+        res
+    }
+
+    fn check_query_in_scope(&mut self, query: &Query) -> Result<(), QueryParserError> {
+        if let Some(with) = &query.with {
+            // a CTE sees the ones before it, or all of them when recursive
+            if with.recursive {
+                self.ctes
+                    .extend(with.cte_tables.iter().map(|cte| cte.alias.name.to_string()));
+            }
+            for cte in &with.cte_tables {
+                self.check_query(&cte.query)?;
+                if !with.recursive {
+                    self.ctes.push(cte.alias.name.to_string());
+                }
+            }
+        }
+        match &*query.body {
+            // ORDER BY of a select sees its FROM
+            SetExpr::Select(select) => self.check_select(select, query.order_by.as_ref())?,
+            body => {
+                self.check_set_expr(body)?;
+                self.check_order_by(query.order_by.as_ref())?;
+            }
+        }
+        self.visit(&query.limit_clause)?;
+        self.visit(&query.fetch)
+    }
+
+    // only selects and their set operations, `SELECT INTO` and data modifying CTEs are refused
+    fn check_set_expr(&mut self, body: &SetExpr) -> Result<(), QueryParserError> {
+        match body {
+            SetExpr::Select(select) => self.check_select(select, None),
+            SetExpr::SetOperation { left, right, .. } => {
+                self.check_set_expr(left)?;
+                self.check_set_expr(right)
+            }
+            SetExpr::Query(query) => self.check_query(query),
+            SetExpr::Values(values) => self.visit(values),
+            _ => Err(QueryParserError::UnsupportedQueryType),
+        }
+    }
+
+    fn check_select(
+        &mut self,
+        select: &Select,
+        order_by: Option<&OrderBy>,
+    ) -> Result<(), QueryParserError> {
+        if select.into.is_some() {
+            return Err(QueryParserError::UnsupportedQueryType);
+        }
+        let joins = select.from.len().saturating_sub(1)
+            + select
+                .from
+                .iter()
+                .map(|from| from.joins.len())
+                .sum::<usize>();
+        self.add_joins(joins)?;
+
+        let mut scope = Scope::default();
+        for from in &select.from {
+            self.add_bindings(&mut scope, from);
+        }
+        scope.aliases = select
+            .projection
+            .iter()
+            .filter_map(|item| match item {
+                SelectItem::ExprWithAlias { alias, .. } => Some(fold_ident(alias)),
+                _ => None,
+            })
+            .collect();
+        self.scopes.push(scope);
+        let res = self.check_select_in_scope(select, order_by);
+        self.scopes.pop();
+        res
+    }
+
+    fn check_select_in_scope(
+        &mut self,
+        select: &Select,
+        order_by: Option<&OrderBy>,
+    ) -> Result<(), QueryParserError> {
+        for item in &select.projection {
+            match item {
+                SelectItem::Wildcard(_) => {
+                    let scope = self.scopes.last().expect("select scope");
+                    if let Some(table) = scope.restricted().next() {
+                        return Err(column_not_allowed(table, "*"));
+                    }
+                }
+                SelectItem::QualifiedWildcard(
+                    SelectItemQualifiedWildcardKind::ObjectName(name),
+                    _,
+                ) => {
+                    if let Some(qualifier) = name.0.last().and_then(|part| part.as_ident()) {
+                        if let Some(table) = self.restricted_binding(&fold_ident(qualifier)) {
+                            return Err(column_not_allowed(table, "*"));
+                        }
                     }
-                } else {
-                    false
                 }
+                _ => {}
             }
-            _ => false,
+        }
+        self.visit(select)?;
+        self.check_order_by(order_by)
+    }
+
+    fn check_order_by(&mut self, order_by: Option<&OrderBy>) -> Result<(), QueryParserError> {
+        let Some(order_by) = order_by else {
+            return Ok(());
         };
-        if !valid_table {
-            return Err(QueryParserError::InvalidTableName);
-        }
-
-        if let SetExpr::Select(_select) = &mut *query.body {
-            // query contains limit
-            if query.limit_clause.is_none() {
-                query.limit_clause = Some(LimitClause::LimitOffset {
-                    limit: Some(Expr::Value(
-                        Value::Number(MAX_ROWS.to_string(), false).into(),
-                    )),
-                    offset: None,
-                    limit_by: vec![],
-                })
-            };
-
-            Ok(ast[0].to_string())
-        } else {
-            Err(QueryParserError::SelectQueryNotFound)
-        }
-    } else {
-        Err(QueryParserError::UnsupportedQueryType)
+        self.ordering = true;
+        let res = self.visit(order_by);
+        self.ordering = false;
+        res
+    }
+
+    fn add_bindings(&self, scope: &mut Scope, from: &TableWithJoins) {
+        let relations =
+            std::iter::once(&from.relation).chain(from.joins.iter().map(|join| &join.relation));
+        for relation in relations {
+            match relation {
+                TableFactor::Table { name, alias, .. } => {
+                    let ident = name.0.last().and_then(|part| part.as_ident());
+                    let binding = alias
+                        .as_ref()
+                        .map(|alias| &alias.name)
+                        .or(ident)
+                        .map(fold_ident)
+                        .unwrap_or_default();
+                    let is_cte = name.0.len() == 1 && self.ctes.contains(&name.to_string());
+                    let table = ident.filter(|_| !is_cte).map(|table| table.value.clone());
+                    let columns = table.as_deref().and_then(Table::new).map(|table| {
+                        let schema = table.schema();
+                        schema
+                            .fields()
+                            .iter()
+                            .map(|field| field.name().clone())
+                            .collect()
+                    });
+                    scope.bindings.push(Binding {
+                        name: binding,
+                        restricted: table
+                            .as_ref()
+                            .is_some_and(|table| self.policy.columns.contains_key(table)),
+                        table,
+                        columns,
+                    });
+                }
+                TableFactor::Derived { alias, .. } => scope.bindings.push(Binding {
+                    name: alias
+                        .as_ref()
+                        .map(|alias| fold_ident(&alias.name))
+                        .unwrap_or_default(),
+                    ..Default::default()
+                }),
+                TableFactor::NestedJoin {
+                    table_with_joins, ..
+                } => self.add_bindings(scope, table_with_joins),
+                // columns of anything else are unknown
+                _ => scope.bindings.push(Binding::default()),
+            }
+        }
+    }
+
+    fn add_joins(&mut self, joins: usize) -> Result<(), QueryParserError> {
+        self.joins += joins;
+        match self.policy.max_joins {
+            Some(max_joins) if self.joins > max_joins => {
+                Err(QueryParserError::TooManyJoins(max_joins))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_relation(&mut self, relation: &ObjectName) -> Result<(), QueryParserError> {
+        let name = relation.to_string();
+        if relation.0.len() == 1 && self.ctes.contains(&name) {
+            return Ok(());
+        }
+        if let (Some(schemas), [.., schema, _]) = (&self.policy.schemas, &relation.0[..]) {
+            let schema = schema
+                .as_ident()
+                .map_or_else(|| schema.to_string(), fold_ident);
+            if !schemas.contains(&schema) {
+                return Err(QueryParserError::SchemaNotAllowed(schema));
+            }
+        }
+        match relation.0.last().map(|ident| ident.to_string()) {
+            Some(table) if self.policy.tables.contains(&table) => {
+                self.relations.push(table);
+                Ok(())
+            }
+            _ => Err(QueryParserError::InvalidTableName),
+        }
+    }
+
+    fn check_function(&self, name: &ObjectName) -> Result<(), QueryParserError> {
+        let Some(function) = name.0.last().and_then(|part| part.as_ident()) else {
+            return Ok(());
+        };
+        let function = function.value.to_lowercase();
+        let allowed = match &self.policy.allowed_functions {
+            Some(allowed) => allowed.contains(&function),
+            None => true,
+        };
+        if !allowed || self.policy.denied_functions.contains(&function) {
+            return Err(QueryParserError::FunctionNotAllowed(function));
+        }
+        Ok(())
+    }
+
+    fn check_column(&self, qualifier: Option<&str>, column: &str) -> Result<(), QueryParserError> {
+        self.check_column_exists(qualifier, column)?;
+        self.check_column_allowed(qualifier, column)
+    }
+
+    // a column of a known table must be in its schema, without a qualifier it must be in the
+    // schema of a table in scope unless the columns of one of them are unknown
+    fn check_column_exists(
+        &self,
+        qualifier: Option<&str>,
+        column: &str,
+    ) -> Result<(), QueryParserError> {
+        let has_column = |binding: &Binding| {
+            binding
+                .columns
+                .as_ref()
+                .is_none_or(|columns| columns.iter().any(|name| name == column))
+        };
+        match qualifier {
+            Some(qualifier) => match self.binding(qualifier) {
+                Some(binding) if !has_column(binding) => Err(unknown_column(&[binding], column)),
+                _ => Ok(()),
+            },
+            None => {
+                let bindings = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .flat_map(|scope| &scope.bindings)
+                    .collect::<Vec<_>>();
+                let is_alias = self
+                    .scopes
+                    .last()
+                    .is_some_and(|scope| scope.aliases.iter().any(|alias| alias == column));
+                if bindings.is_empty() || is_alias || bindings.iter().any(|b| has_column(b)) {
+                    return Ok(());
+                }
+                Err(unknown_column(&bindings, column))
+            }
+        }
+    }
+
+    // without a qualifier the column can belong to any restricted table in scope, so it must be
+    // allowed in one of them
+    fn check_column_allowed(
+        &self,
+        qualifier: Option<&str>,
+        column: &str,
+    ) -> Result<(), QueryParserError> {
+        let allowed = |table: &str| {
+            self.policy
+                .columns
+                .get(table)
+                .is_some_and(|columns| columns.iter().any(|allowed| allowed == column))
+        };
+        match qualifier {
+            Some(qualifier) => match self.restricted_binding(qualifier) {
+                Some(table) if !allowed(table) => Err(column_not_allowed(table, column)),
+                _ => Ok(()),
+            },
+            None => {
+                let mut restricted = self.scopes.iter().rev().flat_map(Scope::restricted);
+                let Some(first) = restricted.next() else {
+                    return Ok(());
+                };
+                let is_alias = self.ordering
+                    && self
+                        .scopes
+                        .last()
+                        .is_some_and(|scope| scope.aliases.iter().any(|alias| alias == column));
+                if is_alias || allowed(first) || restricted.any(allowed) {
+                    return Ok(());
+                }
+                Err(column_not_allowed(first, column))
+            }
+        }
+    }
+
+    // innermost relation referred to by `name`
+    fn binding(&self, name: &str) -> Option<&Binding> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.bindings.iter().rev())
+            .find(|binding| binding.name == name)
+    }
+
+    fn restricted_binding(&self, name: &str) -> Option<&str> {
+        self.binding(name)
+            .filter(|binding| binding.restricted)
+            .and_then(|binding| binding.table.as_deref())
+    }
+
+    // nested queries are checked by `check_query` as the visitor reaches them
+    fn visit<T: Visit>(&mut self, node: &T) -> Result<(), QueryParserError> {
+        let mut visitor = PolicyVisitor {
+            checker: self,
+            depth: 0,
+        };
+        match node.visit(&mut visitor) {
+            ControlFlow::Continue(()) => Ok(()),
+            ControlFlow::Break(e) => Err(e),
+        }
+    }
+}
+
+fn column_not_allowed(table: &str, column: &str) -> QueryParserError {
+    QueryParserError::ColumnNotAllowed(table.to_string(), column.to_string())
+}
+
+// reported against the first table, suggesting similar columns of all of them
+fn unknown_column(bindings: &[&Binding], column: &str) -> QueryParserError {
+    let table = bindings
+        .iter()
+        .find_map(|binding| binding.table.clone())
+        .unwrap_or_default();
+    let columns = bindings
+        .iter()
+        .flat_map(|binding| binding.columns.iter().flatten())
+        .map(String::as_str);
+    QueryParserError::UnknownColumn(table, column.to_string(), similar_columns(column, columns))
+}
+
+/// Up to three of `columns` closest to `column`, by edit distance or because one contains
+/// the other.
+pub fn similar_columns<'a>(column: &str, columns: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let column = column.to_lowercase();
+    let max_distance = (column.len() / 3).max(1);
+    let mut similar = columns
+        .filter_map(|name| {
+            let distance = strsim::levenshtein(&column, &name.to_lowercase());
+            let contains = column.len() > 2 && (name.contains(&column) || column.contains(name));
+            (distance <= max_distance || contains).then_some((distance, name))
+        })
+        .collect::<Vec<_>>();
+    similar.sort();
+    similar.dedup();
+    similar
+        .into_iter()
+        .take(3)
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+// postgres folds unquoted identifiers to lower case
+fn fold_ident(ident: &Ident) -> String {
+    match ident.quote_style {
+        Some(_) => ident.value.clone(),
+        None => ident.value.to_lowercase(),
+    }
+}
+
+fn did_you_mean(columns: &[String]) -> String {
+    match columns {
+        [] => String::new(),
+        columns => format!(", did you mean {}?", columns.join(" or ")),
+    }
+}
+
+struct PolicyVisitor<'a, 'b> {
+    checker: &'a mut QueryChecker<'b>,
+    depth: usize,
+}
+
+impl PolicyVisitor<'_, '_> {
+    fn check(&mut self, res: Result<(), QueryParserError>) -> ControlFlow<QueryParserError> {
+        match res {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(e) => ControlFlow::Break(e),
+        }
+    }
+}
+
+impl Visitor for PolicyVisitor<'_, '_> {
+    type Break = QueryParserError;
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<Self::Break> {
+        if self.depth == 0 {
+            let res = self.checker.check_query(query);
+            self.check(res)?;
+        }
+        self.depth += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &Query) -> ControlFlow<Self::Break> {
+        self.depth -= 1;
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<Self::Break> {
+        if self.depth > 0 {
+            return ControlFlow::Continue(());
+        }
+        match table_factor {
+            TableFactor::Table { args: None, .. } | TableFactor::Derived { .. } => {
+                ControlFlow::Continue(())
+            }
+            TableFactor::NestedJoin {
+                table_with_joins, ..
+            } => {
+                let res = self.checker.add_joins(table_with_joins.joins.len());
+                self.check(res)
+            }
+            // table functions and the like can read anything
+            _ => ControlFlow::Break(QueryParserError::UnsupportedQueryType),
+        }
+    }
+
+    fn pre_visit_relation(&mut self, relation: &ObjectName) -> ControlFlow<Self::Break> {
+        if self.depth > 0 {
+            return ControlFlow::Continue(());
+        }
+        let res = self.checker.check_relation(relation);
+        self.check(res)
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<Self::Break> {
+        // `IN (SELECT ...)` holds its select without a query around it
+        if let Expr::InSubquery { expr, subquery, .. } = expr {
+            if self.depth == 0 {
+                let res = self
+                    .checker
+                    .visit(expr)
+                    .and_then(|()| self.checker.check_set_expr(subquery));
+                self.check(res)?;
+            }
+            self.depth += 1;
+            return ControlFlow::Continue(());
+        }
+        if self.depth > 0 {
+            return ControlFlow::Continue(());
+        }
+        let res = match expr {
+            Expr::Function(function) => self.checker.check_function(&function.name),
+            Expr::Identifier(ident) => self.checker.check_column(None, &fold_ident(ident)),
+            Expr::CompoundIdentifier(idents) => match idents.as_slice() {
+                [.., qualifier, column] => self
+                    .checker
+                    .check_column(Some(&fold_ident(qualifier)), &fold_ident(column)),
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        };
+        self.check(res)
+    }
+
+    fn post_visit_expr(&mut self, expr: &Expr) -> ControlFlow<Self::Break> {
+        if let Expr::InSubquery { .. } = expr {
+            self.depth -= 1;
+        }
+        ControlFlow::Continue(())
     }
 }
 
@@ -118,8 +835,386 @@ mod tests {
         "insert into aircrafts_data(file_name) values('foo')",
         Err(QueryParserError::UnsupportedQueryType)
     )]
+    #[case(
+        "select * from flights f join ticket_flights tf on f.flight_id = tf.flight_id",
+        Ok("SELECT * FROM flights AS f JOIN ticket_flights AS tf ON f.flight_id = tf.flight_id LIMIT 10".to_string())
+    )]
+    #[case(
+        "select * from flights f join foo on true",
+        Err(QueryParserError::InvalidTableName)
+    )]
+    #[case(
+        "select flight_id from flights union select flight_id from ticket_flights",
+        Ok("SELECT flight_id FROM flights UNION SELECT flight_id FROM ticket_flights LIMIT 10".to_string())
+    )]
+    #[case(
+        "select flight_id from flights union all select flight_id from foo",
+        Err(QueryParserError::InvalidTableName)
+    )]
+    #[case(
+        "with f as (select * from flights) select * from f",
+        Ok("WITH f AS (SELECT * FROM flights) SELECT * FROM f LIMIT 10".to_string())
+    )]
+    #[case(
+        "with f as (select * from foo) select * from f",
+        Err(QueryParserError::InvalidTableName)
+    )]
+    #[case(
+        "with a as (select * from b), b as (select * from flights) select * from a",
+        Err(QueryParserError::InvalidTableName)
+    )]
+    #[case(
+        "select * from (with f as (select * from flights) select * from f) x join f on true",
+        Err(QueryParserError::InvalidTableName)
+    )]
+    #[case(
+        "with recursive r as (select 1 as n from seats union all select n + 1 from r where n < 3) select * from r",
+        Ok("WITH RECURSIVE r AS (SELECT 1 AS n FROM seats UNION ALL SELECT n + 1 FROM r WHERE n < 3) SELECT * FROM r LIMIT 10".to_string())
+    )]
+    #[case(
+        "select * from (select * from seats) s",
+        Ok("SELECT * FROM (SELECT * FROM seats) AS s LIMIT 10".to_string())
+    )]
+    #[case(
+        "select * from flights where exists (select 1 from ticket_flights tf where tf.flight_id = flights.flight_id)",
+        Ok("SELECT * FROM flights WHERE EXISTS (SELECT 1 FROM ticket_flights AS tf WHERE tf.flight_id = flights.flight_id) LIMIT 10".to_string())
+    )]
+    #[case(
+        "select * from flights where flight_id in (select flight_id from foo)",
+        Err(QueryParserError::InvalidTableName)
+    )]
+    #[case(
+        "select (select count(*) from foo) from flights",
+        Err(QueryParserError::InvalidTableName)
+    )]
+    #[case(
+        "select * from generate_series(1, 10)",
+        Err(QueryParserError::UnsupportedQueryType)
+    )]
+    #[case(
+        "select * from flights, lateral pg_ls_dir('.')",
+        Err(QueryParserError::UnsupportedQueryType)
+    )]
+    #[case(
+        "select * into foo from flights",
+        Err(QueryParserError::UnsupportedQueryType)
+    )]
+    #[case(
+        "select * from flights; drop table flights",
+        Err(QueryParserError::UnsupportedQueryType)
+    )]
+    #[case(
+        "with d as (delete from flights returning *) select * from d",
+        Err(QueryParserError::UnsupportedQueryType)
+    )]
+    #[case("select 1", Err(QueryParserError::InvalidTableName))]
+    #[case(
+        "select * from flights fetch first 5 rows only",
+        Ok("SELECT * FROM flights FETCH FIRST 5 ROWS ONLY".to_string())
+    )]
     #[case("foo bar baz", Err(QueryParserError::SqlParseError(ParserError::ParserError("Expected: an SQL statement, found: foo at Line: 1, Column: 1".to_string()))))]
+    #[case(
+        "select foo from flights",
+        Err(QueryParserError::UnknownColumn("flights".to_string(), "foo".to_string(), vec![]))
+    )]
+    #[case(
+        "select fligt_id from flights",
+        Err(QueryParserError::UnknownColumn("flights".to_string(), "fligt_id".to_string(), vec!["flight_id".to_string()]))
+    )]
+    #[case(
+        r#"select "Flight_ID" from flights"#,
+        Err(QueryParserError::UnknownColumn("flights".to_string(), "Flight_ID".to_string(), vec!["flight_id".to_string(), "flight_no".to_string()]))
+    )]
+    #[case(
+        "select Flight_ID as id from flights order by id",
+        Ok("SELECT Flight_ID AS id FROM flights ORDER BY id LIMIT 10".to_string())
+    )]
+    #[case(
+        "select f.flight_id from flights f where f.departure > now()",
+        Err(QueryParserError::UnknownColumn(
+            "flights".to_string(),
+            "departure".to_string(),
+            vec!["actual_departure".to_string(), "departure_airport".to_string(), "scheduled_departure".to_string()]
+        ))
+    )]
+    #[case(
+        "select amount, status from flights f join ticket_flights tf on f.flight_id = tf.flight_id",
+        Ok("SELECT amount, status FROM flights AS f JOIN ticket_flights AS tf ON f.flight_id = tf.flight_id LIMIT 10".to_string())
+    )]
+    #[case(
+        "select seat_no from flights f join ticket_flights tf on f.flight_id = tf.flight_id",
+        Err(QueryParserError::UnknownColumn("flights".to_string(), "seat_no".to_string(), vec![]))
+    )]
+    #[case(
+        "with f as (select flight_id as id from flights) select id from f",
+        Ok("WITH f AS (SELECT flight_id AS id FROM flights) SELECT id FROM f LIMIT 10".to_string())
+    )]
+    #[case(
+        "select * from flights f where exists (select 1 from ticket_flights tf where tf.flight_id = f.flight)",
+        Err(QueryParserError::UnknownColumn("flights".to_string(), "flight".to_string(), vec!["flight_id".to_string(), "flight_no".to_string()]))
+    )]
     fn prepare_query_test(#[case] input: &str, #[case] expected: Result<String, QueryParserError>) {
         assert_eq!(expected, prepare_query(input));
     }
+
+    #[rstest]
+    #[case("select * from ticket_flights", Ok("SELECT * FROM ticket_flights".to_string()))]
+    #[case(
+        "select * from ticket_flights where flight_id >= 1 and flight_id < 100",
+        Ok("SELECT * FROM ticket_flights WHERE flight_id >= 1 AND flight_id < 100".to_string())
+    )]
+    #[case("select * from seats limit 5", Ok("SELECT * FROM seats LIMIT 5".to_string()))]
+    #[case("select * from foo", Err(QueryParserError::InvalidTableName))]
+    #[case(
+        "select flight_id from flights except select flight_id from ticket_flights limit 5",
+        Ok("SELECT flight_id FROM flights EXCEPT SELECT flight_id FROM ticket_flights LIMIT 5".to_string())
+    )]
+    fn validate_query_test(
+        #[case] input: &str,
+        #[case] expected: Result<String, QueryParserError>,
+    ) {
+        assert_eq!(expected, validate_query(input));
+    }
+
+    #[rstest]
+    #[case(
+        "select * from flights limit 50000",
+        QueryPolicy::default(),
+        Ok("SELECT * FROM flights LIMIT 10000".to_string())
+    )]
+    #[case(
+        "select * from flights fetch first 500 rows only",
+        QueryPolicy::default().with_max_limit(100),
+        Ok("SELECT * FROM flights FETCH FIRST 100 ROWS ONLY".to_string())
+    )]
+    #[case(
+        "select * from flights offset 5",
+        QueryPolicy::default().with_default_limit(20),
+        Ok("SELECT * FROM flights LIMIT 20 OFFSET 5".to_string())
+    )]
+    #[case(
+        "select * from flights limit (select 5)",
+        QueryPolicy::default(),
+        Err(QueryParserError::InvalidLimit)
+    )]
+    #[case(
+        "select flight_id, status from flights order by status",
+        QueryPolicy::default().with_columns("flights", &["flight_id", "status"]),
+        Ok("SELECT flight_id, status FROM flights ORDER BY status LIMIT 10".to_string())
+    )]
+    #[case(
+        "select flight_id as id from flights f order by id",
+        QueryPolicy::default().with_columns("flights", &["flight_id"]),
+        Ok("SELECT flight_id AS id FROM flights AS f ORDER BY id LIMIT 10".to_string())
+    )]
+    #[case(
+        "select * from flights",
+        QueryPolicy::default().with_columns("flights", &["flight_id"]),
+        Err(QueryParserError::ColumnNotAllowed("flights".to_string(), "*".to_string()))
+    )]
+    #[case(
+        "select f.* from flights f join seats s on true",
+        QueryPolicy::default().with_columns("flights", &["flight_id"]),
+        Err(QueryParserError::ColumnNotAllowed("flights".to_string(), "*".to_string()))
+    )]
+    #[case(
+        "select s.* from flights f join seats s on f.flight_id = s.seat_no",
+        QueryPolicy::default().with_columns("flights", &["flight_id"]),
+        Ok("SELECT s.* FROM flights AS f JOIN seats AS s ON f.flight_id = s.seat_no LIMIT 10".to_string())
+    )]
+    #[case(
+        "select flight_id from flights where scheduled_departure > now()",
+        QueryPolicy::default().with_columns("flights", &["flight_id"]),
+        Err(QueryParserError::ColumnNotAllowed("flights".to_string(), "scheduled_departure".to_string()))
+    )]
+    #[case(
+        "select flight_id from flights where flight_id in (select f.status from flights f)",
+        QueryPolicy::default().with_columns("flights", &["flight_id"]),
+        Err(QueryParserError::ColumnNotAllowed("flights".to_string(), "status".to_string()))
+    )]
+    #[case(
+        "select flight_id from flights where status in (select status from flights)",
+        QueryPolicy::default().with_columns("flights", &["flight_id"]),
+        Err(QueryParserError::ColumnNotAllowed("flights".to_string(), "status".to_string()))
+    )]
+    #[case(
+        "select pg_sleep(10) from flights",
+        QueryPolicy::default(),
+        Err(QueryParserError::FunctionNotAllowed("pg_sleep".to_string()))
+    )]
+    #[case(
+        "select * from flights where flight_id in (select PG_READ_FILE('x') from seats)",
+        QueryPolicy::default(),
+        Err(QueryParserError::FunctionNotAllowed("pg_read_file".to_string()))
+    )]
+    #[case(
+        "select query_to_xml('select * from pg_authid', true, true, '') from flights",
+        QueryPolicy::default(),
+        Err(QueryParserError::FunctionNotAllowed("query_to_xml".to_string()))
+    )]
+    #[case(
+        "select * from flights where flight_id = length(pg_catalog.table_to_xml('pg_authid', true, true, '')::text)",
+        QueryPolicy::default(),
+        Err(QueryParserError::FunctionNotAllowed("table_to_xml".to_string()))
+    )]
+    #[case(
+        "select setval('flights_flight_id_seq', 1) from flights",
+        QueryPolicy::default(),
+        Err(QueryParserError::FunctionNotAllowed("setval".to_string()))
+    )]
+    #[case(
+        "select lo_from_bytea(0, 'x') from flights",
+        QueryPolicy::default(),
+        Err(QueryParserError::FunctionNotAllowed("lo_from_bytea".to_string()))
+    )]
+    #[case(
+        "select pg_notify('channel', status) from flights",
+        QueryPolicy::default(),
+        Err(QueryParserError::FunctionNotAllowed("pg_notify".to_string()))
+    )]
+    #[case(
+        "select count(*), max(flight_id) from flights",
+        QueryPolicy::default().with_allowed_functions(&["COUNT"]),
+        Err(QueryParserError::FunctionNotAllowed("max".to_string()))
+    )]
+    #[case(
+        "select * from flights for update",
+        QueryPolicy::default(),
+        Err(QueryParserError::LockingClauseNotAllowed)
+    )]
+    #[case(
+        "select * from flights for share",
+        QueryPolicy::default().with_locking(true),
+        Ok("SELECT * FROM flights LIMIT 10 FOR SHARE".to_string())
+    )]
+    #[case(
+        "select * from flights f join ticket_flights tf on true, seats",
+        QueryPolicy::default().with_max_joins(1),
+        Err(QueryParserError::TooManyJoins(1))
+    )]
+    #[case(
+        "select * from flights where flight_id in (select f.flight_id from flights f join seats on true)",
+        QueryPolicy::default().with_max_joins(0),
+        Err(QueryParserError::TooManyJoins(0))
+    )]
+    #[case(
+        "select * from seats",
+        QueryPolicy::default().with_tables(&["flights"]),
+        Err(QueryParserError::InvalidTableName)
+    )]
+    #[case(
+        "select * from Bookings.flights f join seats on true",
+        QueryPolicy::default().with_schemas(&["bookings"]),
+        Ok("SELECT * FROM Bookings.flights AS f JOIN seats ON true LIMIT 10".to_string())
+    )]
+    #[case(
+        "select * from flights where flight_id in (select flight_id from public.flights)",
+        QueryPolicy::default().with_schemas(&["bookings"]),
+        Err(QueryParserError::SchemaNotAllowed("public".to_string()))
+    )]
+    #[case(
+        "select * from \"Bookings\".flights",
+        QueryPolicy::default().with_schemas(&["bookings"]),
+        Err(QueryParserError::SchemaNotAllowed("Bookings".to_string()))
+    )]
+    #[case(
+        "select * from bookings.flights",
+        QueryPolicy::default().with_schemas(&[]),
+        Err(QueryParserError::SchemaNotAllowed("bookings".to_string()))
+    )]
+    fn prepare_query_with_policy_test(
+        #[case] input: &str,
+        #[case] policy: QueryPolicy,
+        #[case] expected: Result<String, QueryParserError>,
+    ) {
+        assert_eq!(expected, prepare_query_with_policy(input, &policy));
+    }
+
+    #[rstest]
+    #[case(
+        "select * from flights",
+        "status = 'Arrived'",
+        Ok("SELECT * FROM flights WHERE status = 'Arrived'".to_string())
+    )]
+    #[case(
+        "select * from flights where flight_id < 10 or flight_id > 20",
+        "status in ('Arrived', 'Cancelled')",
+        Ok("SELECT * FROM flights WHERE (flight_id < 10 OR flight_id > 20) AND status IN ('Arrived', 'Cancelled')".to_string())
+    )]
+    #[case(
+        "select * from flights",
+        "true; drop table flights",
+        Err(QueryParserError::SqlParseError(ParserError::ParserError("Expected: EOF, found: ; at Line: 1, Column: 5".to_string())))
+    )]
+    #[case("select * from foo", "true", Err(QueryParserError::InvalidTableName))]
+    fn add_filter_test(
+        #[case] input: &str,
+        #[case] filter: &str,
+        #[case] expected: Result<String, QueryParserError>,
+    ) {
+        assert_eq!(expected, add_filter(input, filter));
+    }
+
+    #[rstest]
+    #[case("select * from flights", Ok("flights".to_string()))]
+    #[case("select * from bookings.seats limit 5", Ok("seats".to_string()))]
+    #[case(
+        "with f as (select * from flights) select * from f join seats on true",
+        Ok("flights".to_string())
+    )]
+    #[case("select * from foo", Err(QueryParserError::InvalidTableName))]
+    fn query_table_name_test(
+        #[case] input: &str,
+        #[case] expected: Result<String, QueryParserError>,
+    ) {
+        assert_eq!(expected, query_table_name(input));
+    }
+
+    #[rstest]
+    #[case(
+        "select * from bookings",
+        None,
+        Ok("SELECT * FROM bookings WHERE \"book_date\" <= '2017-08-15 15:00:00+00'".to_string())
+    )]
+    #[case(
+        "select * from bookings",
+        Some("2017-07-01 00:00:00+00"),
+        Ok("SELECT * FROM bookings WHERE \"book_date\" > '2017-07-01 00:00:00+00' AND \"book_date\" <= '2017-08-15 15:00:00+00'".to_string())
+    )]
+    #[case(
+        "select * from bookings where total_amount > 1000 or book_ref = 'A'",
+        Some("2017-07-01 00:00:00+00"),
+        Ok("SELECT * FROM bookings WHERE (total_amount > 1000 OR book_ref = 'A') AND \"book_date\" > '2017-07-01 00:00:00+00' AND \"book_date\" <= '2017-08-15 15:00:00+00'".to_string())
+    )]
+    #[case("select * from foo", None, Err(QueryParserError::InvalidTableName))]
+    fn add_watermark_filter_test(
+        #[case] input: &str,
+        #[case] low: Option<&str>,
+        #[case] expected: Result<String, QueryParserError>,
+    ) {
+        assert_eq!(
+            expected,
+            add_watermark_filter(input, "book_date", low, Some("2017-08-15 15:00:00+00"))
+        );
+    }
+
+    #[rstest]
+    #[case("book_date", Ok("SELECT * FROM bookings WHERE \"book_date\" > '2017-07-01'".to_string()))]
+    #[case(
+        "Book_Date",
+        Err(QueryParserError::UnknownColumn("bookings".to_string(), "Book_Date".to_string(), vec!["book_date".to_string()]))
+    )]
+    #[case(
+        "book_date > now() or true or book_date",
+        Err(QueryParserError::UnknownColumn("bookings".to_string(), "book_date > now() or true or book_date".to_string(), vec!["book_date".to_string()]))
+    )]
+    fn add_watermark_filter_column_test(
+        #[case] column: &str,
+        #[case] expected: Result<String, QueryParserError>,
+    ) {
+        assert_eq!(
+            expected,
+            add_watermark_filter("select * from bookings", column, Some("2017-07-01"), None)
+        );
+    }
 }
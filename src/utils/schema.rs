@@ -0,0 +1,357 @@
+use std::sync::Arc;
+
+use datafusion::arrow::array::{
+    ArrayRef, BooleanArray, Date32Array, Decimal128Array, Float32Array, Float64Array, Int16Array,
+    Int32Array, Int64Array, IntervalMonthDayNanoArray, ListArray, ListBuilder, RecordBatch,
+    StringArray, StringBuilder, TimestampMicrosecondArray,
+};
+use datafusion::arrow::datatypes::{
+    DataType, Date32Type, Field, Fields, Int32Type, IntervalMonthDayNano, IntervalUnit, Schema,
+    SchemaRef, TimeUnit,
+};
+use serde_json::Value;
+use sqlx::postgres::types::{PgInterval, PgPoint};
+use sqlx::types::chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use sqlx::types::Decimal;
+use sqlx::{postgres::PgRow, Column, Decode, FromRow, PgPool, Postgres, Row, Type, TypeInfo};
+use thiserror::Error;
+
+use super::constants::TIMESTAMP_TZ;
+use super::convert::{decimal_to_i128, point_struct_array, point_struct_type};
+use crate::{AppError, JsonLayout};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SchemaError {
+    #[error("Table not found: {0}")]
+    TableNotFound(String),
+
+    #[error("Unsupported postgres type {1} of column {0}")]
+    UnsupportedType(String, String),
+
+    #[error("Column not found in result: {0}")]
+    ColumnNotFound(String),
+}
+
+/// Column definition as reported by `information_schema.columns`.
+#[derive(Debug, Clone, PartialEq, FromRow)]
+pub struct PgColumn {
+    pub name: String,
+    pub udt_name: String,
+    pub is_nullable: bool,
+    pub numeric_precision: Option<i32>,
+    pub numeric_scale: Option<i32>,
+}
+
+impl PgColumn {
+    pub fn to_field(&self) -> Result<Field, SchemaError> {
+        let data_type =
+            pg_type_to_arrow(&self.udt_name, self.numeric_precision, self.numeric_scale)
+                .ok_or_else(|| {
+                    SchemaError::UnsupportedType(self.name.clone(), self.udt_name.clone())
+                })?;
+        Ok(Field::new(&self.name, data_type, self.is_nullable))
+    }
+}
+
+const COLUMNS_QUERY: &str = "select column_name::text as name, udt_name::text as udt_name, \
+    is_nullable = 'YES' as is_nullable, numeric_precision::int4 as numeric_precision, \
+    numeric_scale::int4 as numeric_scale \
+    from information_schema.columns \
+    where table_schema = coalesce($1, current_schema()) and table_name = $2 \
+    order by ordinal_position";
+
+/// Reads column definitions of a table or view, `schema_name` defaults to `current_schema()`.
+pub async fn fetch_columns(
+    pool: &PgPool,
+    schema_name: Option<&str>,
+    table_name: &str,
+) -> Result<Vec<PgColumn>, AppError> {
+    let columns = sqlx::query_as::<_, PgColumn>(COLUMNS_QUERY)
+        .bind(schema_name)
+        .bind(table_name)
+        .fetch_all(pool)
+        .await?;
+    if columns.is_empty() {
+        return Err(SchemaError::TableNotFound(table_name.to_string()).into());
+    }
+    Ok(columns)
+}
+
+pub fn columns_to_schema(columns: &[PgColumn]) -> Result<Schema, SchemaError> {
+    let fields = columns
+        .iter()
+        .map(PgColumn::to_field)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Schema::new(fields))
+}
+
+/// `schema` with its point columns laid out as `layout`, jsonb columns are JSON strings in
+/// either layout.
+pub fn schema_with_layout(schema: &Schema, layout: JsonLayout) -> Schema {
+    match layout {
+        JsonLayout::Struct => schema.clone(),
+        JsonLayout::JsonString => {
+            let fields = schema
+                .fields()
+                .iter()
+                .map(|field| {
+                    if field.data_type() == &point_struct_type() {
+                        Arc::new(field.as_ref().clone().with_data_type(DataType::Utf8))
+                    } else {
+                        field.clone()
+                    }
+                })
+                .collect::<Fields>();
+            Schema::new_with_metadata(fields, schema.metadata().clone())
+        }
+    }
+}
+
+/// Maps a postgres `udt_name` to the Arrow type the table workers use for it.
+pub fn pg_type_to_arrow(
+    udt_name: &str,
+    precision: Option<i32>,
+    scale: Option<i32>,
+) -> Option<DataType> {
+    let data_type = match udt_name {
+        "bool" => DataType::Boolean,
+        "int2" => DataType::Int16,
+        "int4" => DataType::Int32,
+        "int8" => DataType::Int64,
+        "float4" => DataType::Float32,
+        "float8" => DataType::Float64,
+        "text" | "varchar" | "bpchar" | "name" | "json" | "jsonb" => DataType::Utf8,
+        "numeric" => match (precision, scale) {
+            (Some(precision @ 1..=38), Some(scale @ 0..=38)) => {
+                DataType::Decimal128(precision as u8, scale as i8)
+            }
+            // unconstrained numeric, kept exact as text
+            _ => DataType::Utf8,
+        },
+        "timestamptz" => DataType::Timestamp(TimeUnit::Microsecond, Some(TIMESTAMP_TZ.into())),
+        "timestamp" => DataType::Timestamp(TimeUnit::Microsecond, None),
+        "date" => DataType::Date32,
+        "interval" => DataType::Interval(IntervalUnit::MonthDayNano),
+        "point" => point_struct_type(),
+        "_int4" => DataType::List(Arc::new(Field::new_list_field(DataType::Int32, true))),
+        "_text" | "_varchar" | "_bpchar" => {
+            DataType::List(Arc::new(Field::new_list_field(DataType::Utf8, true)))
+        }
+        _ => return None,
+    };
+    Some(data_type)
+}
+
+/// Decodes `rows` into a record batch of `schema`, matching result columns by field name.
+pub fn rows_to_record_batch(schema: SchemaRef, rows: &[PgRow]) -> Result<RecordBatch, AppError> {
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| column_to_array(field, rows))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+fn get_all<'r, T>(rows: &'r [PgRow], name: &str) -> Result<Vec<Option<T>>, sqlx::Error>
+where
+    T: Decode<'r, Postgres> + Type<Postgres>,
+{
+    rows.iter().map(|row| row.try_get(name)).collect()
+}
+
+fn column_to_array(field: &Field, rows: &[PgRow]) -> Result<ArrayRef, AppError> {
+    let name = field.name().as_str();
+    let Some(first) = rows.first() else {
+        return Ok(datafusion::arrow::array::new_empty_array(field.data_type()));
+    };
+    let pg_type = first
+        .try_column(name)
+        .map_err(|_| SchemaError::ColumnNotFound(name.to_string()))?
+        .type_info()
+        .name()
+        .to_string();
+
+    let array: ArrayRef = match field.data_type() {
+        DataType::Boolean => Arc::new(BooleanArray::from(get_all::<bool>(rows, name)?)),
+        DataType::Int16 => Arc::new(Int16Array::from(get_all::<i16>(rows, name)?)),
+        DataType::Int32 => Arc::new(Int32Array::from(get_all::<i32>(rows, name)?)),
+        DataType::Int64 => Arc::new(Int64Array::from(get_all::<i64>(rows, name)?)),
+        DataType::Float32 => Arc::new(Float32Array::from(get_all::<f32>(rows, name)?)),
+        DataType::Float64 => Arc::new(Float64Array::from(get_all::<f64>(rows, name)?)),
+        DataType::Utf8 => match pg_type.as_str() {
+            "JSON" | "JSONB" => {
+                let values = get_all::<Value>(rows, name)?
+                    .into_iter()
+                    .map(|val| val.map(|val| val.to_string()))
+                    .collect::<Vec<_>>();
+                Arc::new(StringArray::from(values))
+            }
+            "NUMERIC" => {
+                let values = get_all::<Decimal>(rows, name)?
+                    .into_iter()
+                    .map(|val| val.map(|val| val.to_string()))
+                    .collect::<Vec<_>>();
+                Arc::new(StringArray::from(values))
+            }
+            "POINT" => {
+                let values = get_all::<PgPoint>(rows, name)?
+                    .into_iter()
+                    .map(|val| val.map(|val| serde_json::json!({ "x": val.x, "y": val.y })))
+                    .map(|val| val.map(|val| val.to_string()))
+                    .collect::<Vec<_>>();
+                Arc::new(StringArray::from(values))
+            }
+            _ => Arc::new(StringArray::from(get_all::<String>(rows, name)?)),
+        },
+        DataType::Decimal128(precision, scale) => {
+            let values = get_all::<Decimal>(rows, name)?
+                .iter()
+                .map(|val| val.as_ref().map(|val| decimal_to_i128(val, *scale)))
+                .collect::<Vec<_>>();
+            Arc::new(Decimal128Array::from(values).with_precision_and_scale(*precision, *scale)?)
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, Some(tz)) => {
+            let values = get_all::<DateTime<Utc>>(rows, name)?
+                .into_iter()
+                .map(|val| val.map(|val| val.timestamp_micros()))
+                .collect::<Vec<_>>();
+            Arc::new(TimestampMicrosecondArray::from(values).with_timezone(tz.clone()))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, None) => {
+            let values = get_all::<NaiveDateTime>(rows, name)?
+                .into_iter()
+                .map(|val| val.map(|val| val.and_utc().timestamp_micros()))
+                .collect::<Vec<_>>();
+            Arc::new(TimestampMicrosecondArray::from(values))
+        }
+        DataType::Date32 => {
+            let values = get_all::<NaiveDate>(rows, name)?
+                .into_iter()
+                .map(|val| val.map(Date32Type::from_naive_date))
+                .collect::<Vec<_>>();
+            Arc::new(Date32Array::from(values))
+        }
+        DataType::Interval(IntervalUnit::MonthDayNano) => {
+            let values = get_all::<PgInterval>(rows, name)?
+                .into_iter()
+                .map(|val| {
+                    val.map(|val| {
+                        IntervalMonthDayNano::new(val.months, val.days, val.microseconds * 1_000)
+                    })
+                })
+                .collect::<Vec<_>>();
+            Arc::new(IntervalMonthDayNanoArray::from(values))
+        }
+        DataType::Struct(_) if field.data_type() == &point_struct_type() => {
+            let values = get_all::<PgPoint>(rows, name)?
+                .into_iter()
+                .map(|val| val.map(|val| (val.x, val.y)))
+                .collect::<Vec<_>>();
+            Arc::new(point_struct_array(&values)?)
+        }
+        DataType::List(item) if item.data_type() == &DataType::Int32 => {
+            let values = get_all::<Vec<i32>>(rows, name)?
+                .into_iter()
+                .map(|val| val.map(|val| val.into_iter().map(Some)));
+            Arc::new(ListArray::from_iter_primitive::<Int32Type, _, _>(values))
+        }
+        DataType::List(item) if item.data_type() == &DataType::Utf8 => {
+            let mut builder = ListBuilder::new(StringBuilder::new());
+            for val in get_all::<Vec<String>>(rows, name)? {
+                builder.append_option(val.map(|val| val.into_iter().map(Some)));
+            }
+            Arc::new(builder.finish())
+        }
+        data_type => {
+            return Err(
+                SchemaError::UnsupportedType(name.to_string(), data_type.to_string()).into(),
+            )
+        }
+    };
+    Ok(array)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("int4", None, None, Some(DataType::Int32))]
+    #[case("bpchar", None, None, Some(DataType::Utf8))]
+    #[case("jsonb", None, None, Some(DataType::Utf8))]
+    #[case("numeric", Some(10), Some(2), Some(DataType::Decimal128(10, 2)))]
+    #[case("numeric", None, None, Some(DataType::Utf8))]
+    #[case(
+        "timestamptz",
+        None,
+        None,
+        Some(DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())))
+    )]
+    #[case("point", None, None, Some(point_struct_type()))]
+    #[case(
+        "_int4",
+        None,
+        None,
+        Some(DataType::List(Arc::new(Field::new_list_field(DataType::Int32, true))))
+    )]
+    #[case("tsvector", None, None, None)]
+    fn pg_type_to_arrow_test(
+        #[case] udt_name: &str,
+        #[case] precision: Option<i32>,
+        #[case] scale: Option<i32>,
+        #[case] expected: Option<DataType>,
+    ) {
+        assert_eq!(expected, pg_type_to_arrow(udt_name, precision, scale));
+    }
+
+    #[rstest]
+    #[case(JsonLayout::Struct, point_struct_type())]
+    #[case(JsonLayout::JsonString, DataType::Utf8)]
+    fn schema_with_layout_test(#[case] layout: JsonLayout, #[case] expected: DataType) {
+        let schema = Schema::new(vec![
+            Field::new("airport_code", DataType::Utf8, false),
+            Field::new("coordinates", point_struct_type(), true),
+        ]);
+        let schema = schema_with_layout(&schema, layout);
+        assert_eq!(schema.field(0).data_type(), &DataType::Utf8);
+        assert_eq!(schema.field(1).data_type(), &expected);
+        assert!(schema.field(1).is_nullable());
+    }
+
+    #[test]
+    fn columns_to_schema_test() {
+        let columns = vec![
+            PgColumn {
+                name: "book_ref".to_string(),
+                udt_name: "bpchar".to_string(),
+                is_nullable: false,
+                numeric_precision: None,
+                numeric_scale: None,
+            },
+            PgColumn {
+                name: "fts".to_string(),
+                udt_name: "tsvector".to_string(),
+                is_nullable: true,
+                numeric_precision: None,
+                numeric_scale: None,
+            },
+        ];
+        assert_eq!(
+            Err(SchemaError::UnsupportedType(
+                "fts".to_string(),
+                "tsvector".to_string()
+            )),
+            columns_to_schema(&columns)
+        );
+        assert_eq!(
+            Ok(Schema::new(vec![Field::new(
+                "book_ref",
+                DataType::Utf8,
+                false
+            )])),
+            columns_to_schema(&columns[..1])
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use datafusion::prelude::SessionContext;
+use object_store::aws::AmazonS3Builder;
+use object_store::local::LocalFileSystem;
+use object_store::memory::InMemory;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use thiserror::Error;
+use url::Url;
+
+use crate::AppError;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum LakeStoreError {
+    #[error("Invalid lake url: {0}")]
+    InvalidUrl(String),
+
+    #[error("Unsupported lake url scheme: {0}")]
+    UnsupportedScheme(String),
+}
+
+/// Object store the lake is written to, with the URL prefix of the lake inside it.
+///
+/// `file:///data/lake` and plain local paths use the local filesystem, `memory://` keeps
+/// objects in memory for the lifetime of the store, and `s3://bucket/prefix` reads its
+/// credentials and endpoint from the usual `AWS_*` variables, e.g. `AWS_ENDPOINT` and
+/// `AWS_ALLOW_HTTP` for a local MinIO.
+#[derive(Debug, Clone)]
+pub struct LakeStore {
+    store: Arc<dyn ObjectStore>,
+    url: Url,
+    prefix: Path,
+}
+
+impl LakeStore {
+    pub fn from_url(url: &str) -> Result<Self, AppError> {
+        let url = parse_url(url)?;
+        let store: Arc<dyn ObjectStore> = match url.scheme() {
+            "file" => Arc::new(LocalFileSystem::new()),
+            "memory" => Arc::new(InMemory::new()),
+            "s3" => Arc::new(AmazonS3Builder::from_env().with_url(url.as_str()).build()?),
+            scheme => return Err(LakeStoreError::UnsupportedScheme(scheme.to_string()).into()),
+        };
+        Self::with_store(url.as_str(), store)
+    }
+
+    /// Uses an already configured store for `url`.
+    pub fn with_store(url: &str, store: Arc<dyn ObjectStore>) -> Result<Self, AppError> {
+        let url = parse_url(url)?;
+        let prefix = Path::from_url_path(url.path())?;
+        Ok(Self { store, url, prefix })
+    }
+
+    pub fn store(&self) -> Arc<dyn ObjectStore> {
+        self.store.clone()
+    }
+
+    /// Location of `path`, relative to the lake prefix, inside the store.
+    pub fn path(&self, path: &str) -> Path {
+        Path::from_iter(self.prefix.parts().chain(Path::from(path).parts()))
+    }
+
+    /// Full URL of `path`, as DataFusion expects it once the store is registered. A trailing
+    /// `/` is kept, so the path is listed as a directory.
+    pub fn url(&self, path: &str) -> String {
+        let mut url = self.url.clone();
+        let suffix = if path.ends_with('/') { "/" } else { "" };
+        url.set_path(&format!("/{}{}", self.path(path), suffix));
+        url.to_string()
+    }
+
+    /// Local directory or file of `path` when the lake is on the local filesystem.
+    pub fn local_path(&self, path: &str) -> Option<std::path::PathBuf> {
+        match self.url.scheme() {
+            "file" => Url::parse(&self.url(path)).ok()?.to_file_path().ok(),
+            _ => None,
+        }
+    }
+
+    /// Registers the store with `ctx`, so tables can be read back by their [`LakeStore::url`].
+    pub fn register(&self, ctx: &SessionContext) {
+        let mut base = self.url.clone();
+        base.set_path("/");
+        ctx.register_object_store(&base, self.store.clone());
+    }
+}
+
+// plain paths are taken as local directories
+fn parse_url(url: &str) -> Result<Url, LakeStoreError> {
+    let invalid = || LakeStoreError::InvalidUrl(url.to_string());
+    match Url::parse(url) {
+        Ok(url) => Ok(url),
+        Err(url::ParseError::RelativeUrlWithoutBase) => {
+            let path = std::path::absolute(url).map_err(|_| invalid())?;
+            Url::from_directory_path(path).map_err(|_| invalid())
+        }
+        Err(_) => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(
+        "memory://",
+        "bookings/part-0.parquet",
+        "bookings/part-0.parquet",
+        "memory:///bookings/part-0.parquet"
+    )]
+    #[case(
+        "memory:///lake/",
+        "flights.parquet",
+        "lake/flights.parquet",
+        "memory:///lake/flights.parquet"
+    )]
+    #[case(
+        "memory:///lake",
+        "flights/",
+        "lake/flights",
+        "memory:///lake/flights/"
+    )]
+    #[case(
+        "file:///tmp/lake",
+        "seats.parquet",
+        "tmp/lake/seats.parquet",
+        "file:///tmp/lake/seats.parquet"
+    )]
+    fn lake_store_path_test(
+        #[case] url: &str,
+        #[case] path: &str,
+        #[case] expected_path: &str,
+        #[case] expected_url: &str,
+    ) {
+        let store = LakeStore::from_url(url).unwrap();
+        assert_eq!(store.path(path).as_ref(), expected_path);
+        assert_eq!(store.url(path), expected_url);
+    }
+
+    #[rstest]
+    #[case("file:///tmp/lake", "flights/", Some("/tmp/lake/flights"))]
+    #[case("memory:///lake", "flights/", None)]
+    fn local_path_test(#[case] url: &str, #[case] path: &str, #[case] expected: Option<&str>) {
+        let store = LakeStore::from_url(url).unwrap();
+        assert_eq!(
+            store.local_path(path),
+            expected.map(std::path::PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn lake_store_url_test() {
+        let store = LakeStore::from_url("/tmp/lake").unwrap();
+        assert_eq!(store.url("seats.parquet"), "file:///tmp/lake/seats.parquet");
+        assert!(matches!(
+            LakeStore::from_url("ftp://host/lake"),
+            Err(AppError::LakeStoreError(LakeStoreError::UnsupportedScheme(scheme))) if scheme == "ftp"
+        ));
+    }
+}
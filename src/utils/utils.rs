@@ -1,41 +1,280 @@
-use std::io::Cursor;
+use std::path::Path;
 
-use datafusion::{arrow::datatypes::Schema, parquet::arrow::AsyncArrowWriter, prelude::*};
-use futures_util::TryStreamExt;
-use parquet::arrow::ParquetRecordBatchStreamBuilder;
-use tokio::{
-    fs::File,
-    io::{AsyncReadExt, AsyncWriteExt},
+use datafusion::{
+    error::DataFusionError,
+    parquet::arrow::AsyncArrowWriter,
+    physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+    prelude::*,
 };
+use futures_util::TryStreamExt;
+use object_store::buffered::BufReader;
+use parquet::arrow::{async_reader::AsyncFileReader, ParquetRecordBatchStreamBuilder};
+use tokio::{fs::File, io::AsyncWrite};
 use tokio_stream::StreamExt;
 
+use super::format::{write_stream_to_file_as, write_stream_to_store_as, OutputFormat};
+use super::store::LakeStore;
+use super::write_options::ParquetWriteOptions;
 use crate::AppError;
 
 pub async fn write_df_to_file(df: DataFrame, file_path: &str) -> Result<(), AppError> {
-    let mut buf = vec![];
-    let schema = Schema::from(df.clone().schema());
-    let mut stream = df.execute_stream().await?;
-    let mut writer = AsyncArrowWriter::try_new(&mut buf, schema.into(), None)?;
+    write_df_to_file_with(df, file_path, &ParquetWriteOptions::default()).await
+}
+
+pub async fn write_df_to_file_with(
+    df: DataFrame,
+    file_path: &str,
+    options: &ParquetWriteOptions,
+) -> Result<(), AppError> {
+    let stream = df.execute_stream().await?;
+    write_stream_to_file_with(stream, file_path, options).await
+}
+
+pub async fn write_stream_to_file(
+    stream: SendableRecordBatchStream,
+    file_path: &str,
+) -> Result<(), AppError> {
+    write_stream_to_file_with(stream, file_path, &ParquetWriteOptions::default()).await
+}
+
+pub async fn write_stream_to_file_with(
+    stream: SendableRecordBatchStream,
+    file_path: &str,
+    options: &ParquetWriteOptions,
+) -> Result<(), AppError> {
+    let format = OutputFormat::Parquet(options.clone());
+    write_stream_to_file_as(stream, file_path, &format).await
+}
+
+pub async fn write_df_to_writer<W: AsyncWrite + Unpin + Send>(
+    df: DataFrame,
+    writer: W,
+    options: &ParquetWriteOptions,
+) -> Result<(), AppError> {
+    let stream = df.execute_stream().await?;
+    write_stream_to_writer(stream, writer, options).await
+}
+
+/// Writes the batches as Parquet to any async sink one at a time, only the row group in
+/// progress is held in memory.
+pub async fn write_stream_to_writer<W: AsyncWrite + Unpin + Send>(
+    mut stream: SendableRecordBatchStream,
+    writer: W,
+    options: &ParquetWriteOptions,
+) -> Result<(), AppError> {
+    let props = options.writer_properties()?;
+    let mut writer = AsyncArrowWriter::try_new(writer, stream.schema(), Some(props))?;
     while let Some(batch) = stream.next().await.transpose()? {
         writer.write(&batch).await?;
     }
     writer.close().await?;
 
-    let mut file = File::create(file_path).await?;
-    file.write_all(&buf).await?;
-
     Ok(())
 }
 
+/// Returns a dataframe that scans the file when executed instead of loading it up front.
 pub async fn read_file_to_df(file_path: &str) -> Result<DataFrame, AppError> {
-    let mut buf = vec![];
-    let _n = File::open(file_path).await?.read_to_end(&mut buf).await?;
-    let stream = ParquetRecordBatchStreamBuilder::new(Cursor::new(buf))
-        .await?
-        .build()?;
-    let batches = stream.try_collect::<Vec<_>>().await?;
+    let extension = Path::new(file_path)
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let options = ParquetReadOptions {
+        file_extension: &extension,
+        ..Default::default()
+    };
     let ctx = SessionContext::new();
-    let df = ctx.read_batches(batches)?;
+    let df = ctx.read_parquet(file_path, options).await?;
+
+    Ok(df)
+}
+
+pub async fn read_file_to_stream(file_path: &str) -> Result<SendableRecordBatchStream, AppError> {
+    let file = File::open(file_path).await?;
+    read_to_stream(file).await
+}
+
+/// Streams the record batches of a Parquet source such as a `tokio::fs::File`.
+pub async fn read_to_stream<R: AsyncFileReader + Unpin + Send + 'static>(
+    reader: R,
+) -> Result<SendableRecordBatchStream, AppError> {
+    let builder = ParquetRecordBatchStreamBuilder::new(reader).await?;
+    let schema = builder.schema().clone();
+    let stream = builder.build()?.map_err(DataFusionError::from);
+    Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+}
+
+pub async fn write_df_to_store(
+    df: DataFrame,
+    store: &LakeStore,
+    path: &str,
+    options: &ParquetWriteOptions,
+) -> Result<(), AppError> {
+    let stream = df.execute_stream().await?;
+    write_stream_to_store(stream, store, path, options).await
+}
+
+pub async fn write_stream_to_store(
+    stream: SendableRecordBatchStream,
+    store: &LakeStore,
+    path: &str,
+    options: &ParquetWriteOptions,
+) -> Result<(), AppError> {
+    let format = OutputFormat::Parquet(options.clone());
+    write_stream_to_store_as(stream, store, path, &format).await
+}
+
+/// Returns a dataframe scanning `path` of the lake store, registering the store with `ctx`.
+pub async fn read_store_to_df(
+    ctx: &SessionContext,
+    store: &LakeStore,
+    path: &str,
+) -> Result<DataFrame, AppError> {
+    store.register(ctx);
+    let extension = Path::new(path)
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let options = ParquetReadOptions {
+        file_extension: &extension,
+        ..Default::default()
+    };
+    let df = ctx.read_parquet(store.url(path), options).await?;
 
     Ok(df)
 }
+
+pub async fn read_store_to_stream(
+    store: &LakeStore,
+    path: &str,
+) -> Result<SendableRecordBatchStream, AppError> {
+    let meta = store.store().head(&store.path(path)).await?;
+    read_to_stream(BufReader::new(store.store(), &meta)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{Int32Array, RecordBatch};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::physical_plan::stream::RecordBatchReceiverStream;
+    use parquet::basic::Compression;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    use super::*;
+
+    fn ranges() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "range",
+            DataType::Int32,
+            false,
+        )]));
+        RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int32Array::from(vec![11100, 7700, 5600]))],
+        )
+        .unwrap()
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("{}_{}.parquet", name, std::process::id()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[tokio::test]
+    async fn write_df_to_file_with_test() {
+        let df = SessionContext::new().read_batch(ranges()).unwrap();
+        let path = temp_path("write_options");
+        let options = ParquetWriteOptions::new()
+            .with_compression("snappy")
+            .with_max_row_group_size(2)
+            .with_key_value_metadata("source", "demo");
+        write_df_to_file_with(df, &path, &options).await.unwrap();
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.num_row_groups(), 2);
+        assert_eq!(
+            metadata.row_group(0).column(0).compression(),
+            Compression::SNAPPY
+        );
+        let kv = metadata.file_metadata().key_value_metadata().unwrap();
+        assert!(kv
+            .iter()
+            .any(|kv| kv.key == "source" && kv.value.as_deref() == Some("demo")));
+    }
+
+    #[tokio::test]
+    async fn write_stream_to_file_failure_test() {
+        let batch = ranges();
+        let mut builder = RecordBatchReceiverStream::builder(batch.schema(), 2);
+        let tx = builder.tx();
+        builder.spawn(async move {
+            tx.send(Ok(batch)).await.unwrap();
+            tx.send(Err(DataFusionError::Execution(
+                "connection lost".to_string(),
+            )))
+            .await
+            .unwrap();
+            Ok(())
+        });
+        let path = temp_path("write_failure");
+        let res = write_stream_to_file(builder.build(), &path).await;
+
+        assert!(res.is_err());
+        assert!(!Path::new(&path).exists());
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+    }
+
+    #[tokio::test]
+    async fn read_file_to_stream_test() {
+        let mut buf = vec![];
+        let df = SessionContext::new().read_batch(ranges()).unwrap();
+        write_df_to_writer(df, &mut buf, &ParquetWriteOptions::default())
+            .await
+            .unwrap();
+        let path = temp_path("read_stream");
+        tokio::fs::write(&path, &buf).await.unwrap();
+
+        let batches = read_file_to_stream(&path)
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let rows = read_file_to_df(&path).await.unwrap().count().await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(batches, vec![ranges()]);
+        assert_eq!(rows, 3);
+    }
+
+    #[tokio::test]
+    async fn store_roundtrip_test() {
+        let store = LakeStore::from_url("memory:///lake").unwrap();
+        let df = SessionContext::new().read_batch(ranges()).unwrap();
+        let options = ParquetWriteOptions::new().with_compression("zstd(1)");
+        write_df_to_store(df, &store, "aircrafts/part-00000.parquet", &options)
+            .await
+            .unwrap();
+
+        let batches = read_store_to_stream(&store, "aircrafts/part-00000.parquet")
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(batches, vec![ranges()]);
+
+        let ctx = SessionContext::new();
+        let df = read_store_to_df(&ctx, &store, "aircrafts/").await.unwrap();
+        assert_eq!(df.count().await.unwrap(), 3);
+        assert!(read_store_to_stream(&store, "missing.parquet")
+            .await
+            .is_err());
+    }
+}
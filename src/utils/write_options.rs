@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use parquet::basic::Compression;
+use parquet::errors::ParquetError;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::{EnabledStatistics, WriterProperties};
+use parquet::schema::types::ColumnPath;
+use serde::{Deserialize, Serialize};
+
+/// Granularity of the min/max statistics written to Parquet files.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatisticsLevel {
+    None,
+    Chunk,
+    Page,
+}
+
+impl From<StatisticsLevel> for EnabledStatistics {
+    fn from(level: StatisticsLevel) -> Self {
+        match level {
+            StatisticsLevel::None => EnabledStatistics::None,
+            StatisticsLevel::Chunk => EnabledStatistics::Chunk,
+            StatisticsLevel::Page => EnabledStatistics::Page,
+        }
+    }
+}
+
+/// Parquet writer settings, unset values keep the `parquet` crate defaults.
+///
+/// Codecs are given as `snappy`, `lz4_raw`, `gzip(6)`, `zstd(3)` and so on, column paths of
+/// nested fields are dot separated, e.g. `contact_data.email`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParquetWriteOptions {
+    compression: Option<String>,
+    column_compression: BTreeMap<String, String>,
+    max_row_group_size: Option<usize>,
+    dictionary_enabled: Option<bool>,
+    data_page_size_limit: Option<usize>,
+    statistics: Option<StatisticsLevel>,
+    created_by: Option<String>,
+    key_value_metadata: BTreeMap<String, String>,
+}
+
+impl ParquetWriteOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_compression(self, codec: &str) -> Self {
+        Self {
+            compression: Some(codec.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_column_compression(mut self, column: &str, codec: &str) -> Self {
+        self.column_compression
+            .insert(column.to_string(), codec.to_string());
+        self
+    }
+
+    pub fn with_max_row_group_size(self, max_row_group_size: usize) -> Self {
+        Self {
+            max_row_group_size: Some(max_row_group_size),
+            ..self
+        }
+    }
+
+    pub fn with_dictionary_enabled(self, dictionary_enabled: bool) -> Self {
+        Self {
+            dictionary_enabled: Some(dictionary_enabled),
+            ..self
+        }
+    }
+
+    pub fn with_data_page_size_limit(self, data_page_size_limit: usize) -> Self {
+        Self {
+            data_page_size_limit: Some(data_page_size_limit),
+            ..self
+        }
+    }
+
+    pub fn with_statistics(self, statistics: StatisticsLevel) -> Self {
+        Self {
+            statistics: Some(statistics),
+            ..self
+        }
+    }
+
+    pub fn with_created_by(self, created_by: &str) -> Self {
+        Self {
+            created_by: Some(created_by.to_string()),
+            ..self
+        }
+    }
+
+    pub fn with_key_value_metadata(mut self, key: &str, value: &str) -> Self {
+        self.key_value_metadata
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Builds the writer properties, failing on an unknown codec, invalid level or zero size.
+    pub fn writer_properties(&self) -> Result<WriterProperties, ParquetError> {
+        let mut builder = WriterProperties::builder();
+        if let Some(codec) = &self.compression {
+            builder = builder.set_compression(Compression::from_str(codec)?);
+        }
+        for (column, codec) in &self.column_compression {
+            let path = ColumnPath::new(column.split('.').map(String::from).collect());
+            builder = builder.set_column_compression(path, Compression::from_str(codec)?);
+        }
+        if let Some(max_row_group_size) = self.max_row_group_size {
+            if max_row_group_size == 0 {
+                return Err(ParquetError::General(
+                    "max_row_group_size must be greater than 0".to_string(),
+                ));
+            }
+            builder = builder.set_max_row_group_size(max_row_group_size);
+        }
+        if let Some(dictionary_enabled) = self.dictionary_enabled {
+            builder = builder.set_dictionary_enabled(dictionary_enabled);
+        }
+        if let Some(data_page_size_limit) = self.data_page_size_limit {
+            if data_page_size_limit == 0 {
+                return Err(ParquetError::General(
+                    "data_page_size_limit must be greater than 0".to_string(),
+                ));
+            }
+            builder = builder.set_data_page_size_limit(data_page_size_limit);
+        }
+        if let Some(statistics) = self.statistics {
+            builder = builder.set_statistics_enabled(statistics.into());
+        }
+        if let Some(created_by) = &self.created_by {
+            builder = builder.set_created_by(created_by.clone());
+        }
+        if !self.key_value_metadata.is_empty() {
+            let metadata = self
+                .key_value_metadata
+                .iter()
+                .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+                .collect();
+            builder = builder.set_key_value_metadata(Some(metadata));
+        }
+        Ok(builder.build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parquet::basic::ZstdLevel;
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn writer_properties_test() {
+        let options = ParquetWriteOptions::new()
+            .with_compression("zstd(3)")
+            .with_column_compression("contact_data.email", "snappy")
+            .with_max_row_group_size(1024)
+            .with_dictionary_enabled(false)
+            .with_statistics(StatisticsLevel::Chunk)
+            .with_created_by("demodb-to-datalake")
+            .with_key_value_metadata("source", "demo");
+        let props = options.writer_properties().unwrap();
+        let column = ColumnPath::from("book_ref");
+        let nested = ColumnPath::new(vec!["contact_data".to_string(), "email".to_string()]);
+
+        assert_eq!(
+            props.compression(&column),
+            Compression::ZSTD(ZstdLevel::try_new(3).unwrap())
+        );
+        assert_eq!(props.compression(&nested), Compression::SNAPPY);
+        assert_eq!(props.max_row_group_size(), 1024);
+        assert!(!props.dictionary_enabled(&column));
+        assert_eq!(props.statistics_enabled(&column), EnabledStatistics::Chunk);
+        assert_eq!(props.created_by(), "demodb-to-datalake");
+        assert_eq!(
+            props.key_value_metadata(),
+            Some(&vec![KeyValue::new(
+                "source".to_string(),
+                "demo".to_string()
+            )])
+        );
+    }
+
+    #[rstest]
+    #[case("zstd(30)")]
+    #[case("gzip")]
+    #[case("foo")]
+    fn writer_properties_invalid_codec_test(#[case] codec: &str) {
+        let options = ParquetWriteOptions::new().with_compression(codec);
+        assert!(options.writer_properties().is_err());
+    }
+
+    #[rstest]
+    #[case(ParquetWriteOptions::new().with_max_row_group_size(0), "max_row_group_size")]
+    #[case(ParquetWriteOptions::new().with_data_page_size_limit(0), "data_page_size_limit")]
+    fn writer_properties_zero_size_test(
+        #[case] options: ParquetWriteOptions,
+        #[case] option: &str,
+    ) {
+        match options.writer_properties() {
+            Err(ParquetError::General(message)) => assert!(message.starts_with(option)),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn deserialize_test() {
+        let json = r#"{
+            "compression": "snappy",
+            "max_row_group_size": 65536,
+            "statistics": "page",
+            "key_value_metadata": {"source": "demo"}
+        }"#;
+        let options = serde_json::from_str::<ParquetWriteOptions>(json).unwrap();
+        assert_eq!(
+            options,
+            ParquetWriteOptions::new()
+                .with_compression("snappy")
+                .with_max_row_group_size(65536)
+                .with_statistics(StatisticsLevel::Page)
+                .with_key_value_metadata("source", "demo")
+        );
+        assert!(serde_json::from_str::<ParquetWriteOptions>(r#"{"codec": "snappy"}"#).is_err());
+    }
+}
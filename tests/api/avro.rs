@@ -0,0 +1,62 @@
+use demodb_to_datalake::{
+    write_avro_schema_to_file, write_df_to_file_as, AirportsData, AvroCodec, AvroOptions,
+    OutputFormat, PostgresDb, Table, DATABASE_URL, MAX_DB_CONS,
+};
+
+use apache_avro::Reader;
+use color_eyre::Result;
+use datafusion::prelude::*;
+use secrecy::ExposeSecret;
+
+#[tokio::test]
+async fn test_avro_airports_data() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let ctx = SessionContext::new();
+    let df = Table::AirportsDataTable
+        .run_query_table_to_df(db.as_ref(), "select * from airports_data", &ctx)
+        .await?;
+
+    let rows = df.clone().count().await?;
+
+    let dir = std::env::temp_dir().join(format!("avro_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let options = AvroOptions::new()
+        .with_codec(AvroCodec::Deflate)
+        .with_name("airports_data")
+        .with_namespace("demodb.bookings");
+    let format = OutputFormat::Avro(options.clone());
+    let data_path = dir.join(format!("airports_data.{}", format.extension()));
+    let schema_path = dir.join("airports_data.avsc");
+    write_df_to_file_as(df, data_path.to_str().unwrap(), &format).await?;
+    write_avro_schema_to_file(
+        &AirportsData::schema(),
+        &options,
+        schema_path.to_str().unwrap(),
+    )
+    .await?;
+    let data = std::fs::read(&data_path)?;
+    let schema: serde_json::Value = serde_json::from_slice(&std::fs::read(&schema_path)?)?;
+    std::fs::remove_dir_all(&dir)?;
+
+    let field_names = schema["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    let expected = AirportsData::schema()
+        .fields()
+        .iter()
+        .map(|field| field.name().clone())
+        .collect::<Vec<_>>();
+    assert_eq!(field_names, expected);
+    assert_eq!(schema, options.avro_schema(&AirportsData::schema())?);
+    assert_eq!(&data[..4], b"Obj\x01");
+    assert!(data.windows(7).any(|window| window == b"deflate"));
+    assert_eq!(Reader::new(&data[..])?.count(), rows);
+    Ok(())
+}
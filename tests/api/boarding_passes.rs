@@ -2,6 +2,7 @@ use demodb_to_datalake::{PostgresDb, Table, DATABASE_URL, MAX_DB_CONS};
 
 use color_eyre::Result;
 use datafusion::{assert_batches_eq, prelude::*};
+use futures_util::TryStreamExt;
 use secrecy::ExposeSecret;
 
 const TABLE: Table = Table::BoardingPassesTable;
@@ -122,6 +123,25 @@ mod dyn_trait {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_boarding_passes_stream() -> Result<()> {
+        let db = PostgresDb::builder()
+            .with_url(DATABASE_URL.expose_secret())
+            .with_max_cons(MAX_DB_CONS)
+            .build()
+            .await?;
+        let table = TABLE;
+        let worker = table.to_worker();
+        let query = format!("select * from {}", table.as_ref());
+        let stream = worker.query_table_to_stream(db.as_ref(), &query, 4).await?;
+        let batches = stream.try_collect::<Vec<_>>().await?;
+
+        assert_eq!(batches.len(), 3); // batches count
+        assert!(batches.iter().all(|batch| batch.num_rows() <= 4));
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10); // rows count
+        Ok(())
+    }
 }
 
 mod stat_trait {
@@ -238,4 +258,24 @@ mod stat_trait {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_boarding_passes_stream() -> Result<()> {
+        let db = PostgresDb::builder()
+            .with_url(DATABASE_URL.expose_secret())
+            .with_max_cons(MAX_DB_CONS)
+            .build()
+            .await?;
+        let table = TABLE;
+        let query = format!("select * from {}", table.as_ref());
+        let stream = table
+            .run_query_table_to_stream(db.as_ref(), &query, 4)
+            .await?;
+        let batches = stream.try_collect::<Vec<_>>().await?;
+
+        assert_eq!(batches.len(), 3); // batches count
+        assert!(batches.iter().all(|batch| batch.num_rows() <= 4));
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10); // rows count
+        Ok(())
+    }
 }
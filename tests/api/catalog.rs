@@ -0,0 +1,103 @@
+use demodb_to_datalake::{
+    write_df_to_hive_dir, write_stream_to_file, DemoDbCatalog, LakeStore, PartitionColumn,
+    PostgresDb, Table, DATABASE_URL, DEFAULT_BATCH_SIZE, MAX_DB_CONS,
+};
+
+use color_eyre::Result;
+use datafusion::arrow::array::AsArray;
+use datafusion::arrow::datatypes::Int64Type;
+use datafusion::prelude::*;
+use secrecy::ExposeSecret;
+
+const JOIN_QUERY: &str = "select count(*) from demodb.bookings.flights f \
+    join demodb.bookings.ticket_flights tf on f.flight_id = tf.flight_id \
+    where tf.fare_conditions = 'Economy'";
+
+async fn join_count(ctx: &SessionContext) -> Result<i64> {
+    let batches = ctx.sql(JOIN_QUERY).await?.collect().await?;
+    Ok(batches[0].column(0).as_primitive::<Int64Type>().value(0))
+}
+
+#[tokio::test]
+async fn test_catalog_postgres() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let expected: i64 = sqlx::query_scalar(&JOIN_QUERY.replace("demodb.bookings.", ""))
+        .fetch_one(db.as_ref())
+        .await?;
+    let ctx = DemoDbCatalog::postgres(db.as_ref()).session_context();
+    assert_eq!(join_count(&ctx).await?, expected);
+
+    let tables = ctx
+        .catalog("demodb")
+        .and_then(|catalog| catalog.schema("bookings"))
+        .map(|schema| schema.table_names())
+        .unwrap_or_default();
+    assert_eq!(tables.len(), 8);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_catalog_lake() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let expected: i64 = sqlx::query_scalar(&JOIN_QUERY.replace("demodb.bookings.", ""))
+        .fetch_one(db.as_ref())
+        .await?;
+
+    let dir = std::env::temp_dir().join(format!("catalog_lake_{}", std::process::id()));
+    tokio::fs::create_dir_all(&dir).await?;
+    let stream = Table::FlightsTable
+        .run_export_table_to_stream(db.as_ref(), "select * from flights", DEFAULT_BATCH_SIZE)
+        .await?;
+    write_stream_to_file(stream, dir.join("flights.parquet").to_str().unwrap()).await?;
+    let df = Table::TicketFlightsTable
+        .run_query_table_to_df(
+            db.as_ref(),
+            "select * from ticket_flights limit 1000",
+            &SessionContext::new(),
+        )
+        .await?;
+    let partition_by = [PartitionColumn::new("fare_conditions")];
+    write_df_to_hive_dir(
+        df,
+        dir.join("ticket_flights").to_str().unwrap(),
+        &partition_by,
+        5,
+    )
+    .await?;
+
+    let store = LakeStore::from_url(dir.to_str().unwrap())?;
+    let ctx = DemoDbCatalog::lake(store).await?.session_context();
+    assert_eq!(join_count(&ctx).await?, expected);
+
+    let schema = ctx
+        .catalog("demodb")
+        .and_then(|catalog| catalog.schema("bookings"))
+        .unwrap();
+    assert_eq!(schema.table_names(), vec!["flights", "ticket_flights"]);
+    assert!(!schema.table_exist("seats"));
+    let res = ctx.sql("select * from demodb.bookings.seats").await;
+    assert!(res.is_err());
+
+    // a table exported after the lookup is found on first use
+    let stream = Table::SeatsTable
+        .run_export_table_to_stream(db.as_ref(), "select * from seats", DEFAULT_BATCH_SIZE)
+        .await?;
+    write_stream_to_file(stream, dir.join("seats.parquet").to_str().unwrap()).await?;
+    let seats: i64 = sqlx::query_scalar("select count(*) from seats")
+        .fetch_one(db.as_ref())
+        .await?;
+    let df = ctx.sql("select * from demodb.bookings.seats").await?;
+    assert_eq!(df.count().await?, seats as usize);
+    assert!(schema.table_exist("seats"));
+
+    tokio::fs::remove_dir_all(&dir).await?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use std::process::Command;
+
+use demodb_to_datalake::{PostgresDb, ALL_TABLE_NAMES, DATABASE_URL};
+
+use color_eyre::Result;
+use secrecy::ExposeSecret;
+
+fn cli() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_demodb-to-datalake"));
+    command.env("DATABASE_URL", DATABASE_URL.expose_secret());
+    command
+}
+
+#[test]
+fn test_cli_list_tables() -> Result<()> {
+    let output = cli().arg("list-tables").output()?;
+    let tables = String::from_utf8(output.stdout)?;
+
+    assert!(output.status.success());
+    assert_eq!(tables.lines().collect::<Vec<_>>(), ALL_TABLE_NAMES);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_export_and_inspect() -> Result<()> {
+    let query = "select * from seats where fare_conditions <> 'Economy'";
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .build()
+        .await?;
+    let expected = sqlx::query_scalar::<_, i64>(&query.replace('*', "count(*)"))
+        .fetch_one(db.as_ref())
+        .await?;
+
+    let dir = std::env::temp_dir().join(format!("cli_{}", std::process::id()));
+    let export = cli()
+        .args(["export", "--table", "seats", "--backend", "copy-binary"])
+        .args(["--query", query])
+        .arg("--out")
+        .arg(&dir)
+        .output()?;
+    let inspect = cli()
+        .arg("inspect")
+        .arg(dir.join("seats.parquet"))
+        .output()?;
+    let csv = cli()
+        .args(["export", "--table", "seats", "--format", "csv", "--out"])
+        .arg(&dir)
+        .output()?;
+    let csv_exists = dir.join("seats.csv").exists();
+    std::fs::remove_dir_all(&dir)?;
+
+    let metadata = String::from_utf8(inspect.stdout)?;
+    assert!(export.status.success());
+    assert!(inspect.status.success());
+    assert!(metadata.contains(&format!("rows: {}", expected)));
+    assert!(metadata.contains("  seat_no: Utf8"));
+    assert!(csv.status.success());
+    assert!(csv_exists);
+    Ok(())
+}
+
+#[test]
+fn test_cli_exit_codes() -> Result<()> {
+    let unknown_table = cli().args(["export", "--table", "foo"]).output()?;
+    let invalid_query = cli().args(["query", "delete from seats"]).output()?;
+    // queries are checked before connecting
+    let invalid_export_query = cli()
+        .env("DATABASE_URL", "postgres://postgres@127.0.0.1:1/demo")
+        .args([
+            "export",
+            "--table",
+            "seats",
+            "--query",
+            "select * from flights",
+        ])
+        .output()?;
+    let missing_file = cli().args(["inspect", "missing.parquet"]).output()?;
+    let missing_url = cli()
+        .env_remove("DATABASE_URL")
+        .current_dir(std::env::temp_dir())
+        .args(["export", "--table", "seats"])
+        .output()?;
+
+    assert_eq!(unknown_table.status.code(), Some(2));
+    assert_eq!(invalid_query.status.code(), Some(64));
+    assert_eq!(invalid_export_query.status.code(), Some(64));
+    assert_eq!(missing_file.status.code(), Some(74));
+    assert_eq!(missing_url.status.code(), Some(2));
+    Ok(())
+}
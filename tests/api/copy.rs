@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use demodb_to_datalake::{
+    AirportsData, ExtractBackend, GenericTableWorker, JsonLayout, PartitionKey,
+    PartitionedExtractor, PostgresDb, Table, TableWorkerDyn, ALL_TABLE_NAMES, DATABASE_URL,
+    MAX_DB_CONS,
+};
+
+use color_eyre::Result;
+use datafusion::arrow::array::RecordBatch;
+use datafusion::arrow::compute::concat_batches;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use datafusion::prelude::*;
+use futures_util::TryStreamExt;
+use secrecy::ExposeSecret;
+
+async fn collect(stream: SendableRecordBatchStream) -> Result<RecordBatch> {
+    let schema = stream.schema();
+    let batches = stream.try_collect::<Vec<_>>().await?;
+    Ok(concat_batches(&schema, &batches)?)
+}
+
+#[tokio::test]
+async fn test_copy_matches_query() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    for name in ALL_TABLE_NAMES {
+        let table = Table::new(name).unwrap();
+        let query = format!("select * from {} order by 1, 2", table.as_ref());
+        let expected = collect(
+            table
+                .run_export_table_to_stream(db.as_ref(), &query, 7)
+                .await?,
+        )
+        .await?;
+        let res = collect(
+            table
+                .run_copy_table_to_stream(db.as_ref(), &query, 7)
+                .await?,
+        )
+        .await?;
+        assert_eq!(expected, res, "table: {}", name);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_copy_json_layout() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let query = "select * from airports_data order by airport_code";
+    let mut batches = vec![];
+    for backend in [ExtractBackend::Query, ExtractBackend::CopyBinary] {
+        let stream = Table::AirportsDataTable
+            .run_extract_to_stream(db.as_ref(), query, 7, backend, JsonLayout::JsonString)
+            .await?;
+        batches.push(collect(stream).await?);
+    }
+    let schema = batches[0].schema();
+    assert_eq!(*schema, AirportsData::schema_with(JsonLayout::JsonString));
+    for field in ["airport_name", "city", "coordinates"] {
+        assert_eq!(
+            schema.field_with_name(field)?.data_type(),
+            &DataType::Utf8,
+            "field: {}",
+            field
+        );
+    }
+    assert_eq!(batches[0], batches[1]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_copy_generic() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let worker = GenericTableWorker::new("airports_data");
+    let query = "select * from airports_data order by airport_code";
+    let expected = collect(worker.export_table_to_stream(db.as_ref(), query, 3).await?).await?;
+    let res = collect(worker.copy_table_to_stream(db.as_ref(), query, 3).await?).await?;
+    assert_eq!(expected, res);
+
+    // point columns follow the layout like those of the derived worker
+    let layout = JsonLayout::JsonString;
+    let derived = collect(
+        Table::AirportsDataTable
+            .run_extract_to_stream(db.as_ref(), query, 3, ExtractBackend::Query, layout)
+            .await?,
+    )
+    .await?;
+    let expected = collect(
+        worker
+            .export_table_to_stream_with(db.as_ref(), query, 3, layout)
+            .await?,
+    )
+    .await?;
+    let res = collect(
+        worker
+            .copy_table_to_stream_with(db.as_ref(), query, 3, layout)
+            .await?,
+    )
+    .await?;
+    assert_eq!(expected, res);
+    assert_eq!(
+        expected.column_by_name("coordinates"),
+        derived.column_by_name("coordinates")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_copy_partitioned() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let ctx = SessionContext::new();
+    let df = PartitionedExtractor::new(Table::BookingsTable, PartitionKey::Ctid)
+        .with_partitions(3)
+        .with_backend(ExtractBackend::CopyBinary)
+        .extract_to_df(db.as_ref(), &ctx)
+        .await?;
+    let expected = sqlx::query_scalar::<_, i64>("select count(*) from bookings")
+        .fetch_one(db.as_ref())
+        .await?;
+    assert_eq!(df.count().await?, expected as usize);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_copy_unfinished_closes_connection() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(1)
+        .build()
+        .await?;
+    let table = Table::BookingsTable;
+
+    // dropped after the first batch
+    let mut stream = table
+        .run_copy_table_to_stream(db.as_ref(), "select * from bookings", 1)
+        .await?;
+    assert!(stream.try_next().await?.is_some());
+    drop(stream);
+
+    // failing once the copy started
+    let query = "select * from bookings where 1 / (length(book_ref) - 6) = 0";
+    let stream = table
+        .run_copy_table_to_stream(db.as_ref(), query, 1)
+        .await?;
+    assert!(collect(stream).await.is_err());
+
+    // the only connection of the pool is usable again
+    let one = tokio::time::timeout(
+        Duration::from_secs(10),
+        sqlx::query_scalar::<_, i32>("select 1").fetch_one(db.as_ref()),
+    )
+    .await??;
+    assert_eq!(one, 1);
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use demodb_to_datalake::{
+    DeltaTable, ExtractBackend, Flights, JsonLayout, LakeStore, ParquetWriteOptions, PostgresDb,
+    SaveMode, Table, DATABASE_URL, DEFAULT_BATCH_SIZE, MAX_DB_CONS,
+};
+
+use color_eyre::Result;
+use datafusion::prelude::*;
+use secrecy::ExposeSecret;
+
+#[tokio::test]
+async fn test_delta_flights() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let expected = sqlx::query_scalar::<_, i64>("select count(*) from flights")
+        .fetch_one(db.as_ref())
+        .await? as usize;
+
+    let dir = std::env::temp_dir().join(format!("delta_{}", std::process::id()));
+    let store = LakeStore::from_url(dir.to_str().unwrap())?;
+    let table = DeltaTable::new(store, "flights");
+    let options = ParquetWriteOptions::new().with_compression("zstd(3)");
+    table.create(&Flights::schema()).await?;
+    for mode in [SaveMode::Append, SaveMode::Append, SaveMode::Overwrite] {
+        let stream = Table::FlightsTable
+            .run_extract_to_stream(
+                db.as_ref(),
+                "select * from flights",
+                DEFAULT_BATCH_SIZE,
+                ExtractBackend::CopyBinary,
+                JsonLayout::default(),
+            )
+            .await?;
+        table.write_stream(stream, mode, &options).await?;
+    }
+
+    let ctx = SessionContext::new();
+    let mut counts = vec![];
+    for version in [Some(1), Some(2), None] {
+        counts.push(table.read_to_df(&ctx, version).await?.count().await?);
+    }
+    let snapshot = table.snapshot(None).await?;
+    let log_files = std::fs::read_dir(dir.join("flights/_delta_log"))?.count();
+    std::fs::remove_dir_all(&dir)?;
+
+    assert_eq!(counts, vec![expected, expected * 2, expected]);
+    assert_eq!(snapshot.version(), 3);
+    assert_eq!(snapshot.schema().as_ref(), &Flights::schema());
+    assert_eq!(log_files, 4);
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use demodb_to_datalake::{
+    helpers, Flights, PostgresDb, Table, TableWorker, TableWorkerDyn, ALL_TABLE_NAMES,
+    DATABASE_URL, FLIGHTS_TABLE_NAME, MAX_DB_CONS, SEATS_TABLE_NAME,
+};
+
+use color_eyre::Result;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::prelude::*;
+use secrecy::ExposeSecret;
+use serde::Serialize;
+use sqlx::FromRow;
+
+#[derive(Debug, Default, FromRow, Serialize, TableWorker)]
+#[table_worker(table = SEATS_TABLE_NAME)]
+struct SeatNumbers {
+    aircraft_code: String,
+    seat_no: Option<String>,
+}
+
+#[test]
+fn test_derive_schema() {
+    let expected = Schema::new(vec![
+        Field::new("aircraft_code", DataType::Utf8, false),
+        Field::new("seat_no", DataType::Utf8, true),
+    ]);
+    assert_eq!(SeatNumbers::schema(), expected);
+    assert_eq!(SeatNumbers::default().as_ref(), SEATS_TABLE_NAME);
+}
+
+#[tokio::test]
+async fn test_derive_dyn_df() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let ctx = SessionContext::new();
+    let worker = SeatNumbers::default();
+    let query = format!("select aircraft_code, seat_no from {}", worker.as_ref());
+    let df = worker.query_table_to_df(db.as_ref(), &query, &ctx).await?;
+    assert_eq!(df.schema().as_arrow(), &SeatNumbers::schema());
+    assert_eq!(df.count().await?, 10);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_derive_stat_string() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let query = format!("select aircraft_code, seat_no from {}", SEATS_TABLE_NAME);
+    let res = helpers::process_table_to_string::<SeatNumbers>(db.as_ref(), &query).await?;
+    assert_eq!(res.len(), 10);
+    assert!(res
+        .iter()
+        .all(|row| row.starts_with("aircraft_code: ") && row.contains(", seat_no: ")));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_derive_format_override_partial_projection() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let query = format!(
+        "select flight_id, actual_departure from {} order by flight_id limit 1",
+        FLIGHTS_TABLE_NAME
+    );
+    let res = Flights::new()
+        .query_table_to_string(db.as_ref(), &query)
+        .await?;
+    assert_eq!(
+        res,
+        vec!["flight_id: 1, actual_departure: 2017-06-13 15:29:00 UTC"]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_derive_decimal_columns_match_catalog() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let mut checked = 0;
+    for name in ALL_TABLE_NAMES {
+        let schema = Table::new(name).unwrap().schema();
+        for field in schema.fields() {
+            let DataType::Decimal128(precision, scale) = field.data_type() else {
+                continue;
+            };
+            let (numeric_precision, numeric_scale) = sqlx::query_as::<_, (i32, i32)>(
+                "select numeric_precision, numeric_scale from information_schema.columns \
+                where table_schema = 'bookings' and table_name = $1 and column_name = $2",
+            )
+            .bind(name)
+            .bind(field.name())
+            .fetch_one(db.as_ref())
+            .await?;
+            assert_eq!(
+                (*precision as i32, *scale as i32),
+                (numeric_precision, numeric_scale),
+                "{}.{}",
+                name,
+                field.name()
+            );
+            checked += 1;
+        }
+    }
+    assert_eq!(checked, 2);
+    Ok(())
+}
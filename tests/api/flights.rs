@@ -2,6 +2,7 @@ use demodb_to_datalake::{PostgresDb, Table, DATABASE_URL, MAX_DB_CONS};
 
 use color_eyre::Result;
 use datafusion::{assert_batches_eq, prelude::*};
+use futures_util::TryStreamExt;
 use secrecy::ExposeSecret;
 
 const TABLE: Table = Table::FlightsTable;
@@ -108,25 +109,44 @@ mod dyn_trait {
             .unwrap();
         assert_batches_eq!(
             &[
-                "+-----------+-----------+---------------------------+---------------------------+-------------------+-----------------+---------+---------------+---------------------------+---------------------------+",
-                "| flight_id | flight_no | scheduled_departure       | scheduled_arrival         | departure_airport | arrival_airport | status  | aircraft_code | actual_departure          | actual_arrival            |",
-                "+-----------+-----------+---------------------------+---------------------------+-------------------+-----------------+---------+---------------+---------------------------+---------------------------+",
-                "| 1         | PG0403    | 2017-06-13T08:25:00+00:00 | 2017-06-13T09:20:00+00:00 | DME               | LED             | Arrived | 321           | 2017-06-13T08:29:00+00:00 | 2017-06-13T09:24:00+00:00 |",
-                "| 2         | PG0404    | 2017-06-13T16:05:00+00:00 | 2017-06-13T17:00:00+00:00 | DME               | LED             | Arrived | 321           | 2017-06-13T16:11:00+00:00 | 2017-06-13T17:06:00+00:00 |",
-                "| 3         | PG0405    | 2017-06-13T06:35:00+00:00 | 2017-06-13T07:30:00+00:00 | DME               | LED             | Arrived | 321           | 2017-06-13T06:38:00+00:00 | 2017-06-13T07:33:00+00:00 |",
-                "| 4         | PG0402    | 2017-02-10T09:25:00+00:00 | 2017-02-10T10:20:00+00:00 | DME               | LED             | Arrived | 321           | 2017-02-10T09:30:00+00:00 | 2017-02-10T10:26:00+00:00 |",
-                "| 5         | PG0403    | 2017-02-10T08:25:00+00:00 | 2017-02-10T09:20:00+00:00 | DME               | LED             | Arrived | 321           | 2017-02-10T08:28:00+00:00 | 2017-02-10T09:22:00+00:00 |",
-                "| 6         | PG0403    | 2016-12-08T08:25:00+00:00 | 2016-12-08T09:20:00+00:00 | DME               | LED             | Arrived | 321           | 2016-12-08T08:31:00+00:00 | 2016-12-08T09:25:00+00:00 |",
-                "| 7         | PG0404    | 2017-02-10T16:05:00+00:00 | 2017-02-10T17:00:00+00:00 | DME               | LED             | Arrived | 321           | 2017-02-10T16:07:00+00:00 | 2017-02-10T17:02:00+00:00 |",
-                "| 8         | PG0404    | 2016-12-08T16:05:00+00:00 | 2016-12-08T17:00:00+00:00 | DME               | LED             | Arrived | 321           | 2016-12-08T16:10:00+00:00 | 2016-12-08T17:05:00+00:00 |",
-                "| 9         | PG0404    | 2016-11-26T16:05:00+00:00 | 2016-11-26T17:00:00+00:00 | DME               | LED             | Arrived | 321           | 2016-11-26T16:09:00+00:00 | 2016-11-26T17:03:00+00:00 |",
-                "| 10        | PG0404    | 2017-01-16T16:05:00+00:00 | 2017-01-16T17:00:00+00:00 | DME               | LED             | Arrived | 321           | 2017-01-16T16:08:00+00:00 | 2017-01-16T17:03:00+00:00 |",
-                "+-----------+-----------+---------------------------+---------------------------+-------------------+-----------------+---------+---------------+---------------------------+---------------------------+",
+                "+-----------+-----------+----------------------+----------------------+-------------------+-----------------+---------+---------------+----------------------+----------------------+",
+                "| flight_id | flight_no | scheduled_departure  | scheduled_arrival    | departure_airport | arrival_airport | status  | aircraft_code | actual_departure     | actual_arrival       |",
+                "+-----------+-----------+----------------------+----------------------+-------------------+-----------------+---------+---------------+----------------------+----------------------+",
+                "| 1         | PG0403    | 2017-06-13T08:25:00Z | 2017-06-13T09:20:00Z | DME               | LED             | Arrived | 321           | 2017-06-13T08:29:00Z | 2017-06-13T09:24:00Z |",
+                "| 2         | PG0404    | 2017-06-13T16:05:00Z | 2017-06-13T17:00:00Z | DME               | LED             | Arrived | 321           | 2017-06-13T16:11:00Z | 2017-06-13T17:06:00Z |",
+                "| 3         | PG0405    | 2017-06-13T06:35:00Z | 2017-06-13T07:30:00Z | DME               | LED             | Arrived | 321           | 2017-06-13T06:38:00Z | 2017-06-13T07:33:00Z |",
+                "| 4         | PG0402    | 2017-02-10T09:25:00Z | 2017-02-10T10:20:00Z | DME               | LED             | Arrived | 321           | 2017-02-10T09:30:00Z | 2017-02-10T10:26:00Z |",
+                "| 5         | PG0403    | 2017-02-10T08:25:00Z | 2017-02-10T09:20:00Z | DME               | LED             | Arrived | 321           | 2017-02-10T08:28:00Z | 2017-02-10T09:22:00Z |",
+                "| 6         | PG0403    | 2016-12-08T08:25:00Z | 2016-12-08T09:20:00Z | DME               | LED             | Arrived | 321           | 2016-12-08T08:31:00Z | 2016-12-08T09:25:00Z |",
+                "| 7         | PG0404    | 2017-02-10T16:05:00Z | 2017-02-10T17:00:00Z | DME               | LED             | Arrived | 321           | 2017-02-10T16:07:00Z | 2017-02-10T17:02:00Z |",
+                "| 8         | PG0404    | 2016-12-08T16:05:00Z | 2016-12-08T17:00:00Z | DME               | LED             | Arrived | 321           | 2016-12-08T16:10:00Z | 2016-12-08T17:05:00Z |",
+                "| 9         | PG0404    | 2016-11-26T16:05:00Z | 2016-11-26T17:00:00Z | DME               | LED             | Arrived | 321           | 2016-11-26T16:09:00Z | 2016-11-26T17:03:00Z |",
+                "| 10        | PG0404    | 2017-01-16T16:05:00Z | 2017-01-16T17:00:00Z | DME               | LED             | Arrived | 321           | 2017-01-16T16:08:00Z | 2017-01-16T17:03:00Z |",
+                "+-----------+-----------+----------------------+----------------------+-------------------+-----------------+---------+---------------+----------------------+----------------------+",
             ],
             &rows.collect().await.unwrap()
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_flights_stream() -> Result<()> {
+        let db = PostgresDb::builder()
+            .with_url(DATABASE_URL.expose_secret())
+            .with_max_cons(MAX_DB_CONS)
+            .build()
+            .await?;
+        let table = TABLE;
+        let worker = table.to_worker();
+        let query = format!("select * from {}", table.as_ref());
+        let stream = worker.query_table_to_stream(db.as_ref(), &query, 4).await?;
+        let batches = stream.try_collect::<Vec<_>>().await?;
+
+        assert_eq!(batches.len(), 3); // batches count
+        assert!(batches.iter().all(|batch| batch.num_rows() <= 4));
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10); // rows count
+        Ok(())
+    }
 }
 
 mod stat_trait {
@@ -229,23 +249,43 @@ mod stat_trait {
             .unwrap();
         assert_batches_eq!(
             &[
-                "+-----------+-----------+---------------------------+---------------------------+-------------------+-----------------+---------+---------------+---------------------------+---------------------------+",
-                "| flight_id | flight_no | scheduled_departure       | scheduled_arrival         | departure_airport | arrival_airport | status  | aircraft_code | actual_departure          | actual_arrival            |",
-                "+-----------+-----------+---------------------------+---------------------------+-------------------+-----------------+---------+---------------+---------------------------+---------------------------+",
-                "| 1         | PG0403    | 2017-06-13T08:25:00+00:00 | 2017-06-13T09:20:00+00:00 | DME               | LED             | Arrived | 321           | 2017-06-13T08:29:00+00:00 | 2017-06-13T09:24:00+00:00 |",
-                "| 2         | PG0404    | 2017-06-13T16:05:00+00:00 | 2017-06-13T17:00:00+00:00 | DME               | LED             | Arrived | 321           | 2017-06-13T16:11:00+00:00 | 2017-06-13T17:06:00+00:00 |",
-                "| 3         | PG0405    | 2017-06-13T06:35:00+00:00 | 2017-06-13T07:30:00+00:00 | DME               | LED             | Arrived | 321           | 2017-06-13T06:38:00+00:00 | 2017-06-13T07:33:00+00:00 |",
-                "| 4         | PG0402    | 2017-02-10T09:25:00+00:00 | 2017-02-10T10:20:00+00:00 | DME               | LED             | Arrived | 321           | 2017-02-10T09:30:00+00:00 | 2017-02-10T10:26:00+00:00 |",
-                "| 5         | PG0403    | 2017-02-10T08:25:00+00:00 | 2017-02-10T09:20:00+00:00 | DME               | LED             | Arrived | 321           | 2017-02-10T08:28:00+00:00 | 2017-02-10T09:22:00+00:00 |",
-                "| 6         | PG0403    | 2016-12-08T08:25:00+00:00 | 2016-12-08T09:20:00+00:00 | DME               | LED             | Arrived | 321           | 2016-12-08T08:31:00+00:00 | 2016-12-08T09:25:00+00:00 |",
-                "| 7         | PG0404    | 2017-02-10T16:05:00+00:00 | 2017-02-10T17:00:00+00:00 | DME               | LED             | Arrived | 321           | 2017-02-10T16:07:00+00:00 | 2017-02-10T17:02:00+00:00 |",
-                "| 8         | PG0404    | 2016-12-08T16:05:00+00:00 | 2016-12-08T17:00:00+00:00 | DME               | LED             | Arrived | 321           | 2016-12-08T16:10:00+00:00 | 2016-12-08T17:05:00+00:00 |",
-                "| 9         | PG0404    | 2016-11-26T16:05:00+00:00 | 2016-11-26T17:00:00+00:00 | DME               | LED             | Arrived | 321           | 2016-11-26T16:09:00+00:00 | 2016-11-26T17:03:00+00:00 |",
-                "| 10        | PG0404    | 2017-01-16T16:05:00+00:00 | 2017-01-16T17:00:00+00:00 | DME               | LED             | Arrived | 321           | 2017-01-16T16:08:00+00:00 | 2017-01-16T17:03:00+00:00 |",
-                "+-----------+-----------+---------------------------+---------------------------+-------------------+-----------------+---------+---------------+---------------------------+---------------------------+",
+                "+-----------+-----------+----------------------+----------------------+-------------------+-----------------+---------+---------------+----------------------+----------------------+",
+                "| flight_id | flight_no | scheduled_departure  | scheduled_arrival    | departure_airport | arrival_airport | status  | aircraft_code | actual_departure     | actual_arrival       |",
+                "+-----------+-----------+----------------------+----------------------+-------------------+-----------------+---------+---------------+----------------------+----------------------+",
+                "| 1         | PG0403    | 2017-06-13T08:25:00Z | 2017-06-13T09:20:00Z | DME               | LED             | Arrived | 321           | 2017-06-13T08:29:00Z | 2017-06-13T09:24:00Z |",
+                "| 2         | PG0404    | 2017-06-13T16:05:00Z | 2017-06-13T17:00:00Z | DME               | LED             | Arrived | 321           | 2017-06-13T16:11:00Z | 2017-06-13T17:06:00Z |",
+                "| 3         | PG0405    | 2017-06-13T06:35:00Z | 2017-06-13T07:30:00Z | DME               | LED             | Arrived | 321           | 2017-06-13T06:38:00Z | 2017-06-13T07:33:00Z |",
+                "| 4         | PG0402    | 2017-02-10T09:25:00Z | 2017-02-10T10:20:00Z | DME               | LED             | Arrived | 321           | 2017-02-10T09:30:00Z | 2017-02-10T10:26:00Z |",
+                "| 5         | PG0403    | 2017-02-10T08:25:00Z | 2017-02-10T09:20:00Z | DME               | LED             | Arrived | 321           | 2017-02-10T08:28:00Z | 2017-02-10T09:22:00Z |",
+                "| 6         | PG0403    | 2016-12-08T08:25:00Z | 2016-12-08T09:20:00Z | DME               | LED             | Arrived | 321           | 2016-12-08T08:31:00Z | 2016-12-08T09:25:00Z |",
+                "| 7         | PG0404    | 2017-02-10T16:05:00Z | 2017-02-10T17:00:00Z | DME               | LED             | Arrived | 321           | 2017-02-10T16:07:00Z | 2017-02-10T17:02:00Z |",
+                "| 8         | PG0404    | 2016-12-08T16:05:00Z | 2016-12-08T17:00:00Z | DME               | LED             | Arrived | 321           | 2016-12-08T16:10:00Z | 2016-12-08T17:05:00Z |",
+                "| 9         | PG0404    | 2016-11-26T16:05:00Z | 2016-11-26T17:00:00Z | DME               | LED             | Arrived | 321           | 2016-11-26T16:09:00Z | 2016-11-26T17:03:00Z |",
+                "| 10        | PG0404    | 2017-01-16T16:05:00Z | 2017-01-16T17:00:00Z | DME               | LED             | Arrived | 321           | 2017-01-16T16:08:00Z | 2017-01-16T17:03:00Z |",
+                "+-----------+-----------+----------------------+----------------------+-------------------+-----------------+---------+---------------+----------------------+----------------------+",
             ],
             &rows.collect().await.unwrap()
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_flights_stream() -> Result<()> {
+        let db = PostgresDb::builder()
+            .with_url(DATABASE_URL.expose_secret())
+            .with_max_cons(MAX_DB_CONS)
+            .build()
+            .await?;
+        let table = TABLE;
+        let query = format!("select * from {}", table.as_ref());
+        let stream = table
+            .run_query_table_to_stream(db.as_ref(), &query, 4)
+            .await?;
+        let batches = stream.try_collect::<Vec<_>>().await?;
+
+        assert_eq!(batches.len(), 3); // batches count
+        assert!(batches.iter().all(|batch| batch.num_rows() <= 4));
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10); // rows count
+        Ok(())
+    }
 }
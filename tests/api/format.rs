@@ -0,0 +1,72 @@
+use demodb_to_datalake::{
+    write_df_to_file_as, ArrowIpcOptions, CsvOptions, FileCompression, IpcCompression,
+    NdJsonOptions, OutputFormat, PostgresDb, Table, DATABASE_URL, MAX_DB_CONS,
+};
+
+use color_eyre::Result;
+use datafusion::datasource::file_format::file_compression_type::FileCompressionType;
+use datafusion::datasource::file_format::options::ArrowReadOptions;
+use datafusion::prelude::*;
+use secrecy::ExposeSecret;
+
+#[tokio::test]
+async fn test_seats_output_formats() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let ctx = SessionContext::new();
+    let df = Table::SeatsTable
+        .run_query_table_to_df(db.as_ref(), "select * from seats", &ctx)
+        .await?;
+    let expected = df.clone().count().await?;
+
+    let dir = std::env::temp_dir().join(format!("formats_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let formats = [
+        OutputFormat::Csv(
+            CsvOptions::new()
+                .with_delimiter('|')
+                .with_compression(FileCompression::Gzip),
+        ),
+        OutputFormat::NdJson(NdJsonOptions::new()),
+        OutputFormat::ArrowIpc(ArrowIpcOptions::new().with_compression(IpcCompression::Zstd)),
+    ];
+    let mut paths = vec![];
+    for format in &formats {
+        let path = dir.join(format!("seats.{}", format.extension()));
+        let path = path.to_str().unwrap().to_string();
+        write_df_to_file_as(df.clone(), &path, format).await?;
+        paths.push(path);
+    }
+
+    let csv_options = CsvReadOptions::new()
+        .delimiter(b'|')
+        .file_extension(".csv.gz")
+        .file_compression_type(FileCompressionType::GZIP);
+    let json_options = NdJsonReadOptions::default().file_extension(".ndjson");
+    let counts = vec![
+        ctx.read_csv(&paths[0], csv_options).await?.count().await?,
+        ctx.read_json(&paths[1], json_options)
+            .await?
+            .count()
+            .await?,
+        ctx.read_arrow(&paths[2], ArrowReadOptions::default())
+            .await?
+            .count()
+            .await?,
+    ];
+    let arrow_schema = ctx
+        .read_arrow(&paths[2], ArrowReadOptions::default())
+        .await?
+        .schema()
+        .as_arrow()
+        .clone();
+    std::fs::remove_dir_all(&dir)?;
+
+    assert_eq!(paths[0].rsplit('/').next(), Some("seats.csv.gz"));
+    assert_eq!(counts, vec![expected; 3]);
+    assert_eq!(&arrow_schema, df.schema().as_arrow());
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use demodb_to_datalake::{
+    AppError, Flights, GenericTableWorker, PostgresDb, QueryParserError, SchemaError,
+    TableWorkerDyn, DATABASE_URL, MAX_DB_CONS,
+};
+
+use color_eyre::Result;
+use datafusion::prelude::*;
+use futures_util::TryStreamExt;
+use secrecy::ExposeSecret;
+
+#[tokio::test]
+async fn test_generic_schema() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let worker = GenericTableWorker::new("flights");
+    let schema = worker.schema(db.as_ref()).await?;
+    let expected = Flights::schema();
+    let data_types = |fields: &datafusion::arrow::datatypes::Fields| {
+        fields
+            .iter()
+            .map(|field| (field.name().clone(), field.data_type().clone()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(data_types(schema.fields()), data_types(expected.fields()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_generic_unknown_table() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let worker = GenericTableWorker::new("foo");
+    let res = worker.schema(db.as_ref()).await;
+    assert!(matches!(
+        res,
+        Err(AppError::SchemaError(SchemaError::TableNotFound(_)))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_generic_view_df() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let ctx = SessionContext::new();
+    let worker = GenericTableWorker::new("aircrafts").with_schema_name("bookings");
+    let query = format!("select * from {}", worker.as_ref());
+    let df = worker.query_table_to_df(db.as_ref(), &query, &ctx).await?;
+    let columns = df
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| field.name().as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(columns, vec!["aircraft_code", "model", "range"]);
+    assert_eq!(df.count().await?, 9);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_generic_schema_qualifier() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let ctx = SessionContext::new();
+    let worker = GenericTableWorker::new("aircrafts").with_schema_name("bookings");
+    let df = worker
+        .query_table_to_df(
+            db.as_ref(),
+            "select * from bookings.aircrafts limit 5",
+            &ctx,
+        )
+        .await?;
+    assert_eq!(df.count().await?, 5);
+
+    for query in [
+        "select * from public.aircrafts",
+        "select * from aircrafts where aircraft_code in (select aircraft_code from public.aircrafts)",
+    ] {
+        let res = worker
+            .copy_table_to_stream(db.as_ref(), query, 10)
+            .await
+            .map(|_| ());
+        assert!(
+            matches!(
+                res,
+                Err(AppError::QueryParserError(QueryParserError::SchemaNotAllowed(ref schema)))
+                    if schema == "public"
+            ),
+            "query: {}",
+            query
+        );
+    }
+
+    let worker = GenericTableWorker::new("flights");
+    let res = worker
+        .query_table_to_stream(db.as_ref(), "select * from bookings.flights", 10)
+        .await
+        .map(|_| ());
+    assert!(matches!(
+        res,
+        Err(AppError::QueryParserError(
+            QueryParserError::SchemaNotAllowed(_)
+        ))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_generic_stream() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let worker = GenericTableWorker::new("flights");
+    let query = format!("select * from {}", worker.as_ref());
+    let stream = worker.query_table_to_stream(db.as_ref(), &query, 4).await?;
+    let batches = stream.try_collect::<Vec<_>>().await?;
+
+    assert_eq!(batches.len(), 3); // batches count
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10); // rows count
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_generic_projection() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let ctx = SessionContext::new();
+    let worker = GenericTableWorker::new("flights");
+    let query = "select status, flight_id from flights limit 5";
+    let names = |schema: &datafusion::arrow::datatypes::Schema| {
+        schema
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>()
+    };
+
+    let df = worker.query_table_to_df(db.as_ref(), query, &ctx).await?;
+    assert_eq!(names(df.schema().as_arrow()), vec!["status", "flight_id"]);
+    assert_eq!(df.count().await?, 5);
+
+    let stream = worker.copy_table_to_stream(db.as_ref(), query, 2).await?;
+    let batches = stream.try_collect::<Vec<_>>().await?;
+    assert_eq!(names(&batches[0].schema()), vec!["status", "flight_id"]);
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 5);
+
+    let res = worker
+        .export_table_to_stream(db.as_ref(), "select flight_id as id from flights", 2)
+        .await
+        .map(|_| ());
+    assert!(matches!(
+        res,
+        Err(AppError::QueryParserError(QueryParserError::UnknownColumn(
+            ..
+        )))
+    ));
+    Ok(())
+}
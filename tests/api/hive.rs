@@ -0,0 +1,55 @@
+use demodb_to_datalake::{
+    read_file_to_df, write_df_to_hive_dir, PartitionColumn, PartitionKey, PartitionedExtractor,
+    PostgresDb, Table, DATABASE_URL, MAX_DB_CONS,
+};
+
+use color_eyre::Result;
+use datafusion::prelude::*;
+use secrecy::ExposeSecret;
+
+#[tokio::test]
+async fn test_hive_bookings_by_day() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let ctx = SessionContext::new();
+    let df = PartitionedExtractor::new(Table::BookingsTable, PartitionKey::Ctid)
+        .extract_to_df(db.as_ref(), &ctx)
+        .await?;
+    let expected = df.clone().count().await?;
+    let days = df
+        .clone()
+        .select(vec![to_char(col("book_date"), lit("%Y-%m-%d"))])?
+        .distinct()?
+        .count()
+        .await?;
+
+    let dir = std::env::temp_dir().join(format!("hive_bookings_{}", std::process::id()));
+    let partition_by = [PartitionColumn::day("book_day", "book_date")];
+    let files = write_df_to_hive_dir(df, dir.to_str().unwrap(), &partition_by, 3).await?;
+
+    let mut rows = 0;
+    let mut partitions = vec![];
+    for file in &files {
+        let df = read_file_to_df(file).await?;
+        let count = df.count().await?;
+        assert!(count <= 3);
+        rows += count;
+        let partition = std::path::Path::new(file).parent().unwrap().to_path_buf();
+        if !partitions.contains(&partition) {
+            partitions.push(partition);
+        }
+    }
+    std::fs::remove_dir_all(&dir)?;
+
+    assert_eq!(rows, expected);
+    assert_eq!(partitions.len(), days);
+    assert!(partitions.iter().all(|partition| partition
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .starts_with("book_day=")));
+    Ok(())
+}
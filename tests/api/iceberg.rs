@@ -0,0 +1,78 @@
+use demodb_to_datalake::{
+    AirportsData, Bookings, IcebergCatalog, IcebergPartitionField, LakeStore, ParquetWriteOptions,
+    PartitionKey, PartitionedExtractor, PostgresDb, SaveMode, Table, DATABASE_URL, MAX_DB_CONS,
+};
+
+use color_eyre::Result;
+use datafusion::prelude::*;
+use secrecy::ExposeSecret;
+
+#[tokio::test]
+async fn test_iceberg_tables() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let dir = std::env::temp_dir().join(format!("iceberg_{}", std::process::id()));
+    let catalog = IcebergCatalog::new(LakeStore::from_url(dir.to_str().unwrap())?);
+    let options = ParquetWriteOptions::default();
+    let ctx = SessionContext::new();
+
+    let partition_by = ["day(book_date)".parse::<IcebergPartitionField>()?];
+    let mut bookings = catalog
+        .create_table("demo", "bookings", &Bookings::schema(), &partition_by)
+        .await?;
+    let df = PartitionedExtractor::new(Table::BookingsTable, PartitionKey::Ctid)
+        .extract_to_df(db.as_ref(), &ctx)
+        .await?;
+    let expected = df.clone().count().await?;
+    let days = df
+        .clone()
+        .select(vec![to_char(col("book_date"), lit("%Y-%m-%d"))])?
+        .distinct()?
+        .count()
+        .await?;
+    let first = bookings
+        .write_df(df.clone(), SaveMode::Append, &options)
+        .await?;
+    let second = bookings.write_df(df, SaveMode::Append, &options).await?;
+
+    let mut airports = catalog
+        .create_table("demo", "airports_data", &AirportsData::schema(), &[])
+        .await?;
+    let df = PartitionedExtractor::new(Table::AirportsDataTable, PartitionKey::Ctid)
+        .extract_to_df(db.as_ref(), &ctx)
+        .await?;
+    let airports_expected = df.clone().count().await?;
+    airports.write_df(df, SaveMode::Overwrite, &options).await?;
+
+    let bookings = catalog.load_table("demo", "bookings").await?;
+    let first_rows = bookings
+        .read_to_df(&ctx, Some(first))
+        .await?
+        .count()
+        .await?;
+    let second_rows = bookings
+        .read_to_df(&ctx, Some(second))
+        .await?
+        .count()
+        .await?;
+    let first_files = bookings.data_files(Some(first)).await?;
+    let airports = catalog.load_table("demo", "airports_data").await?;
+    let airports_rows = airports.read_to_df(&ctx, None).await?.count().await?;
+    let version_hint =
+        std::fs::read_to_string(dir.join("demo/bookings/metadata/version-hint.text"))?;
+    std::fs::remove_dir_all(&dir)?;
+
+    assert_eq!(bookings.snapshot_ids(), vec![first, second]);
+    assert_eq!(first_rows, expected);
+    assert_eq!(second_rows, expected * 2);
+    assert_eq!(first_files.len(), days);
+    assert!(first_files
+        .iter()
+        .all(|file| file.contains("/data/book_date_day=")));
+    assert_eq!(airports_rows, airports_expected);
+    assert_eq!(version_hint, "2");
+    Ok(())
+}
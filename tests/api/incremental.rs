@@ -0,0 +1,50 @@
+use demodb_to_datalake::{
+    read_file_to_df, IncrementalExtractor, PostgresDb, Table, Watermarks, DATABASE_URL, MAX_DB_CONS,
+};
+
+use color_eyre::Result;
+use secrecy::ExposeSecret;
+
+#[tokio::test]
+async fn test_incremental_bookings() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let dir = std::env::temp_dir().join(format!("incremental_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let dir = dir.to_str().unwrap();
+    let state_path = format!("{}/watermarks.json", dir);
+
+    // pretend a previous run loaded the older half of the table
+    let (low, expected) = sqlx::query_as::<_, (String, i64)>(
+        "select book_date::text, (select count(*) from bookings b where b.book_date > m.book_date) \
+         from bookings m order by book_date offset (select count(*) / 2 from bookings) limit 1",
+    )
+    .fetch_one(db.as_ref())
+    .await?;
+    let mut watermarks = Watermarks::default();
+    watermarks.set("bookings", &low);
+    watermarks.save(&state_path).await?;
+
+    let extractor = IncrementalExtractor::new(Table::BookingsTable, "book_date", &state_path);
+    let file = extractor.extract_to_file(db.as_ref(), dir).await?;
+    let rows = read_file_to_df(file.as_deref().unwrap())
+        .await?
+        .count()
+        .await?;
+    let high = sqlx::query_scalar::<_, String>("select max(book_date)::text from bookings")
+        .fetch_one(db.as_ref())
+        .await?;
+    let watermarks = Watermarks::load(&state_path).await?;
+
+    // nothing changed since, the next run has no delta
+    let next = extractor.extract_to_file(db.as_ref(), dir).await?;
+    std::fs::remove_dir_all(dir)?;
+
+    assert_eq!(rows, expected as usize);
+    assert_eq!(watermarks.get("bookings"), Some(high.as_str()));
+    assert_eq!(next, None);
+    Ok(())
+}
@@ -1,8 +1,25 @@
 mod aircrafts_data;
 mod airports_data;
+mod avro;
 mod boarding_passes;
 mod bookings;
+mod catalog;
+mod cli;
+mod copy;
+mod delta;
+mod derive;
 mod flights;
+mod format;
+mod generic;
+mod hive;
+mod iceberg;
+mod incremental;
+mod partition;
+mod pipeline;
+mod projection;
+mod provider;
+mod query_policy;
 mod seats;
+mod snapshot;
 mod ticket_flights;
 mod tickets;
@@ -0,0 +1,64 @@
+use demodb_to_datalake::{
+    read_file_to_df, PartitionKey, PartitionedExtractor, PostgresDb, Table, DATABASE_URL,
+    MAX_DB_CONS, MAX_ROWS,
+};
+
+use color_eyre::Result;
+use datafusion::prelude::*;
+use secrecy::ExposeSecret;
+
+async fn count_rows(db: &PostgresDb, table: &Table) -> Result<usize> {
+    let query = format!("select count(*) from {}", table.as_ref());
+    let count = sqlx::query_scalar::<_, i64>(&query)
+        .fetch_one(db.as_ref())
+        .await?;
+    Ok(count as usize)
+}
+
+#[tokio::test]
+async fn test_partition_by_column_df() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let ctx = SessionContext::new();
+    let extractor = PartitionedExtractor::new(
+        Table::TicketFlightsTable,
+        PartitionKey::Column("flight_id".to_string()),
+    )
+    .with_partitions(4)
+    .with_parallelism(2);
+    assert_eq!(extractor.predicates(db.as_ref()).await?.len(), 4);
+
+    let df = extractor.extract_to_df(db.as_ref(), &ctx).await?;
+    let expected = count_rows(&db, &Table::TicketFlightsTable).await?;
+    assert!(expected > MAX_ROWS as usize);
+    assert_eq!(df.count().await?, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_partition_by_ctid_files() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let dir = std::env::temp_dir().join(format!("partition_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let extractor = PartitionedExtractor::new(Table::BoardingPassesTable, PartitionKey::Ctid)
+        .with_partitions(3)
+        .with_batch_size(4);
+    let files = extractor
+        .extract_to_files(db.as_ref(), dir.to_str().unwrap())
+        .await?;
+
+    let mut rows = 0;
+    for file in &files {
+        rows += read_file_to_df(file).await?.count().await?;
+    }
+    std::fs::remove_dir_all(&dir)?;
+    assert_eq!(rows, count_rows(&db, &Table::BoardingPassesTable).await?);
+    Ok(())
+}
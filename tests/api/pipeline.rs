@@ -0,0 +1,85 @@
+use demodb_to_datalake::{read_file_to_df, PipelineConfig, DATABASE_URL};
+
+use color_eyre::Result;
+use secrecy::ExposeSecret;
+
+#[tokio::test]
+async fn test_pipeline_run() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("pipeline_{}", std::process::id()));
+    let config = format!(
+        r#"
+destination = "{}"
+backend = "copy_binary"
+
+[database]
+url = "{}"
+
+[[tables]]
+name = "flights"
+filter = "status = 'Arrived'"
+columns = ["flight_no", "flight_id"]
+
+[[tables]]
+name = "bookings"
+partition_by = ["month(book_date)"]
+
+[[tables]]
+name = "seats"
+path = "dim/seats.csv"
+output = {{ format = "csv", delimiter = ";" }}
+
+[[tables]]
+name = "tickets"
+filter = "1 / 0 = 1"
+"#,
+        dir.display(),
+        DATABASE_URL.expose_secret()
+    );
+    let config_path = dir.join("pipeline.toml");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(&config_path, config)?;
+
+    let config = PipelineConfig::load(config_path.to_str().unwrap()).await?;
+    let db = config.connect().await?;
+    let expected =
+        sqlx::query_scalar::<_, i64>("select count(*) from flights where status = 'Arrived'")
+            .fetch_one(db.as_ref())
+            .await? as usize;
+    let report = config.run(&db).await?;
+
+    let flights = read_file_to_df(dir.join("flights.parquet").to_str().unwrap()).await?;
+    let columns = flights
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| field.name().clone())
+        .collect::<Vec<_>>();
+    let rows = flights.count().await?;
+    let bookings = report.tables()[1].files().to_vec();
+    let seats_exists = dir.join("dim/seats.csv").exists();
+    std::fs::remove_dir_all(&dir)?;
+
+    let tables = report
+        .tables()
+        .iter()
+        .map(|table| (table.table(), table.error().is_none()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tables,
+        vec![
+            ("flights", true),
+            ("bookings", true),
+            ("seats", true),
+            ("tickets", false)
+        ]
+    );
+    assert!(!report.is_success());
+    assert_eq!(columns, vec!["flight_no", "flight_id"]);
+    assert_eq!(rows, expected);
+    assert!(!bookings.is_empty());
+    assert!(bookings
+        .iter()
+        .all(|file| file.contains("/bookings/book_date_month=")));
+    assert!(seats_exists);
+    Ok(())
+}
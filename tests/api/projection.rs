@@ -0,0 +1,168 @@
+use demodb_to_datalake::{
+    AppError, PostgresDb, QueryParserError, Table, DATABASE_URL, MAX_DB_CONS,
+};
+
+use color_eyre::Result;
+use datafusion::arrow::array::{AsArray, RecordBatch};
+use datafusion::arrow::compute::concat_batches;
+use datafusion::arrow::datatypes::Int32Type;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use datafusion::prelude::*;
+use futures_util::TryStreamExt;
+use rstest::rstest;
+use secrecy::ExposeSecret;
+
+async fn test_db() -> Result<PostgresDb> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    Ok(db)
+}
+
+async fn collect(stream: SendableRecordBatchStream) -> Result<RecordBatch> {
+    let schema = stream.schema();
+    let batches = stream.try_collect::<Vec<_>>().await?;
+    Ok(concat_batches(&schema, &batches)?)
+}
+
+fn column_names(batch: &RecordBatch) -> Vec<String> {
+    let schema = batch.schema();
+    schema
+        .fields()
+        .iter()
+        .map(|field| field.name().clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_projection_df() -> Result<()> {
+    let db = test_db().await?;
+    let ctx = SessionContext::new();
+    let query = "select status, f.flight_id from flights f where status = 'Scheduled'";
+    let df = Table::FlightsTable
+        .run_query_table_to_df(db.as_ref(), query, &ctx)
+        .await?;
+    let batches = df.collect().await?;
+    assert_eq!(column_names(&batches[0]), ["status", "flight_id"]);
+    let expected: i64 =
+        sqlx::query_scalar("select count(*) from flights where status = 'Scheduled'")
+            .fetch_one(db.as_ref())
+            .await?;
+    let rows = batches.iter().map(|batch| batch.num_rows()).sum::<usize>();
+    assert_eq!(rows as i64, expected.min(10));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_projection_stream() -> Result<()> {
+    let db = test_db().await?;
+    let table = Table::TicketFlightsTable;
+    let query = "select amount, ticket_no from ticket_flights order by ticket_no, flight_id";
+    let res = collect(
+        table
+            .run_export_table_to_stream(db.as_ref(), query, 7)
+            .await?,
+    )
+    .await?;
+    assert_eq!(column_names(&res), ["amount", "ticket_no"]);
+    let expected: i64 = sqlx::query_scalar("select count(*) from ticket_flights")
+        .fetch_one(db.as_ref())
+        .await?;
+    assert_eq!(res.num_rows() as i64, expected);
+
+    let copied = collect(
+        table
+            .run_copy_table_to_stream(db.as_ref(), query, 7)
+            .await?,
+    )
+    .await?;
+    assert_eq!(copied, res);
+    Ok(())
+}
+
+#[rstest]
+#[case("select * from (select flight_id from flights) s")]
+#[case("with f as (select flight_id from flights) select * from f")]
+#[tokio::test]
+async fn test_projection_wildcard_of_subquery(#[case] query: &str) -> Result<()> {
+    let db = test_db().await?;
+    let table = Table::FlightsTable;
+    let res = collect(
+        table
+            .run_export_table_to_stream(db.as_ref(), query, 7)
+            .await?,
+    )
+    .await?;
+    assert_eq!(column_names(&res), ["flight_id"]);
+    let expected: Vec<i32> = sqlx::query_scalar(query).fetch_all(db.as_ref()).await?;
+    let flight_ids = res.column(0).as_primitive::<Int32Type>().values().to_vec();
+    assert_eq!(flight_ids, expected);
+
+    let copied = collect(
+        table
+            .run_copy_table_to_stream(db.as_ref(), query, 7)
+            .await?,
+    )
+    .await?;
+    assert_eq!(copied, res);
+
+    // decoding into the struct needs every column
+    let res = table.run_query_table(db.as_ref(), query).await;
+    assert!(matches!(
+        res,
+        Err(AppError::SqlxError(sqlx::Error::ColumnNotFound(_)))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_projection_string_json() -> Result<()> {
+    let db = test_db().await?;
+    let table = Table::FlightsTable;
+    let query = "select flight_no, flight_id from flights order by flight_id limit 1";
+    let (flight_id, flight_no): (i32, String) = sqlx::query_as(query)
+        .fetch_one(db.as_ref())
+        .await
+        .map(|(flight_no, flight_id)| (flight_id, flight_no))?;
+
+    let res = table.run_query_table_to_string(db.as_ref(), query).await?;
+    assert_eq!(
+        res,
+        [format!(
+            "flight_no: {}, flight_id: {}",
+            flight_no, flight_id
+        )]
+    );
+
+    let res = table.run_query_table_to_json(db.as_ref(), query).await?;
+    let expected = serde_json::json!([{ "flight_id": flight_id, "flight_no": flight_no }]);
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&res)?, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_projection_unknown_column() -> Result<()> {
+    let db = test_db().await?;
+    let ctx = SessionContext::new();
+    let res = Table::FlightsTable
+        .run_query_table_to_df(db.as_ref(), "select fligt_no from flights", &ctx)
+        .await;
+    let Err(AppError::QueryParserError(e)) = res else {
+        panic!("expected an unknown column error");
+    };
+    assert_eq!(
+        e,
+        QueryParserError::UnknownColumn(
+            "flights".to_string(),
+            "fligt_no".to_string(),
+            vec!["flight_no".to_string()]
+        )
+    );
+    assert_eq!(
+        e.to_string(),
+        "Invalid query: column fligt_no does not exist in table flights, did you mean flight_no?"
+    );
+    Ok(())
+}
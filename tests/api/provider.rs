@@ -0,0 +1,152 @@
+use demodb_to_datalake::{
+    GenericTableWorker, PostgresDb, PostgresTableProvider, Table, DATABASE_URL, MAX_DB_CONS,
+};
+
+use std::sync::Arc;
+
+use color_eyre::Result;
+use datafusion::arrow::array::{AsArray, RecordBatch};
+use datafusion::arrow::datatypes::{Int32Type, Int64Type};
+use datafusion::functions_aggregate::expr_fn;
+use datafusion::prelude::*;
+use datafusion::scalar::ScalarValue;
+use secrecy::ExposeSecret;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::Executor;
+
+async fn test_ctx(db: &PostgresDb) -> Result<SessionContext> {
+    let ctx = SessionContext::new();
+    for table in [Table::FlightsTable, Table::TicketFlightsTable] {
+        let provider = PostgresTableProvider::try_new(db.as_ref(), table).await?;
+        ctx.register_table(table.as_ref(), Arc::new(provider))?;
+    }
+    let worker = GenericTableWorker::new("aircrafts").with_schema_name("bookings");
+    let provider = PostgresTableProvider::try_new_generic(db.as_ref(), &worker).await?;
+    ctx.register_table("aircrafts", Arc::new(provider.with_batch_size(4)))?;
+    Ok(ctx)
+}
+
+fn count(batches: &[RecordBatch]) -> i64 {
+    batches[0].column(0).as_primitive::<Int64Type>().value(0)
+}
+
+#[tokio::test]
+async fn test_provider_join() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let ctx = test_ctx(&db).await?;
+    let query =
+        "select count(*) from flights f join ticket_flights tf on f.flight_id = tf.flight_id \
+        where f.status = 'Arrived'";
+    let expected: i64 = sqlx::query_scalar(query).fetch_one(db.as_ref()).await?;
+    let batches = ctx.sql(query).await?.collect().await?;
+    assert_eq!(count(&batches), expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_provider_filter_limit() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let ctx = test_ctx(&db).await?;
+    let expected: Vec<i32> = sqlx::query_scalar(
+        "select flight_id from flights where status = 'Scheduled' order by flight_id limit 3",
+    )
+    .fetch_all(db.as_ref())
+    .await?;
+    let df = ctx
+        .sql("select flight_id, status from flights where status = 'Scheduled' order by flight_id limit 3")
+        .await?;
+    let batches = df.collect().await?;
+    let flight_ids = batches
+        .iter()
+        .flat_map(|batch| {
+            batch
+                .column(0)
+                .as_primitive::<Int32Type>()
+                .values()
+                .to_vec()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(flight_ids, expected);
+    assert_eq!(batches[0].num_columns(), 2);
+
+    let batches = ctx
+        .sql("select * from flights limit 2")
+        .await?
+        .collect()
+        .await?;
+    let rows = batches.iter().map(RecordBatch::num_rows).sum::<usize>();
+    assert_eq!(rows, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_provider_generic() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let ctx = test_ctx(&db).await?;
+    let expected: i64 =
+        sqlx::query_scalar("select count(*) from bookings.aircrafts where range > 5000")
+            .fetch_one(db.as_ref())
+            .await?;
+    let batches = ctx
+        .sql("select count(*) from aircrafts where range > 5000")
+        .await?
+        .collect()
+        .await?;
+    assert_eq!(count(&batches), expected);
+
+    let batches = ctx
+        .sql("select model from aircrafts order by aircraft_code")
+        .await?
+        .collect()
+        .await?;
+    assert_eq!(batches[0].num_columns(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_provider_timestamp_filter() -> Result<()> {
+    // the demo database compares timestamps in Moscow time, sqlx connects in UTC
+    let pool = PgPoolOptions::new()
+        .max_connections(MAX_DB_CONS)
+        .after_connect(|conn, _| {
+            Box::pin(async move {
+                conn.execute("set timezone = 'Europe/Moscow'").await?;
+                Ok(())
+            })
+        })
+        .connect(DATABASE_URL.expose_secret())
+        .await?;
+    let ctx = SessionContext::new();
+    let provider = PostgresTableProvider::try_new(&pool, Table::FlightsTable).await?;
+    ctx.register_table("flights", Arc::new(provider))?;
+
+    let expected: i64 = sqlx::query_scalar(
+        "select count(*) from flights where scheduled_departure < '2017-06-14 06:00:00+00'",
+    )
+    .fetch_one(&pool)
+    .await?;
+    // 2017-06-14 06:00:00 UTC
+    let timestamp =
+        ScalarValue::TimestampMicrosecond(Some(1_497_420_000_000_000), Some("+00:00".into()));
+    let batches = ctx
+        .table("flights")
+        .await?
+        .filter(col("scheduled_departure").lt(lit(timestamp)))?
+        .aggregate(vec![], vec![expr_fn::count(col("flight_id"))])?
+        .collect()
+        .await?;
+    assert_eq!(count(&batches), expected);
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use demodb_to_datalake::{
+    prepare_query_with_policy, PostgresDb, QueryParserError, QueryPolicy, DATABASE_URL, MAX_DB_CONS,
+};
+
+use std::time::Duration;
+
+use color_eyre::Result;
+use secrecy::ExposeSecret;
+
+#[tokio::test]
+async fn test_query_policy_statement_timeout() -> Result<()> {
+    let policy = QueryPolicy::default()
+        .with_denied_functions(&[])
+        .with_statement_timeout(Duration::from_millis(100));
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .with_query_policy(&policy)
+        .build()
+        .await?;
+    let query = "select pg_sleep(2) from flights";
+    assert_eq!(
+        prepare_query_with_policy(query, &QueryPolicy::default()),
+        Err(QueryParserError::FunctionNotAllowed("pg_sleep".to_string()))
+    );
+    let query = prepare_query_with_policy(query, &policy)?;
+    let res = sqlx::query(&query).fetch_all(db.as_ref()).await;
+    let Err(sqlx::Error::Database(e)) = res else {
+        panic!("expected the statement to time out");
+    };
+    // query_canceled
+    assert_eq!(e.code().as_deref(), Some("57014"));
+    Ok(())
+}
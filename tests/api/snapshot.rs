@@ -0,0 +1,71 @@
+use demodb_to_datalake::{PostgresDb, Table, DATABASE_URL, DEFAULT_BATCH_SIZE, MAX_DB_CONS};
+
+use color_eyre::Result;
+use futures_util::TryStreamExt;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+async fn count_rows(pool: &PgPool, table: &str) -> Result<i64> {
+    let query = format!("select count(*) from {}", table);
+    Ok(sqlx::query_scalar::<_, i64>(&query).fetch_one(pool).await?)
+}
+
+#[tokio::test]
+async fn test_snapshot_hides_later_writes() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let table = format!("snapshot_probe_{}", std::process::id());
+    sqlx::query(&format!("create table {} (id int4)", table))
+        .execute(db.as_ref())
+        .await?;
+    sqlx::query(&format!("insert into {} values (1)", table))
+        .execute(db.as_ref())
+        .await?;
+
+    let snapshot = db.snapshot().await?;
+    sqlx::query(&format!("insert into {} values (2)", table))
+        .execute(db.as_ref())
+        .await?;
+    // several connections of the snapshot pool see the same point in time
+    let counts =
+        futures_util::future::try_join_all((0..4).map(|_| count_rows(snapshot.as_ref(), &table)))
+            .await?;
+    let current = count_rows(db.as_ref(), &table).await?;
+    snapshot.close().await?;
+    sqlx::query(&format!("drop table {}", table))
+        .execute(db.as_ref())
+        .await?;
+
+    assert_eq!(counts, vec![1; 4]);
+    assert_eq!(current, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_snapshot_export() -> Result<()> {
+    let db = PostgresDb::builder()
+        .with_url(DATABASE_URL.expose_secret())
+        .with_max_cons(MAX_DB_CONS)
+        .build()
+        .await?;
+    let snapshot = db.snapshot().await?;
+    assert!(!snapshot.snapshot_id().is_empty());
+
+    let table = Table::TicketFlightsTable;
+    let query = format!("select * from {}", table.as_ref());
+    let stream = table
+        .to_worker()
+        .export_table_to_stream(snapshot.as_ref(), &query, DEFAULT_BATCH_SIZE)
+        .await?;
+    let batches = stream.try_collect::<Vec<_>>().await?;
+    let rows = batches.iter().map(|b| b.num_rows()).sum::<usize>();
+    assert_eq!(
+        rows as i64,
+        count_rows(snapshot.as_ref(), table.as_ref()).await?
+    );
+    snapshot.close().await?;
+    Ok(())
+}
@@ -2,6 +2,7 @@ use demodb_to_datalake::{PostgresDb, Table, DATABASE_URL, MAX_DB_CONS};
 
 use color_eyre::Result;
 use datafusion::{assert_batches_eq, prelude::*};
+use futures_util::TryStreamExt;
 use secrecy::ExposeSecret;
 
 const TABLE: Table = Table::TicketsTable;
@@ -103,25 +104,44 @@ mod dyn_stat {
             .unwrap();
         assert_batches_eq!(
             &[
-                  "+---------------+----------+--------------+----------------------+---------------------------------------------------------------------------+",
-                  "| ticket_no     | book_ref | passenger_id | passenger_name       | contact_data                                                              |",
-                  "+---------------+----------+--------------+----------------------+---------------------------------------------------------------------------+",
-                r#"| 0005432000284 | 1A40A1   | 4030 855525  | MIKHAIL SEMENOV      | {"email":null,"phone":"+70110137563"}                                     |"#,
-                r#"| 0005432000285 | 13736D   | 8360 311602  | ELENA ZAKHAROVA      | {"email":null,"phone":"+70670013989"}                                     |"#,
-                r#"| 0005432000286 | DC89BC   | 4510 377533  | ILYA PAVLOV          | {"email":null,"phone":"+70624013335"}                                     |"#,
-                r#"| 0005432000287 | CDE08B   | 5952 253588  | ELENA BELOVA         | {"email":"e.belova.07121974@postgrespro.ru","phone":"+70340423946"}       |"#,
-                r#"| 0005432000288 | BEFB90   | 4313 788533  | VYACHESLAV IVANOV    | {"email":"vyacheslav-ivanov051968@postgrespro.ru","phone":"+70417078841"} |"#,
-                r#"| 0005432000289 | A903E4   | 2742 028983  | NATALIYA NESTEROVA   | {"email":null,"phone":"+70031478265"}                                     |"#,
-                r#"| 0005432000290 | CC77B6   | 9873 744760  | ALEKSANDRA ARKHIPOVA | {"email":"arkhipovaa-1980@postgrespro.ru","phone":"+70185914840"}         |"#,
-                r#"| 0005432000291 | D530F6   | 2695 977692  | EVGENIY SERGEEV      | {"email":null,"phone":"+70007395677"}                                     |"#,
-                r#"| 0005432000292 | F26006   | 2512 253082  | TATYANA ZHUKOVA      | {"email":"zhukova_tatyana_121964@postgrespro.ru","phone":"+70505293692"}  |"#,
-                r#"| 0005432000293 | 739B4E   | 5763 638275  | ILYA KRASNOV         | {"email":"ilya_krasnov_081985@postgrespro.ru","phone":"+70669365996"}     |"#,
-                  "+---------------+----------+--------------+----------------------+---------------------------------------------------------------------------+",
+                "+---------------+----------+--------------+----------------------+----------------------------------------------------------------------+",
+                "| ticket_no     | book_ref | passenger_id | passenger_name       | contact_data                                                         |",
+                "+---------------+----------+--------------+----------------------+----------------------------------------------------------------------+",
+                "| 0005432000284 | 1A40A1   | 4030 855525  | MIKHAIL SEMENOV      | {email: , phone: +70110137563}                                       |",
+                "| 0005432000285 | 13736D   | 8360 311602  | ELENA ZAKHAROVA      | {email: , phone: +70670013989}                                       |",
+                "| 0005432000286 | DC89BC   | 4510 377533  | ILYA PAVLOV          | {email: , phone: +70624013335}                                       |",
+                "| 0005432000287 | CDE08B   | 5952 253588  | ELENA BELOVA         | {email: e.belova.07121974@postgrespro.ru, phone: +70340423946}       |",
+                "| 0005432000288 | BEFB90   | 4313 788533  | VYACHESLAV IVANOV    | {email: vyacheslav-ivanov051968@postgrespro.ru, phone: +70417078841} |",
+                "| 0005432000289 | A903E4   | 2742 028983  | NATALIYA NESTEROVA   | {email: , phone: +70031478265}                                       |",
+                "| 0005432000290 | CC77B6   | 9873 744760  | ALEKSANDRA ARKHIPOVA | {email: arkhipovaa-1980@postgrespro.ru, phone: +70185914840}         |",
+                "| 0005432000291 | D530F6   | 2695 977692  | EVGENIY SERGEEV      | {email: , phone: +70007395677}                                       |",
+                "| 0005432000292 | F26006   | 2512 253082  | TATYANA ZHUKOVA      | {email: zhukova_tatyana_121964@postgrespro.ru, phone: +70505293692}  |",
+                "| 0005432000293 | 739B4E   | 5763 638275  | ILYA KRASNOV         | {email: ilya_krasnov_081985@postgrespro.ru, phone: +70669365996}     |",
+                "+---------------+----------+--------------+----------------------+----------------------------------------------------------------------+",
             ],
             &rows.collect().await.unwrap()
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_tickets_stream() -> Result<()> {
+        let db = PostgresDb::builder()
+            .with_url(DATABASE_URL.expose_secret())
+            .with_max_cons(MAX_DB_CONS)
+            .build()
+            .await?;
+        let table = TABLE;
+        let worker = table.to_worker();
+        let query = format!("select * from {}", table.as_ref());
+        let stream = worker.query_table_to_stream(db.as_ref(), &query, 4).await?;
+        let batches = stream.try_collect::<Vec<_>>().await?;
+
+        assert_eq!(batches.len(), 3); // batches count
+        assert!(batches.iter().all(|batch| batch.num_rows() <= 4));
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10); // rows count
+        Ok(())
+    }
 }
 
 mod stat_trait {
@@ -219,23 +239,43 @@ mod stat_trait {
             .unwrap();
         assert_batches_eq!(
             &[
-                  "+---------------+----------+--------------+----------------------+---------------------------------------------------------------------------+",
-                  "| ticket_no     | book_ref | passenger_id | passenger_name       | contact_data                                                              |",
-                  "+---------------+----------+--------------+----------------------+---------------------------------------------------------------------------+",
-                r#"| 0005432000284 | 1A40A1   | 4030 855525  | MIKHAIL SEMENOV      | {"email":null,"phone":"+70110137563"}                                     |"#,
-                r#"| 0005432000285 | 13736D   | 8360 311602  | ELENA ZAKHAROVA      | {"email":null,"phone":"+70670013989"}                                     |"#,
-                r#"| 0005432000286 | DC89BC   | 4510 377533  | ILYA PAVLOV          | {"email":null,"phone":"+70624013335"}                                     |"#,
-                r#"| 0005432000287 | CDE08B   | 5952 253588  | ELENA BELOVA         | {"email":"e.belova.07121974@postgrespro.ru","phone":"+70340423946"}       |"#,
-                r#"| 0005432000288 | BEFB90   | 4313 788533  | VYACHESLAV IVANOV    | {"email":"vyacheslav-ivanov051968@postgrespro.ru","phone":"+70417078841"} |"#,
-                r#"| 0005432000289 | A903E4   | 2742 028983  | NATALIYA NESTEROVA   | {"email":null,"phone":"+70031478265"}                                     |"#,
-                r#"| 0005432000290 | CC77B6   | 9873 744760  | ALEKSANDRA ARKHIPOVA | {"email":"arkhipovaa-1980@postgrespro.ru","phone":"+70185914840"}         |"#,
-                r#"| 0005432000291 | D530F6   | 2695 977692  | EVGENIY SERGEEV      | {"email":null,"phone":"+70007395677"}                                     |"#,
-                r#"| 0005432000292 | F26006   | 2512 253082  | TATYANA ZHUKOVA      | {"email":"zhukova_tatyana_121964@postgrespro.ru","phone":"+70505293692"}  |"#,
-                r#"| 0005432000293 | 739B4E   | 5763 638275  | ILYA KRASNOV         | {"email":"ilya_krasnov_081985@postgrespro.ru","phone":"+70669365996"}     |"#,
-                  "+---------------+----------+--------------+----------------------+---------------------------------------------------------------------------+",
+                "+---------------+----------+--------------+----------------------+----------------------------------------------------------------------+",
+                "| ticket_no     | book_ref | passenger_id | passenger_name       | contact_data                                                         |",
+                "+---------------+----------+--------------+----------------------+----------------------------------------------------------------------+",
+                "| 0005432000284 | 1A40A1   | 4030 855525  | MIKHAIL SEMENOV      | {email: , phone: +70110137563}                                       |",
+                "| 0005432000285 | 13736D   | 8360 311602  | ELENA ZAKHAROVA      | {email: , phone: +70670013989}                                       |",
+                "| 0005432000286 | DC89BC   | 4510 377533  | ILYA PAVLOV          | {email: , phone: +70624013335}                                       |",
+                "| 0005432000287 | CDE08B   | 5952 253588  | ELENA BELOVA         | {email: e.belova.07121974@postgrespro.ru, phone: +70340423946}       |",
+                "| 0005432000288 | BEFB90   | 4313 788533  | VYACHESLAV IVANOV    | {email: vyacheslav-ivanov051968@postgrespro.ru, phone: +70417078841} |",
+                "| 0005432000289 | A903E4   | 2742 028983  | NATALIYA NESTEROVA   | {email: , phone: +70031478265}                                       |",
+                "| 0005432000290 | CC77B6   | 9873 744760  | ALEKSANDRA ARKHIPOVA | {email: arkhipovaa-1980@postgrespro.ru, phone: +70185914840}         |",
+                "| 0005432000291 | D530F6   | 2695 977692  | EVGENIY SERGEEV      | {email: , phone: +70007395677}                                       |",
+                "| 0005432000292 | F26006   | 2512 253082  | TATYANA ZHUKOVA      | {email: zhukova_tatyana_121964@postgrespro.ru, phone: +70505293692}  |",
+                "| 0005432000293 | 739B4E   | 5763 638275  | ILYA KRASNOV         | {email: ilya_krasnov_081985@postgrespro.ru, phone: +70669365996}     |",
+                "+---------------+----------+--------------+----------------------+----------------------------------------------------------------------+",
             ],
             &rows.collect().await.unwrap()
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_tickets_stream() -> Result<()> {
+        let db = PostgresDb::builder()
+            .with_url(DATABASE_URL.expose_secret())
+            .with_max_cons(MAX_DB_CONS)
+            .build()
+            .await?;
+        let table = TABLE;
+        let query = format!("select * from {}", table.as_ref());
+        let stream = table
+            .run_query_table_to_stream(db.as_ref(), &query, 4)
+            .await?;
+        let batches = stream.try_collect::<Vec<_>>().await?;
+
+        assert_eq!(batches.len(), 3); // batches count
+        assert!(batches.iter().all(|batch| batch.num_rows() <= 4));
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 10); // rows count
+        Ok(())
+    }
 }